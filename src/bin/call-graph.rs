@@ -0,0 +1,125 @@
+//! Example demonstrating how to build the call graph of all functions in a project.
+//!
+//! The graph is written in the DOT format of Graphviz, to `--output` if given,
+//! otherwise stdout. With `--no-lsp` the graph is approximated by matching call
+//! names to declarations with tree-sitter.
+//!
+//! Usage: cargo run --bin call-graph -- <project_path> --language <language> | dot -Tsvg > calls.svg
+
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tree_sitter_lsp_experiment::call_graph::{CallGraph, build_tree_sitter_call_graph, item_key};
+use tree_sitter_lsp_experiment::file_search::workspace_root;
+use tree_sitter_lsp_experiment::lsp::CallableSymbolFilter;
+use tree_sitter_lsp_experiment::{
+    Args, FileSearchConfig, GoLang, Language, LspServer, LspServerConfig, PhpLang, PythonLang,
+    RetryPolicy, RustLang, SwiftLang, TypeScriptLang, init_tracing,
+};
+
+/// How many calls away from each function the graph is followed
+const MAX_DEPTH: usize = 3;
+
+fn build_project_call_graph<L: Language>(
+    language: L,
+    project_path: &Path,
+    config: &FileSearchConfig,
+    lsp_config: LspServerConfig,
+) -> Result<CallGraph> {
+    let mut graph = CallGraph::default();
+    let matching_files = config.find_language_files(project_path, language)?;
+    if matching_files.is_empty() {
+        tracing::warn!("No matching files found in {}", project_path.display());
+        return Ok(graph);
+    }
+    tracing::info!("Found {} matching files", matching_files.len());
+
+    if lsp_config.disabled {
+        tracing::info!("LSP features are disabled, matching calls to declarations by name");
+        return build_tree_sitter_call_graph(&matching_files, language);
+    }
+
+    let mut lsp_server = LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    )?;
+
+    for file_path in &matching_files {
+        let absolute_path = file_path.canonicalize()?;
+        let file_content = std::fs::read_to_string(&absolute_path)?;
+        lsp_server.open_file(&absolute_path, &file_content)?;
+
+        let callable_symbols =
+            lsp_server.get_callable_symbols(&absolute_path, &CallableSymbolFilter::default())?;
+
+        for (i, symbol) in callable_symbols.iter().enumerate() {
+            // The server might not have finished loading the file for the first symbol
+            let retry = if i == 0 {
+                RetryPolicy::new(5, Duration::from_millis(100))
+            } else {
+                RetryPolicy::NONE
+            };
+            let root = match lsp_server.prepare_call_hierarchy(
+                &absolute_path,
+                symbol.selection_range.start,
+                retry,
+            ) {
+                Ok(Some(root)) => root,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to prepare call hierarchy for {}: {}",
+                        symbol.name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            // Functions reached from an earlier root have already been expanded
+            if graph.edges.contains_key(&item_key(&root)) {
+                continue;
+            }
+            match lsp_server.build_call_graph(root, MAX_DEPTH) {
+                Ok(subgraph) => graph.merge(subgraph),
+                Err(e) => tracing::warn!("Failed to build call graph for {}: {}", symbol.name, e),
+            }
+        }
+
+        lsp_server.close_file(&absolute_path)?;
+    }
+
+    Ok(graph)
+}
+
+fn main() -> Result<()> {
+    // Parse and validate command-line arguments
+    let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
+    let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+
+    let graph = match args.language() {
+        "rust" => build_project_call_graph(RustLang, &args.project_path, &config, lsp_config)?,
+        "python" => build_project_call_graph(PythonLang, &args.project_path, &config, lsp_config)?,
+        "typescript" => {
+            build_project_call_graph(TypeScriptLang, &args.project_path, &config, lsp_config)?
+        }
+        "go" => build_project_call_graph(GoLang, &args.project_path, &config, lsp_config)?,
+        "swift" => build_project_call_graph(SwiftLang, &args.project_path, &config, lsp_config)?,
+        "php" => build_project_call_graph(PhpLang, &args.project_path, &config, lsp_config)?,
+        _ => unreachable!(),
+    };
+    tracing::info!(
+        "Built a call graph with {} functions and {} edges",
+        graph.items.len(),
+        graph.edges.values().map(Vec::len).sum::<usize>()
+    );
+
+    let mut writer = args.output_writer()?;
+    write!(writer, "{}", graph.to_dot())?;
+    writer.flush()?;
+
+    Ok(())
+}
@@ -3,21 +3,21 @@
 //! Usage: cargo run --bin call-hierachy -- <project_path> --language <language>
 
 use anyhow::Result;
-use lsp_types::{
-    CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
-    TextDocumentPositionParams,
-    request::{CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare},
-};
-use lsp_types::{DocumentSymbol, SymbolKind};
+use lsp_types::{DocumentSymbol, PositionEncodingKind};
 use serde::Serialize;
+use std::io::{self, Write};
 use std::{path::Path, time::Duration};
-use tree_sitter_lsp_experiment::location::print_highlighted_range;
-use tree_sitter_lsp_experiment::{
-    Args, FileSearchConfig, GoLang, Language, LspServer, PythonLang, RustLang, SwiftLang,
-    TypeScriptLang,
-};
+use tree_sitter_lsp_experiment::file_search::workspace_root;
+use tree_sitter_lsp_experiment::location::{highlight_range, lsp_to_display};
+use tree_sitter_lsp_experiment::lsp::{CallableSymbolFilter, callable_symbols};
+use tree_sitter_lsp_experiment::parser::parse_file_content;
+use tree_sitter_lsp_experiment::report::{ReferenceRecord, Render, write_csv};
+use tree_sitter_lsp_experiment::timing::{Phase, TimingReport};
+use tree_sitter_lsp_experiment::uri::display_uri;
 use tree_sitter_lsp_experiment::{
-    lsp::text_document_identifier_from_path, parser::parse_file_content,
+    Args, ErrorPolicy, FileSearchConfig, GoLang, Language, LspServer, LspServerConfig,
+    OutputFormat, PhpLang, PythonLang, Reporter, RetryPolicy, RustLang, SwiftLang, TypeScriptLang,
+    init_tracing,
 };
 
 /// Represents a single call and its target
@@ -25,124 +25,144 @@ use tree_sitter_lsp_experiment::{
 struct CallInfo {
     /// The file path of the call
     call_file: String,
-    /// The place where the call is made
-    call_range: lsp_types::Range,
+    /// The place where the call is made, if the server reported it
+    call_range: Option<lsp_types::Range>,
     /// The name of the function/method being called
     target_name: String,
     /// The file path of the target
     target_file: String,
     /// The line number where the target is defined
-    target_line: u32,
+    target_line: usize,
+    /// The column where the name of the target starts
+    target_column: usize,
 }
 
 impl CallInfo {
-    pub fn pretty_print(&self, file_lines: &[&str]) {
-        print_highlighted_range(file_lines, self.call_range);
-        println!(
-            " -> {} ({}:{})",
-            self.target_name, self.target_file, self.target_line
-        );
+    /// Creates the call info for an outgoing call made from `call_file`
+    fn from_outgoing(call_file: &Path, call: &lsp_types::CallHierarchyOutgoingCall) -> CallInfo {
+        let target = lsp_to_display(call.to.selection_range.start);
+        CallInfo {
+            call_file: call_file.display().to_string(),
+            call_range: call.from_ranges.first().copied(),
+            target_name: call.to.name.clone(),
+            target_file: display_uri(&call.to.uri),
+            target_line: target.line,
+            target_column: target.column,
+        }
     }
-}
 
-fn extract_call_hierachy<L: Language>(
-    language: L,
-    project_path: &Path,
-    config: &FileSearchConfig,
-) -> Result<Vec<CallInfo>> {
-    // Find all matching files
-    let matching_files = config.find_language_files(project_path, language)?;
-
-    if matching_files.is_empty() {
-        println!("No matching files found in {}", project_path.display());
-        return Ok(Vec::new());
+    pub fn pretty_print(
+        &self,
+        file_lines: &[&str],
+        encoding: &PositionEncodingKind,
+        reporter: Reporter,
+    ) {
+        let target = format!(
+            "-> {} ({}:{})",
+            self.target_name, self.target_file, self.target_line
+        );
+        match self
+            .call_range
+            .and_then(|range| highlight_range(file_lines, range, encoding))
+        {
+            Some([line, underline]) => {
+                reporter.detail(format_args!("    {}", line));
+                reporter.detail(format_args!("    {} {}", underline, target));
+            }
+            None => reporter.detail(format_args!("    from unknown line {}", target)),
+        }
     }
 
-    println!("Found {} matching files", matching_files.len());
-    println!("{:?}", matching_files);
-
-    extract_call_hierachy_for_files(language, project_path, &matching_files)
-}
-
-// Recursively collect all callable symbols (functions/methods) including nested ones
-// Note: for flat symbols, there won't be any children
-fn collect_symbols_with_calls<'a>(
-    symbols: &'a [lsp_types::DocumentSymbol],
-    result: &mut Vec<&'a lsp_types::DocumentSymbol>,
-) {
-    for symbol in symbols {
-        if matches!(
-            symbol.kind,
-            SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::CONSTRUCTOR // | SymbolKind::PROPERTY
-                                                                                // | SymbolKind::FIELD
-                                                                                // | SymbolKind::ENUM_MEMBER
-        ) {
-            result.push(symbol);
-        }
-        // Recursively process children, skip interfaces as these rarely contain calls
-        if !matches!(symbol.kind, SymbolKind::INTERFACE)
-            && let Some(ref children) = symbol.children
-        {
-            collect_symbols_with_calls(children, result);
+    /// Returns the call as a reference to its target, with an unknown
+    /// position if the server did not report where the call is made
+    fn to_record(&self) -> ReferenceRecord {
+        let call_position = self.call_range.map(|range| lsp_to_display(range.start));
+        ReferenceRecord {
+            symbol: self.target_name.clone(),
+            kind: "call".to_string(),
+            ref_file: self.call_file.clone(),
+            ref_line: call_position.map_or(0, |position| position.line),
+            ref_col: call_position.map_or(0, |position| position.column),
+            def_file: self.target_file.clone(),
+            def_line: self.target_line,
+            def_col: self.target_column,
         }
     }
 }
 
-fn get_symbols_with_calls(symbols: &[lsp_types::DocumentSymbol]) -> Vec<&DocumentSymbol> {
-    let mut symbols_with_calls = Vec::new();
-    collect_symbols_with_calls(symbols, &mut symbols_with_calls);
-    symbols_with_calls
+/// The outgoing calls of all callable symbols in a project
+struct CallHierarchyReport {
+    calls: Vec<CallInfo>,
 }
 
-fn prepare_call_hierarchy(
-    lsp_server: &mut LspServer<impl Language>,
-    absolute_path: &Path,
-    symbol: &DocumentSymbol,
-    enable_retries: bool,
-) -> Result<Option<lsp_types::CallHierarchyItem>> {
-    for retries in 0..6 {
-        if retries > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(100u64 * retries));
-        }
-        let before_prepare = std::time::Instant::now();
-
-        let prepare_params = CallHierarchyPrepareParams {
-            text_document_position_params: TextDocumentPositionParams {
-                text_document: text_document_identifier_from_path(absolute_path)?,
-                position: symbol.selection_range.start,
-            },
-            work_done_progress_params: Default::default(),
-        };
-        let prepare_response = lsp_server.request::<CallHierarchyPrepare>(prepare_params);
-        let prepare_elapsed = before_prepare.elapsed();
-
-        match prepare_response {
-            Ok(Some(items)) => match items.into_iter().next() {
-                Some(item) => {
-                    println!("  Prepared call hierarchy ({:?})", prepare_elapsed);
-                    return Ok(Some(item));
-                }
-                None => {
-                    println!("  No call hierarchy items found ({:?})", prepare_elapsed);
+/// Written as one line per call, `CallInfo` JSON or `ReferenceRecord` CSV
+impl Render for CallHierarchyReport {
+    fn render(&self, format: OutputFormat, mut writer: &mut dyn Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                for call in &self.calls {
+                    let call_position = match call.call_range {
+                        Some(range) => lsp_to_display(range.start).to_string(),
+                        None => "unknown".to_string(),
+                    };
+                    writeln!(
+                        writer,
+                        "{}:{} -> {} ({}:{}:{})",
+                        call.call_file,
+                        call_position,
+                        call.target_name,
+                        call.target_file,
+                        call.target_line,
+                        call.target_column
+                    )?;
                 }
-            },
-            Ok(None) => {
-                println!("  No call hierarchy available ({:?})", prepare_elapsed);
+                writer.flush()
             }
-            Err(e) => {
-                tracing::warn!(
-                    "Failed to prepare call hierarchy ({:?}): {}",
-                    prepare_elapsed,
-                    e
-                );
-                return Err(e);
+            OutputFormat::Json => {
+                for call in &self.calls {
+                    serde_json::to_writer(&mut *writer, call)?;
+                    writer.write_all(b"\n")?;
+                }
+                writer.flush()
             }
-        };
-        if !enable_retries {
-            return Ok(None);
+            OutputFormat::Csv => write_csv(&mut writer, self.calls.iter().map(CallInfo::to_record)),
         }
     }
-    Ok(None)
+}
+
+fn extract_call_hierachy<L: Language>(
+    language: L,
+    project_path: &Path,
+    config: &FileSearchConfig,
+    lsp_config: LspServerConfig,
+    reporter: Reporter,
+    error_policy: ErrorPolicy,
+) -> Result<(Vec<CallInfo>, TimingReport)> {
+    // Find all matching files
+    let matching_files = config.find_language_files(project_path, language)?;
+
+    if matching_files.is_empty() {
+        reporter.status(format_args!(
+            "No matching files found in {}",
+            project_path.display()
+        ));
+        return Ok((Vec::new(), TimingReport::default()));
+    }
+
+    reporter.status(format_args!(
+        "Found {} matching files",
+        matching_files.len()
+    ));
+    reporter.detail(format_args!("{:?}", matching_files));
+
+    extract_call_hierachy_for_files(
+        language,
+        project_path,
+        &matching_files,
+        lsp_config,
+        reporter,
+        error_policy,
+    )
 }
 
 struct CallHierarchyResult {
@@ -156,39 +176,44 @@ fn get_call_hierarchy(
     absolute_path: &Path,
     symbol: &DocumentSymbol,
     enable_retries: bool,
+    reporter: Reporter,
+    timings: &mut TimingReport,
 ) -> Result<Option<CallHierarchyResult>> {
     let before_prepare = std::time::Instant::now();
 
     // Prepare call hierarchy
-    let Some(item) = prepare_call_hierarchy(lsp_server, absolute_path, symbol, enable_retries)?
+    let retry = if enable_retries {
+        RetryPolicy::new(5, Duration::from_millis(100))
+    } else {
+        RetryPolicy::NONE
+    };
+    let Some(item) =
+        lsp_server.prepare_call_hierarchy(absolute_path, symbol.selection_range.start, retry)?
     else {
-        println!(
+        reporter.detail(format_args!(
             "  No call hierarchy items found after {:?} (including retries)",
             before_prepare.elapsed()
-        );
+        ));
         return Ok(None);
     };
+    timings.record(absolute_path, Phase::Prepare, before_prepare.elapsed());
+    reporter.detail(format_args!(
+        "  Prepared call hierarchy ({:?})",
+        before_prepare.elapsed()
+    ));
 
     let before_incoming = std::time::Instant::now();
     // Get incoming calls
-    let incoming_params = CallHierarchyIncomingCallsParams {
-        item: item.clone(),
-        work_done_progress_params: Default::default(),
-        partial_result_params: Default::default(),
-    };
-    let incoming = match lsp_server.request::<CallHierarchyIncomingCalls>(incoming_params) {
-        Ok(Some(incoming)) => {
-            println!(
+    let incoming = match lsp_server.incoming_calls(item.clone()) {
+        Ok(incoming) => {
+            timings.record(absolute_path, Phase::Incoming, before_incoming.elapsed());
+            reporter.detail(format_args!(
                 "  Incoming calls after {:?} ({}):",
                 before_incoming.elapsed(),
                 incoming.len()
-            );
+            ));
             incoming
         }
-        Ok(None) => {
-            println!("  Incoming calls: 0");
-            Vec::new()
-        }
         Err(e) => {
             tracing::warn!("  Failed to get incoming calls: {}", e);
             Vec::new()
@@ -197,24 +222,16 @@ fn get_call_hierarchy(
 
     let before_outgoing = std::time::Instant::now();
     // Get outgoing calls
-    let outgoing_params = CallHierarchyOutgoingCallsParams {
-        item: item.clone(),
-        work_done_progress_params: Default::default(),
-        partial_result_params: Default::default(),
-    };
-    let outgoing = match lsp_server.request::<CallHierarchyOutgoingCalls>(outgoing_params) {
-        Ok(Some(outgoing)) => {
-            println!(
+    let outgoing = match lsp_server.outgoing_calls(item) {
+        Ok(outgoing) => {
+            timings.record(absolute_path, Phase::Outgoing, before_outgoing.elapsed());
+            reporter.detail(format_args!(
                 "  Outgoing calls after {:?} ({}):",
                 before_outgoing.elapsed(),
                 outgoing.len()
-            );
+            ));
             outgoing
         }
-        Ok(None) => {
-            println!("  Outgoing calls: 0");
-            Vec::new()
-        }
         Err(e) => {
             tracing::warn!("  Failed to get outgoing calls: {}", e);
             Vec::new()
@@ -228,7 +245,10 @@ fn extract_call_hierachy_for_files<L: Language>(
     language: L,
     project_path: &Path,
     files: &[std::path::PathBuf],
-) -> Result<Vec<CallInfo>> {
+    lsp_config: LspServerConfig,
+    reporter: Reporter,
+    error_policy: ErrorPolicy,
+) -> Result<(Vec<CallInfo>, TimingReport)> {
     let mut total_calls = 0;
     let mut total_incoming_calls = 0;
     let mut total_symbols = 0;
@@ -236,52 +256,45 @@ fn extract_call_hierachy_for_files<L: Language>(
 
     // Start and initialize LSP server
     tracing::info!("Starting LSP server for {}...", language);
-    let mut lsp_server = LspServer::start_and_init(language, project_path.to_path_buf())?;
+    let mut lsp_server = LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    )?;
 
-    let mut durations = Vec::<(&str, Duration)>::new();
+    let mut timings = TimingReport::default();
 
-    // NOTE: It seems that for some LSP servers, giving them a bit of time to
-    // start makes it possible for them to resolve more call hierarchy requests.
-    std::thread::sleep(std::time::Duration::from_millis(10_000));
+    // NOTE: It seems that for some LSP servers, giving them time to index the
+    // project makes it possible for them to resolve more call hierarchy requests.
+    lsp_server.wait_for_indexing()?;
 
     let start_time = std::time::Instant::now();
     // Process each file
     for (index, file_path) in files.iter().enumerate() {
-        // Skip if file name contains spaces
-        if file_path.display().to_string().contains(' ') {
-            println!(
-                "\n[Skipping {}/{}] File name contains spaces: {}",
-                index + 1,
-                files.len(),
-                file_path.display()
-            );
-            continue;
-        }
-        println!("\n{}", "=".repeat(80));
-        println!(
+        reporter.detail(format_args!("\n{}", "=".repeat(80)));
+        reporter.detail(format_args!(
             "[{}/{}] Processing: {}",
             index + 1,
             files.len(),
             file_path.display()
-        );
-        println!("{}", "=".repeat(80));
+        ));
+        reporter.detail(format_args!("{}", "=".repeat(80)));
 
         // Get absolute path
-        let absolute_path = match file_path.canonicalize() {
-            Ok(path) => path,
-            Err(e) => {
-                tracing::warn!("Failed to canonicalize path {}: {}", file_path.display(), e);
-                continue;
-            }
+        let Some(absolute_path) = error_policy.handle(file_path.canonicalize(), || {
+            format!("Failed to canonicalize path {}", file_path.display())
+        })?
+        else {
+            continue;
         };
 
         // Read file content
-        let file_content = match std::fs::read_to_string(&absolute_path) {
-            Ok(content) => content,
-            Err(e) => {
-                tracing::warn!("Failed to read file {}: {}", absolute_path.display(), e);
-                continue;
-            }
+        let Some(file_content) = error_policy
+            .handle(std::fs::read_to_string(&absolute_path), || {
+                format!("Failed to read file {}", absolute_path.display())
+            })?
+        else {
+            continue;
         };
 
         // Split file content into lines for later source code display
@@ -295,97 +308,103 @@ fn extract_call_hierachy_for_files<L: Language>(
         let (symbols, is_flat) = lsp_server.get_document_symbols(&absolute_path)?;
         let symbols_elapsed = before_symbols.elapsed();
 
-        durations.push((file_path.to_str().unwrap_or(""), symbols_elapsed));
+        timings.record(&absolute_path, Phase::Symbols, symbols_elapsed);
 
-        println!(
+        reporter.detail(format_args!(
             "Found {} symbols ({}) in {:.2?}",
             symbols.len(),
             if is_flat { "flat" } else { "nested" },
             symbols_elapsed
-        );
+        ));
 
         // Parse the file with tree sitter, this is merely to compare performance
         let before_parse = std::time::Instant::now();
         let _ = parse_file_content(&file_content, language)?;
-        println!("Parsed file content in {:.2?}", before_parse.elapsed());
+        reporter.detail(format_args!(
+            "Parsed file content in {:.2?}",
+            before_parse.elapsed()
+        ));
 
-        let symbols = get_symbols_with_calls(&symbols);
+        let symbols = callable_symbols(symbols, &CallableSymbolFilter::default());
 
-        println!(
+        reporter.detail(format_args!(
             "\nFound {} callable symbols (functions/methods)",
             symbols.len()
-        );
+        ));
         total_symbols += symbols.len();
 
         // Get call hierarchy information for each callable symbol
         for (i, symbol) in symbols.iter().enumerate() {
-            println!(
+            reporter.detail(format_args!(
                 "\n[{}/{}] [{}/{}] Analyzing calls for: {}",
                 index + 1,
                 files.len(),
                 i + 1,
                 symbols.len(),
                 symbol.name
-            );
+            ));
 
             // Only enable retries for the first two symbols, as the LSP server
             // might not have finished loading the file yet.
             let enable_retries = i < 2;
 
-            let result =
-                match get_call_hierarchy(&mut lsp_server, &absolute_path, symbol, enable_retries) {
-                    Ok(Some(r)) => r,
-                    Ok(None) => {
-                        println!("  No call hierarchy available");
-                        continue;
-                    }
-                    Err(e) => {
-                        println!("  Error: {}", e);
-                        tracing::warn!("Failed to get call hierarchy for {}: {}", symbol.name, e);
-                        continue;
-                    }
-                };
+            let result = match get_call_hierarchy(
+                &mut lsp_server,
+                &absolute_path,
+                symbol,
+                enable_retries,
+                reporter,
+                &mut timings,
+            ) {
+                Ok(Some(r)) => r,
+                Ok(None) => {
+                    reporter.detail(format_args!("  No call hierarchy available"));
+                    continue;
+                }
+                Err(e) => {
+                    reporter.detail(format_args!("  Error: {}", e));
+                    tracing::warn!("Failed to get call hierarchy for {}: {}", symbol.name, e);
+                    continue;
+                }
+            };
 
             // Display incoming calls
             total_incoming_calls += result.incoming.len();
             for call in result.incoming.iter().take(10) {
-                println!(
+                reporter.detail(format_args!(
                     "    <- {} ({}:{})",
                     call.from.name,
-                    call.from.uri.path(),
-                    call.from.selection_range.start.line + 1
-                );
+                    display_uri(&call.from.uri),
+                    lsp_to_display(call.from.selection_range.start).line
+                ));
             }
             if result.incoming.len() > 10 {
-                println!("    ... and {} more", result.incoming.len() - 10);
+                reporter.detail(format_args!(
+                    "    ... and {} more",
+                    result.incoming.len() - 10
+                ));
             }
 
             // Display outgoing calls and collect them
             total_calls += result.outgoing.len();
             for call in result.outgoing.iter() {
-                // Get the line number and source code where the call is made from
-                let Some(range) = call.from_ranges.first() else {
-                    panic!("No from_ranges in outgoing call");
-                };
-
-                let call_info = CallInfo {
-                    call_file: absolute_path.display().to_string(),
-                    call_range: *range,
-                    target_name: call.to.name.clone(),
-                    target_file: call.to.uri.path().to_string(),
-                    target_line: call.to.selection_range.start.line + 1,
-                };
+                let call_info = CallInfo::from_outgoing(&absolute_path, call);
 
                 // Display first 10 for console output
-                if all_calls.len() <= total_calls - result.outgoing.len() + 10 {
-                    call_info.pretty_print(&file_lines);
+                if reporter.shows_details()
+                    && all_calls.len() <= total_calls - result.outgoing.len() + 10
+                {
+                    call_info.pretty_print(&file_lines, &lsp_server.position_encoding, reporter);
                 }
 
                 // Store call information
                 all_calls.push(call_info);
             }
             if result.outgoing.len() > 10 {
-                println!("    ... and {} more", result.outgoing.len() - 10);
+                reporter.detail(format_args!(
+                    "    ... and {} more",
+                    result.outgoing.len() - 10
+                ));
             }
         }
 
@@ -396,73 +415,209 @@ fn extract_call_hierachy_for_files<L: Language>(
     let elapsed = start_time.elapsed();
     let ops_per_sec = (total_calls + total_incoming_calls) as f64 / elapsed.as_secs_f64();
 
-    println!(
+    reporter.status(format_args!(
         "Summary: {} calls with definitions and {} incoming calls found in {:.2?}, {:.2} calls/sec",
         total_calls, total_incoming_calls, elapsed, ops_per_sec
-    );
-    println!(
+    ));
+    reporter.status(format_args!(
         "Symbols processed: {}, {:.2} symbols/sec",
         total_symbols,
         total_symbols as f64 / elapsed.as_secs_f64()
-    );
-    println!(
+    ));
+    reporter.status(format_args!(
         "Calls per request: {:.3}",
         total_calls as f64 / total_symbols as f64
-    );
-
-    // durations.sort_by_key(|t| t.1);
-    // let total_durations: Duration = durations.iter().map(|(_, duration)| duration).sum();
-    // print!(
-    //     "Total durations: {:.2?} n={}",
-    //     total_durations,
-    //     durations.len()
-    // );
-    // print!("All durations: {:?}", durations);
-
-    Ok(all_calls)
+    ));
+
+    Ok((all_calls, timings))
 }
 
 fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-
     // Parse and validate command-line arguments
     let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
     let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+    let reporter = args.reporter();
+    let error_policy = args.error_policy();
 
-    println!(
+    reporter.status(format_args!(
         "Finding all symbols in files in {}",
         args.project_path.display()
-    );
+    ));
 
     // Initialize performance timer
     let start_time = std::time::Instant::now();
 
     // Process files based on language
-    let call_results = match args.language.as_str() {
-        "rust" => extract_call_hierachy(RustLang, &args.project_path, &config)?,
-        "python" => extract_call_hierachy(PythonLang, &args.project_path, &config)?,
-        "typescript" => extract_call_hierachy(TypeScriptLang, &args.project_path, &config)?,
-        "go" => extract_call_hierachy(GoLang, &args.project_path, &config)?,
-        "swift" => extract_call_hierachy(SwiftLang, &args.project_path, &config)?,
+    let (call_results, timings) = match args.language() {
+        "rust" => extract_call_hierachy(
+            RustLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "python" => extract_call_hierachy(
+            PythonLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "typescript" => extract_call_hierachy(
+            TypeScriptLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "go" => extract_call_hierachy(
+            GoLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "swift" => extract_call_hierachy(
+            SwiftLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "php" => extract_call_hierachy(
+            PhpLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
         _ => unreachable!(),
     };
 
+    for phase in [
+        Phase::Symbols,
+        Phase::Prepare,
+        Phase::Incoming,
+        Phase::Outgoing,
+    ] {
+        if let (Some(p50), Some(p95)) = (timings.p50(phase), timings.p95(phase)) {
+            reporter.status(format_args!(
+                "{:?} requests: n={}, total {:.2?}, p50 {:.2?}, p95 {:.2?}",
+                phase,
+                timings.durations(phase).len(),
+                timings.total(phase),
+                p50,
+                p95
+            ));
+        }
+    }
+
     let elapsed = start_time.elapsed();
-    println!("\n{}", "=".repeat(80));
-    println!("Completed in {:.2?}", elapsed);
-
-    // Write output to JSON file if specified
-    if let Some(output_path) = &args.output {
-        println!(
-            "Writing {} call results to {}",
-            call_results.len(),
-            output_path.display()
-        );
-        let json = serde_json::to_string_pretty(&call_results)?;
-        std::fs::write(output_path, json)?;
-        println!("Successfully wrote results to {}", output_path.display());
+    reporter.status(format_args!("\n{}", "=".repeat(80)));
+    reporter.status(format_args!("Completed in {:.2?}", elapsed));
+
+    // The text details are printed while the calls are found
+    let format = args.output_format();
+    if format != OutputFormat::Text || args.output.is_some() {
+        let calls = call_results.len();
+        CallHierarchyReport {
+            calls: call_results,
+        }
+        .render(format, &mut args.output_writer()?)?;
+        if let Some(output_path) = &args.output {
+            reporter.status(format_args!(
+                "Wrote {} call results to {}",
+                calls,
+                output_path.display()
+            ));
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outgoing_call_without_from_ranges() -> Result<()> {
+        let call: lsp_types::CallHierarchyOutgoingCall = serde_json::from_value(
+            serde_json::json!({
+                "to": {
+                    "name": "helper",
+                    "kind": 12,
+                    "uri": "file:///project/src/lib.rs",
+                    "range": {"start": {"line": 4, "character": 0}, "end": {"line": 6, "character": 1}},
+                    "selectionRange": {"start": {"line": 4, "character": 3}, "end": {"line": 4, "character": 9}},
+                },
+                "fromRanges": [],
+            }),
+        )?;
+
+        let call_info = CallInfo::from_outgoing(Path::new("/project/src/main.rs"), &call);
+        assert_eq!(call_info.call_range, None);
+        assert_eq!(call_info.target_name, "helper");
+        assert_eq!(call_info.target_line, 5);
+        // Printing a call without a range must not abort processing
+        call_info.pretty_print(
+            &["fn main() {", "    helper();", "}"],
+            &PositionEncodingKind::UTF16,
+            Reporter::default(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_call_hierarchy_report() -> Result<()> {
+        let call = |call_range| CallInfo {
+            call_file: "src/main.rs".to_string(),
+            call_range,
+            target_name: "helper".to_string(),
+            target_file: "/project/src/lib.rs".to_string(),
+            target_line: 5,
+            target_column: 4,
+        };
+        let range = lsp_types::Range::new(
+            lsp_types::Position::new(1, 4),
+            lsp_types::Position::new(1, 10),
+        );
+        let report = CallHierarchyReport {
+            calls: vec![call(Some(range)), call(None)],
+        };
+        let render = |format| -> Result<String> {
+            let mut buffer = Vec::new();
+            report.render(format, &mut buffer)?;
+            Ok(String::from_utf8(buffer)?)
+        };
+
+        assert_eq!(
+            render(OutputFormat::Text)?,
+            "src/main.rs:2:5 -> helper (/project/src/lib.rs:5:4)\n\
+             src/main.rs:unknown -> helper (/project/src/lib.rs:5:4)\n"
+        );
+        let json = render(OutputFormat::Json)?;
+        let first: serde_json::Value = serde_json::from_str(json.lines().next().unwrap())?;
+        assert_eq!(first["target_column"], 4);
+        assert_eq!(json.lines().count(), 2);
+        assert_eq!(
+            render(OutputFormat::Csv)?,
+            format!(
+                "{}\nhelper,call,src/main.rs,2,5,/project/src/lib.rs,5,4\n\
+                 helper,call,src/main.rs,0,0,/project/src/lib.rs,5,4\n",
+                tree_sitter_lsp_experiment::report::CSV_HEADER
+            )
+        );
+
+        Ok(())
+    }
+}
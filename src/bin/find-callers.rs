@@ -0,0 +1,63 @@
+//! Example demonstrating how to find the callers of all functions in a project.
+//!
+//! This is the inverse of goto-definition: the function declarations are found
+//! with tree-sitter and their incoming calls with the call hierarchy of the LSP
+//! server. The callers are written in `--format`, to `--output` if given.
+//!
+//! Usage: cargo run --bin find-callers -- <project_path> --language <language>
+
+use anyhow::Result;
+use tree_sitter_lsp_experiment::report::{CallersReport, Render};
+use tree_sitter_lsp_experiment::{
+    Args, GoLang, OutputFormat, PhpLang, PythonLang, RustLang, SwiftLang, TypeScriptLang,
+    find_all_callers, init_tracing,
+};
+
+fn main() -> Result<()> {
+    // Parse and validate command-line arguments
+    let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
+    let reporter = args.reporter();
+    let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+
+    reporter.status(format_args!(
+        "Finding the callers of all functions in {}",
+        args.project_path.display()
+    ));
+    let start_time = std::time::Instant::now();
+
+    let path = &args.project_path;
+    let policy = args.error_policy();
+    let declarations = match args.language() {
+        "rust" => find_all_callers(RustLang, path, &config, lsp_config, policy)?,
+        "python" => find_all_callers(PythonLang, path, &config, lsp_config, policy)?,
+        "typescript" => find_all_callers(TypeScriptLang, path, &config, lsp_config, policy)?,
+        "go" => find_all_callers(GoLang, path, &config, lsp_config, policy)?,
+        "swift" => find_all_callers(SwiftLang, path, &config, lsp_config, policy)?,
+        "php" => find_all_callers(PhpLang, path, &config, lsp_config, policy)?,
+        _ => unreachable!(),
+    };
+
+    let total = declarations.len();
+    let uncalled = declarations
+        .iter()
+        .filter(|declaration| declaration.callers.is_empty())
+        .count();
+    let format = args.output_format();
+    if format != OutputFormat::Text || args.output.is_some() || reporter.shows_details() {
+        CallersReport { declarations }.render(format, &mut args.output_writer()?)?;
+        if let Some(output_path) = &args.output {
+            reporter.status(format_args!("Wrote callers to {}", output_path.display()));
+        }
+    }
+    reporter.status(format_args!("\n{}", "=".repeat(80)));
+    reporter.status(format_args!(
+        "Summary: {} functions, {} without callers, in {:.2?}",
+        total,
+        uncalled,
+        start_time.elapsed()
+    ));
+
+    Ok(())
+}
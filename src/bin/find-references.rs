@@ -3,74 +3,86 @@
 //! Usage: cargo run --bin find-references -- <project_path> --language <language>
 
 use anyhow::Result;
-use lsp_types::{ReferenceContext, ReferenceParams, SymbolKind, request::References};
+use lsp_types::{ReferenceContext, ReferenceParams, request::References};
 use std::path::Path;
-use tree_sitter_lsp_experiment::lsp::text_document_position_params;
+use tree_sitter_lsp_experiment::file_search::workspace_root;
+use tree_sitter_lsp_experiment::location::{SourceCache, lsp_to_display};
+use tree_sitter_lsp_experiment::lsp::{
+    CallableSymbolFilter, callable_symbols, text_document_position_params,
+};
+use tree_sitter_lsp_experiment::report::{ReferenceRecord, ReferencesReport, Render};
+use tree_sitter_lsp_experiment::uri::display_uri;
 use tree_sitter_lsp_experiment::{
-    Args, FileSearchConfig, GoLang, Language, LspServer, PythonLang, RustLang, SwiftLang,
-    TypeScriptLang,
+    Args, ErrorPolicy, FileSearchConfig, GoLang, Language, LspServer, LspServerConfig,
+    OutputFormat, PhpLang, PythonLang, Reporter, RustLang, SwiftLang, TypeScriptLang, init_tracing,
 };
 
 fn process_files<L: Language>(
     language: L,
     project_path: &Path,
     config: &FileSearchConfig,
-) -> Result<()> {
+    lsp_config: LspServerConfig,
+    reporter: Reporter,
+    error_policy: ErrorPolicy,
+) -> Result<Vec<ReferenceRecord>> {
     let start_time = std::time::Instant::now();
     let mut total_symbols = 0;
     let mut total_references = 0;
+    let mut records = Vec::new();
+    let mut sources = SourceCache::default();
 
     // Find all matching files
     let matching_files = config.find_language_files(project_path, language)?;
 
     if matching_files.is_empty() {
-        println!("No matching files found in {}", project_path.display());
-        return Ok(());
+        reporter.status(format_args!(
+            "No matching files found in {}",
+            project_path.display()
+        ));
+        return Ok(records);
     }
 
-    println!("Found {} matching files", matching_files.len());
+    reporter.status(format_args!(
+        "Found {} matching files",
+        matching_files.len()
+    ));
 
     // Start and initialize LSP server
     tracing::info!("Starting LSP server for {}...", language);
-    let mut lsp_server = LspServer::start_and_init(language, project_path.to_path_buf())?;
+    let mut lsp_server = LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    )?;
 
-    // Give LSP server time to start indexing
-    tracing::info!("Giving LSP server time to start indexing...");
-    std::thread::sleep(std::time::Duration::from_secs(1));
+    lsp_server.wait_for_indexing()?;
 
     // Process each file
     for (index, file_path) in matching_files.iter().enumerate() {
-        // Skip if file name contains spaces (can cause URI issues)
-        if file_path.display().to_string().contains(' ') {
-            tracing::debug!("Skipping file with spaces in path: {}", file_path.display());
-            continue;
-        }
-
-        println!("\n{}", "=".repeat(80));
-        println!(
+        reporter.detail(format_args!("\n{}", "=".repeat(80)));
+        reporter.detail(format_args!(
             "[{}/{}] Processing: {}",
             index + 1,
             matching_files.len(),
             file_path.display()
-        );
-        println!("{}", "=".repeat(80));
+        ));
+        reporter.detail(format_args!("{}", "=".repeat(80)));
 
         // Get absolute path
-        let absolute_path = match file_path.canonicalize() {
-            Ok(path) => path,
-            Err(e) => {
-                tracing::warn!("Failed to canonicalize path {}: {}", file_path.display(), e);
-                continue;
-            }
+        let Some(absolute_path) = error_policy.handle(file_path.canonicalize(), || {
+            format!("Failed to canonicalize path {}", file_path.display())
+        })?
+        else {
+            continue;
         };
 
         // Read file content
-        let file_content = match std::fs::read_to_string(&absolute_path) {
-            Ok(content) => content,
-            Err(e) => {
-                tracing::warn!("Failed to read file {}: {}", absolute_path.display(), e);
-                continue;
-            }
+        let Some(file_content) = error_policy
+            .handle(std::fs::read_to_string(&absolute_path), || {
+                format!("Failed to read file {}", absolute_path.display())
+            })?
+        else {
+            continue;
         };
 
         // Open the document in the LSP server
@@ -79,50 +91,30 @@ fn process_files<L: Language>(
         // Request document symbols
         let before_symbols = std::time::Instant::now();
         let (symbols, is_flat) = lsp_server.get_document_symbols(&absolute_path)?;
-        println!(
+        reporter.detail(format_args!(
             "Found {} symbols ({}) in {:.2?}",
             symbols.len(),
             if is_flat { "flat" } else { "nested" },
             before_symbols.elapsed()
-        );
-
-        // Recursively collect all callable symbols (functions/methods)
-        fn collect_callable_symbols<'a>(
-            symbols: &'a [lsp_types::DocumentSymbol],
-            result: &mut Vec<&'a lsp_types::DocumentSymbol>,
-        ) {
-            for symbol in symbols {
-                if matches!(
-                    symbol.kind,
-                    SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::CONSTRUCTOR
-                ) {
-                    result.push(symbol);
-                }
-                // Recursively process children
-                if let Some(ref children) = symbol.children {
-                    collect_callable_symbols(children, result);
-                }
-            }
-        }
+        ));
 
-        let mut callable_symbols = Vec::new();
-        collect_callable_symbols(&symbols, &mut callable_symbols);
+        let callable_symbols = callable_symbols(symbols, &CallableSymbolFilter::default());
 
-        println!(
+        reporter.detail(format_args!(
             "\nFound {} callable symbols (functions/methods/constructors)",
             callable_symbols.len()
-        );
+        ));
 
         total_symbols += callable_symbols.len();
 
         // Find references for each callable symbol
         for (i, symbol) in callable_symbols.iter().enumerate() {
-            println!(
+            reporter.detail(format_args!(
                 "\n[{}/{}] Analyzing references for: {}",
                 i + 1,
                 callable_symbols.len(),
                 symbol.name
-            );
+            ));
 
             // Request references at the symbol's position with exponential backoff
             let reference_params = ReferenceParams {
@@ -159,7 +151,7 @@ fn process_files<L: Language>(
                 }
 
                 let request_start = std::time::Instant::now();
-                match lsp_server.request::<References>(reference_params.clone()) {
+                match lsp_server.request_retrying::<References>(reference_params.clone()) {
                     Ok(Some(locations)) if !locations.is_empty() => {
                         let request_time = request_start.elapsed();
                         tracing::info!(
@@ -168,18 +160,45 @@ fn process_files<L: Language>(
                             locations.len(),
                             attempt + 1
                         );
-                        println!("  Found {} references:", locations.len());
+                        reporter.detail(format_args!("  Found {} references:", locations.len()));
                         total_references += locations.len();
 
+                        let definition = lsp_to_display(symbol.selection_range.start);
+                        records.extend(locations.iter().map(|location| {
+                            let reference = lsp_to_display(location.range.start);
+                            ReferenceRecord {
+                                symbol: symbol.name.clone(),
+                                kind: format!("{:?}", symbol.kind),
+                                ref_file: display_uri(&location.uri),
+                                ref_line: reference.line,
+                                ref_col: reference.column,
+                                def_file: absolute_path.display().to_string(),
+                                def_line: definition.line,
+                                def_col: definition.column,
+                            }
+                        }));
+
                         for (j, location) in locations.iter().enumerate().take(10) {
-                            let file_path = location.uri.path();
-                            let line = location.range.start.line + 1;
-                            let char = location.range.start.character;
-                            println!("    {}. {}:{}:{}", j + 1, file_path, line, char);
+                            let file_path = display_uri(&location.uri);
+                            let position = lsp_to_display(location.range.start);
+                            reporter.detail(format_args!(
+                                "    {}. {}:{}",
+                                j + 1,
+                                file_path,
+                                position
+                            ));
+                            if reporter.shows_details()
+                                && let Some([line, underline]) =
+                                    sources.context(location, &lsp_server.position_encoding)
+                            {
+                                reporter.detail(format_args!("         {}", line));
+                                reporter.detail(format_args!("         {}", underline));
+                            }
                         }
 
                         if locations.len() > 10 {
-                            println!("    ... and {} more", locations.len() - 10);
+                            reporter
+                                .detail(format_args!("    ... and {} more", locations.len() - 10));
                         }
                         found_references = true;
                         break;
@@ -193,7 +212,7 @@ fn process_files<L: Language>(
                         );
                         // No references yet, will retry if attempts remain
                         if attempt == max_attempts - 1 {
-                            println!("  No references found");
+                            reporter.detail(format_args!("  No references found"));
                         }
                     }
                     Err(e) => {
@@ -219,36 +238,92 @@ fn process_files<L: Language>(
 
     let elapsed = start_time.elapsed();
     let symbols_per_sec = total_symbols as f64 / elapsed.as_secs_f64();
-    println!("\n{}", "=".repeat(80));
-    println!(
+    reporter.status(format_args!("\n{}", "=".repeat(80)));
+    reporter.status(format_args!(
         "Summary: Analyzed {} symbols, found {} total references in {:.2?} ({:.2} symbols/sec)",
         total_symbols, total_references, elapsed, symbols_per_sec
-    );
+    ));
 
-    Ok(())
+    Ok(records)
 }
 
 fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-
     // Parse and validate command-line arguments
     let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
     let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+    let reporter = args.reporter();
+    let error_policy = args.error_policy();
 
-    println!(
+    reporter.status(format_args!(
         "Finding all references to functions/methods in {}",
         args.project_path.display()
-    );
+    ));
 
     // Process files based on language
-    match args.language.as_str() {
-        "rust" => process_files(RustLang, &args.project_path, &config)?,
-        "python" => process_files(PythonLang, &args.project_path, &config)?,
-        "typescript" => process_files(TypeScriptLang, &args.project_path, &config)?,
-        "go" => process_files(GoLang, &args.project_path, &config)?,
-        "swift" => process_files(SwiftLang, &args.project_path, &config)?,
+    let records = match args.language() {
+        "rust" => process_files(
+            RustLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "python" => process_files(
+            PythonLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "typescript" => process_files(
+            TypeScriptLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "go" => process_files(
+            GoLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "swift" => process_files(
+            SwiftLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "php" => process_files(
+            PhpLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
         _ => unreachable!(),
+    };
+
+    // The text details are printed while the references are found
+    let format = args.output_format();
+    if format != OutputFormat::Text {
+        ReferencesReport { records }.render(format, &mut args.output_writer()?)?;
+        if let Some(output_path) = &args.output {
+            reporter.status(format_args!(
+                "Wrote references to {}",
+                output_path.display()
+            ));
+        }
     }
 
     Ok(())
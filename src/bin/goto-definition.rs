@@ -1,54 +1,146 @@
 //! Example demonstrating how to find all function calls and their definitions in a project.
 //!
+//! Calls are written as soon as their definition is found, except with
+//! `--dedup-edges` where all calls are needed to collapse identical ones.
+//!
 //! Usage: cargo run --bin goto-definition -- <project_path> --language <language>
 
 use anyhow::Result;
+use tree_sitter_lsp_experiment::call_with_target::CallWithTarget;
+use tree_sitter_lsp_experiment::integration::dedup_call_edges;
+use tree_sitter_lsp_experiment::report::CallWriter;
 use tree_sitter_lsp_experiment::{
-    Args, GoLang, PythonLang, RustLang, SwiftLang, TypeScriptLang, find_all_call_targets,
+    Args, GoLang, OutputFormat, PhpLang, PythonLang, RustLang, SwiftLang, TypeScriptLang,
+    init_tracing, stream_call_targets,
 };
 
 fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-
     // Parse and validate command-line arguments
     let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
+    let reporter = args.reporter();
     let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+    let error_policy = args.error_policy();
 
-    println!(
+    reporter.status(format_args!(
         "Finding all function calls and their definitions in {}",
         args.project_path.display()
-    );
+    ));
 
     // Initialize performance timer
     let start_time = std::time::Instant::now();
 
+    let format = args.output_format();
+    let show_calls = format != OutputFormat::Text || reporter.shows_details();
+    let mut writer = CallWriter::new(args.output_writer()?, format)?;
+    let mut calls_with_targets = 0;
+    let mut collected_calls = Vec::new();
+    let mut on_call = |call: CallWithTarget| -> Result<()> {
+        calls_with_targets += 1;
+        if args.dedup_edges {
+            collected_calls.push(call);
+        } else if show_calls {
+            writer.write_call(&call)?;
+        }
+        Ok(())
+    };
+
     // Find all calls and their definitions
-    let results = match args.language.as_str() {
-        "rust" => find_all_call_targets(RustLang, &args.project_path, &config)?,
-        "python" => find_all_call_targets(PythonLang, &args.project_path, &config)?,
-        "typescript" => find_all_call_targets(TypeScriptLang, &args.project_path, &config)?,
-        "go" => find_all_call_targets(GoLang, &args.project_path, &config)?,
-        "swift" => find_all_call_targets(SwiftLang, &args.project_path, &config)?,
+    let path = &args.project_path;
+    let results = match args.language() {
+        "rust" => stream_call_targets(
+            RustLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
+        "python" => stream_call_targets(
+            PythonLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
+        "typescript" => stream_call_targets(
+            TypeScriptLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
+        "go" => stream_call_targets(
+            GoLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
+        "swift" => stream_call_targets(
+            SwiftLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
+        "php" => stream_call_targets(
+            PhpLang,
+            path,
+            &config,
+            lsp_config,
+            error_policy,
+            &mut on_call,
+        )?,
         _ => unreachable!(),
     };
 
-    for call in &results.calls_with_targets {
-        for line in call.pretty_print() {
-            println!("{}", line);
+    if args.dedup_edges {
+        let calls = dedup_call_edges(collected_calls);
+        calls_with_targets = calls.len();
+        if show_calls {
+            for call in &calls {
+                writer.write_call(call)?;
+            }
         }
     }
+    if show_calls {
+        for (file_path, call_node) in &results.calls_without_lsp {
+            writer.write_call_without_lsp(file_path, call_node)?;
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+    if let Some(output_path) = &args.output {
+        reporter.status(format_args!(
+            "Wrote call results to {}",
+            output_path.display()
+        ));
+    }
+    if !results.lsp_enabled {
+        reporter
+            .status("Note: LSP features are disabled, calls are listed without their definitions");
+    }
 
     let elapsed = start_time.elapsed();
     let ops_per_sec = (results.total_calls as f64) / elapsed.as_secs_f64();
-    println!("\n{}", "=".repeat(80));
-    println!(
+    reporter.status(format_args!("\n{}", "=".repeat(80)));
+    if !results.lsp_enabled {
+        reporter.status(format_args!(
+            "Summary: {} calls found with tree-sitter only in {:.2?}",
+            results.total_calls, elapsed
+        ));
+        return Ok(());
+    }
+    reporter.status(format_args!(
         "Summary: {} calls with definitions found out of {} total calls in {:.2?}, {:.2} ops/sec",
-        results.calls_with_targets.len(),
-        results.total_calls,
-        elapsed,
-        ops_per_sec
-    );
+        calls_with_targets, results.total_calls, elapsed, ops_per_sec
+    ));
 
     Ok(())
 }
@@ -3,88 +3,191 @@
 //! Usage: cargo run --bin inlay-hints -- <project_path> --language <language>
 
 use anyhow::Result;
-use lsp_types::{InlayHintParams, Range, TextDocumentIdentifier, WorkDoneProgressParams};
+use lsp_types::{
+    InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, InlayHintTooltip, Range,
+    TextDocumentIdentifier, WorkDoneProgressParams,
+};
+use serde::Serialize;
+use std::io::{self, Write};
 use std::path::Path;
+use tree_sitter_lsp_experiment::file_search::workspace_root;
+use tree_sitter_lsp_experiment::location::lsp_to_display;
+use tree_sitter_lsp_experiment::report::{Render, escape_csv_field};
+use tree_sitter_lsp_experiment::uri::to_file_uri;
 use tree_sitter_lsp_experiment::{
-    Args, FileSearchConfig, GoLang, Language, LspServer, PythonLang, RustLang, SwiftLang,
-    TypeScriptLang,
+    Args, ErrorPolicy, FileSearchConfig, GoLang, Language, LspServer, LspServerConfig,
+    OutputFormat, PhpLang, PythonLang, Reporter, RustLang, SwiftLang, TypeScriptLang, init_tracing,
 };
 
+/// An inlay hint, with its position in a file
+#[derive(Debug, Serialize)]
+struct HintRecord {
+    file: String,
+    line: usize,
+    column: usize,
+    /// `Type`, `Parameter` or `Other`
+    kind: &'static str,
+    label: String,
+    tooltip: Option<String>,
+}
+
+impl HintRecord {
+    fn new(file_path: &Path, hint: &InlayHint) -> HintRecord {
+        let position = lsp_to_display(hint.position);
+        HintRecord {
+            file: file_path.display().to_string(),
+            line: position.line,
+            column: position.column,
+            kind: match hint.kind {
+                Some(InlayHintKind::TYPE) => "Type",
+                Some(InlayHintKind::PARAMETER) => "Parameter",
+                _ => "Other",
+            },
+            label: match &hint.label {
+                InlayHintLabel::String(s) => s.clone(),
+                InlayHintLabel::LabelParts(parts) => {
+                    parts.iter().map(|p| p.value.as_str()).collect()
+                }
+            },
+            tooltip: hint.tooltip.as_ref().map(|tooltip| match tooltip {
+                InlayHintTooltip::String(s) => s.clone(),
+                InlayHintTooltip::MarkupContent(markup) => markup.value.clone(),
+            }),
+        }
+    }
+}
+
+/// The CSV header, matching the fields of `HintRecord`
+const CSV_HEADER: &str = "file,line,column,kind,label,tooltip";
+
+/// The inlay hints of all files in a project
+struct InlayHintsReport {
+    hints: Vec<HintRecord>,
+}
+
+impl Render for InlayHintsReport {
+    fn render(&self, format: OutputFormat, writer: &mut dyn Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                for hint in &self.hints {
+                    writeln!(
+                        writer,
+                        "{}:{}:{} [{}] {}",
+                        hint.file, hint.line, hint.column, hint.kind, hint.label
+                    )?;
+                }
+            }
+            OutputFormat::Json => {
+                for hint in &self.hints {
+                    serde_json::to_writer(&mut *writer, hint)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            OutputFormat::Csv => {
+                writeln!(writer, "{}", CSV_HEADER)?;
+                for hint in &self.hints {
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{}",
+                        escape_csv_field(&hint.file),
+                        hint.line,
+                        hint.column,
+                        hint.kind,
+                        escape_csv_field(&hint.label),
+                        escape_csv_field(hint.tooltip.as_deref().unwrap_or_default())
+                    )?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
 fn process_files<L: Language>(
     language: L,
     project_path: &Path,
     config: &FileSearchConfig,
-) -> Result<()> {
+    lsp_config: LspServerConfig,
+    reporter: Reporter,
+    error_policy: ErrorPolicy,
+) -> Result<Vec<HintRecord>> {
     let start_time = std::time::Instant::now();
     let mut total_files_with_hints = 0;
     let mut total_hints = 0;
+    let mut records = Vec::new();
 
     // Find all matching files
     let matching_files = config.find_language_files(project_path, language)?;
 
     if matching_files.is_empty() {
-        println!("No matching files found in {}", project_path.display());
-        return Ok(());
+        reporter.status(format_args!(
+            "No matching files found in {}",
+            project_path.display()
+        ));
+        return Ok(records);
     }
 
-    println!("Found {} matching files", matching_files.len());
+    reporter.status(format_args!(
+        "Found {} matching files",
+        matching_files.len()
+    ));
 
     // Start and initialize LSP server
     tracing::info!("Starting LSP server for {}...", language);
-    let mut lsp_server = LspServer::start_and_init(language, project_path.to_path_buf())?;
+    let mut lsp_server = LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    )?;
 
-    // Give LSP server time to start indexing
-    tracing::info!("Giving LSP server time to start indexing...");
-    std::thread::sleep(std::time::Duration::from_secs(3));
-
-    // Process each file
-    for (index, file_path) in matching_files.iter().enumerate() {
-        // Skip if file name contains spaces (can cause URI issues)
-        if file_path.display().to_string().contains(' ') {
-            tracing::debug!("Skipping file with spaces in path: {}", file_path.display());
-            continue;
-        }
-
-        println!("\n{}", "=".repeat(80));
-        println!(
-            "[{}/{}] Processing: {}",
-            index + 1,
-            matching_files.len(),
-            file_path.display()
-        );
-        println!("{}", "=".repeat(80));
+    lsp_server.wait_for_indexing()?;
 
+    // Open all files first, so the refresh below covers every document
+    let mut open_files = Vec::new();
+    for file_path in &matching_files {
         // Get absolute path
-        let absolute_path = match file_path.canonicalize() {
-            Ok(path) => path,
-            Err(e) => {
-                tracing::warn!("Failed to canonicalize path {}: {}", file_path.display(), e);
-                continue;
-            }
+        let Some(absolute_path) = error_policy.handle(file_path.canonicalize(), || {
+            format!("Failed to canonicalize path {}", file_path.display())
+        })?
+        else {
+            continue;
         };
 
         // Read file content
-        let file_content = match std::fs::read_to_string(&absolute_path) {
-            Ok(content) => content,
-            Err(e) => {
-                tracing::warn!("Failed to read file {}: {}", absolute_path.display(), e);
-                continue;
-            }
+        let Some(file_content) = error_policy
+            .handle(std::fs::read_to_string(&absolute_path), || {
+                format!("Failed to read file {}", absolute_path.display())
+            })?
+        else {
+            continue;
         };
 
         // Open the document in the LSP server
         lsp_server.open_file(&absolute_path, &file_content)?;
+        open_files.push((file_path, absolute_path, file_content));
+    }
+
+    // Get fresh hints for all open documents rather than incremental state
+    lsp_server.request_inlay_hints_refresh()?;
+
+    // Process each file
+    for (index, (file_path, absolute_path, file_content)) in open_files.iter().enumerate() {
+        reporter.detail(format_args!("\n{}", "=".repeat(80)));
+        reporter.detail(format_args!(
+            "[{}/{}] Processing: {}",
+            index + 1,
+            open_files.len(),
+            file_path.display()
+        ));
+        reporter.detail(format_args!("{}", "=".repeat(80)));
 
         // Count lines in the file
         let line_count = file_content.lines().count() as u32;
 
-        // Create the file URI
-        let file_uri = format!("file://{}", absolute_path.display());
-
         // Request inlay hints for the entire file
         let inlay_hint_params = InlayHintParams {
             text_document: TextDocumentIdentifier {
-                uri: file_uri.parse()?,
+                uri: to_file_uri(absolute_path)?,
             },
             range: Range {
                 start: lsp_types::Position {
@@ -104,135 +207,88 @@ fn process_files<L: Language>(
         // Give LSP a moment after opening the file
         // std::thread::sleep(std::time::Duration::from_millis(100));
 
-        // Send the inlay hint request with retry logic
+        // Send the inlay hint request, retrying transient errors with backoff
         let before_request = std::time::Instant::now();
-        let mut last_error = None;
-        let mut hints_result = None;
-
-        // Try up to 3 times with exponential backoff (0ms, 50ms, 250ms)
-        for attempt in 0..3 {
-            if attempt > 0 {
-                let delay_ms = match attempt {
-                    1 => 50,
-                    2 => 250,
-                    _ => 0,
-                };
-                tracing::debug!("Retry attempt {} after {}ms delay", attempt + 1, delay_ms);
-                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
-            }
-
-            match lsp_server
-                .request::<lsp_types::request::InlayHintRequest>(inlay_hint_params.clone())
-            {
-                Ok(result) => {
-                    hints_result = Some(result);
-                    break;
-                }
-                Err(e) if e.to_string().contains("content modified") && attempt < 2 => {
-                    tracing::debug!(
-                        "Got 'content modified' error on attempt {}, retrying...",
-                        attempt + 1
-                    );
-                    last_error = Some(e);
-                    continue;
-                }
-                Err(e) => {
-                    last_error = Some(e);
-                    break;
-                }
-            }
-        }
+        let hints_result =
+            lsp_server.request_retrying::<lsp_types::request::InlayHintRequest>(inlay_hint_params);
 
         match hints_result {
-            Some(Some(hints)) => {
+            Ok(Some(hints)) => {
                 let request_time = before_request.elapsed();
-                println!(
+                reporter.detail(format_args!(
                     "\nFound {} inlay hints in {:.2?}",
                     hints.len(),
                     request_time
-                );
+                ));
 
                 total_files_with_hints += 1;
                 total_hints += hints.len();
 
-                // Display each hint
+                // Split the file content into lines for display
+                let lines: Vec<&str> = file_content.lines().collect();
                 if !hints.is_empty() {
-                    println!("\nInlay Hints:");
-                    println!("{}", "-".repeat(80));
-
-                    // Split the file content into lines for display
-                    let lines: Vec<&str> = file_content.lines().collect();
-
-                    for hint in &hints {
-                        let line_num = hint.position.line as usize;
-                        let char_pos = hint.position.character as usize;
-
-                        // Get the line content if available
-                        let line_content = if line_num < lines.len() {
-                            lines[line_num].trim()
-                        } else {
-                            ""
-                        };
-
-                        // Format the hint label
-                        let label = match &hint.label {
-                            lsp_types::InlayHintLabel::String(s) => s.clone(),
-                            lsp_types::InlayHintLabel::LabelParts(parts) => {
-                                parts.iter().map(|p| p.value.as_str()).collect::<String>()
-                            }
-                        };
-
-                        // Determine hint kind
-                        let kind = match hint.kind {
-                            Some(lsp_types::InlayHintKind::TYPE) => "Type",
-                            Some(lsp_types::InlayHintKind::PARAMETER) => "Parameter",
-                            _ => "Other",
-                        };
-
-                        // Display the hint
-                        println!("  Line {}:{} [{}]: {}", line_num + 1, char_pos, kind, label);
-
-                        // Show a snippet of the line for context
-                        if !line_content.is_empty() {
-                            println!("    Context: {}", line_content);
-                        }
+                    reporter.detail("\nInlay Hints:");
+                    reporter.detail("-".repeat(80));
+                }
 
-                        // Add padding hint if available
-                        if hint.padding_left == Some(true) || hint.padding_right == Some(true) {
-                            let padding = match (hint.padding_left, hint.padding_right) {
-                                (Some(true), Some(true)) => " (with padding left & right)",
-                                (Some(true), _) => " (with padding left)",
-                                (_, Some(true)) => " (with padding right)",
-                                _ => "",
-                            };
-                            println!("    {}", padding);
+                for hint in hints {
+                    // Resolve lazily computed properties such as the tooltip
+                    let hint = match lsp_server.resolve_inlay_hint(hint.clone()) {
+                        Ok(resolved) => resolved,
+                        Err(e) => {
+                            tracing::debug!("Failed to resolve inlay hint: {}", e);
+                            hint
                         }
+                    };
+                    let record = HintRecord::new(file_path, &hint);
+
+                    // Display the hint
+                    reporter.detail(format_args!(
+                        "  Line {}:{} [{}]: {}",
+                        record.line, record.column, record.kind, record.label
+                    ));
+
+                    // Show the tooltip if the server provided one
+                    if let Some(tooltip) = &record.tooltip {
+                        reporter.detail(format_args!("    Tooltip: {}", tooltip));
+                    }
 
-                        println!();
+                    // Show a snippet of the line for context
+                    let line_content = lines
+                        .get(hint.position.line as usize)
+                        .map_or("", |line| line.trim());
+                    if !line_content.is_empty() {
+                        reporter.detail(format_args!("    Context: {}", line_content));
                     }
+
+                    // Add padding hint if available
+                    let padding = match (hint.padding_left, hint.padding_right) {
+                        (Some(true), Some(true)) => " (with padding left & right)",
+                        (Some(true), _) => " (with padding left)",
+                        (_, Some(true)) => " (with padding right)",
+                        _ => "",
+                    };
+                    if !padding.is_empty() {
+                        reporter.detail(format_args!("    {}", padding));
+                    }
+
+                    reporter.detail("");
+                    records.push(record);
                 }
             }
-            Some(None) => {
-                println!("\nNo inlay hints available for this file");
+            Ok(None) => {
+                reporter.detail(format_args!("\nNo inlay hints available for this file"));
             }
-            None => {
-                if let Some(e) = last_error {
-                    tracing::warn!("Failed to get inlay hints after retries: {}", e);
-                    println!("\nError getting inlay hints: {}", e);
-                } else {
-                    println!("\nNo inlay hints available for this file");
-                }
+            Err(e) => {
+                tracing::warn!("Failed to get inlay hints after retries: {}", e);
+                reporter.detail(format_args!("\nError getting inlay hints: {}", e));
             }
         }
+    }
 
-        // Close the document
-        if let Err(e) = lsp_server.close_file(&absolute_path) {
-            tracing::warn!(
-                "Failed to close document {}: {}",
-                absolute_path.display(),
-                e
-            );
-        }
+    // Close the documents
+    if let Err(e) = lsp_server.close_all() {
+        tracing::warn!("Failed to close documents: {}", e);
     }
 
     // Stop the LSP server
@@ -243,44 +299,132 @@ fn process_files<L: Language>(
 
     // Print summary
     let elapsed = start_time.elapsed();
-    println!("\n{}", "=".repeat(80));
-    println!("Summary:");
-    println!(
+    reporter.status(format_args!("\n{}", "=".repeat(80)));
+    reporter.status("Summary:");
+    reporter.status(format_args!(
         "  Files with hints: {} / {}",
         total_files_with_hints,
         matching_files.len()
-    );
-    println!("  Total inlay hints: {}", total_hints);
-    println!("  Time elapsed: {:.2?}", elapsed);
-    println!("{}", "=".repeat(80));
+    ));
+    reporter.status(format_args!("  Total inlay hints: {}", total_hints));
+    reporter.status(format_args!("  Time elapsed: {:.2?}", elapsed));
+    reporter.status("=".repeat(80));
 
-    Ok(())
+    Ok(records)
 }
 
 fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
-
     // Parse and validate command-line arguments
     let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
 
     // Create file search configuration
     let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+    let reporter = args.reporter();
+    let error_policy = args.error_policy();
 
     // Process files based on language
-    match args.language.as_str() {
-        "rust" => process_files(RustLang, &args.project_path, &config)?,
-        "python" => process_files(PythonLang, &args.project_path, &config)?,
-        "typescript" => process_files(TypeScriptLang, &args.project_path, &config)?,
-        "go" => process_files(GoLang, &args.project_path, &config)?,
-        "swift" => process_files(SwiftLang, &args.project_path, &config)?,
+    let records = match args.language() {
+        "rust" => process_files(
+            RustLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "python" => process_files(
+            PythonLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "typescript" => process_files(
+            TypeScriptLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "go" => process_files(
+            GoLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "swift" => process_files(
+            SwiftLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "php" => process_files(
+            PhpLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
         _ => unreachable!("Language should have been validated"),
+    };
+
+    // The text details are printed while the hints are requested
+    let format = args.output_format();
+    if format != OutputFormat::Text || args.output.is_some() {
+        InlayHintsReport { hints: records }.render(format, &mut args.output_writer()?)?;
+        if let Some(output_path) = &args.output {
+            reporter.status(format_args!(
+                "Wrote inlay hints to {}",
+                output_path.display()
+            ));
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_inlay_hints_report() -> Result<()> {
+        let hint: InlayHint = serde_json::from_value(serde_json::json!({
+            "position": {"line": 1, "character": 9},
+            "label": [{"value": ": "}, {"value": "Vec<i32>"}],
+            "kind": 1,
+            "tooltip": "type of `x`, inferred",
+        }))?;
+        let report = InlayHintsReport {
+            hints: vec![HintRecord::new(Path::new("src/main.rs"), &hint)],
+        };
+        let render = |format| -> Result<String> {
+            let mut buffer = Vec::new();
+            report.render(format, &mut buffer)?;
+            Ok(String::from_utf8(buffer)?)
+        };
+
+        assert_eq!(
+            render(OutputFormat::Text)?,
+            "src/main.rs:2:10 [Type] : Vec<i32>\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(render(OutputFormat::Json)?.trim_end())?;
+        assert_eq!(json["label"], ": Vec<i32>");
+        assert_eq!(json["tooltip"], "type of `x`, inferred");
+        assert_eq!(
+            render(OutputFormat::Csv)?,
+            format!("{CSV_HEADER}\nsrc/main.rs,2,10,Type,: Vec<i32>,\"type of `x`, inferred\"\n")
+        );
+
+        Ok(())
+    }
+}
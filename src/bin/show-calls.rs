@@ -13,8 +13,9 @@ use anyhow::Result;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use tree_sitter_lsp_experiment::location::ts_point_to_display;
 use tree_sitter_lsp_experiment::{
-    GoLang, Language, PythonLang, RustLang, SwiftLang, TypeScriptLang,
+    GoLang, Language, PhpLang, PythonLang, RustLang, SwiftLang, TypeScriptLang,
 };
 
 /// Process a file with a specific language
@@ -45,15 +46,14 @@ fn process_file<L: Language>(file_path: &Path, language: L) -> Result<()> {
             println!();
         } else {
             // Multi-line call - show basic info
-            let line_num = call.call_node.start_position().row;
+            let start = ts_point_to_display(call.call_node.start_position());
+            let end = ts_point_to_display(call.call_node.end_position());
             println!(
-                "Call #{}: line {} (multi-line, spans {}:{} to {}:{})",
+                "Call #{}: line {} (multi-line, spans {} to {})",
                 idx + 1,
-                line_num + 1,
-                call.call_node.start_position().row + 1,
-                call.call_node.start_position().column,
-                call.call_node.end_position().row + 1,
-                call.call_node.end_position().column
+                start.line,
+                start,
+                end
             );
             println!();
         }
@@ -95,6 +95,7 @@ fn main() -> Result<()> {
         "ts" | "tsx" => process_file(&file_path, TypeScriptLang),
         "go" => process_file(&file_path, GoLang),
         "swift" => process_file(&file_path, SwiftLang),
+        "php" => process_file(&file_path, PhpLang),
         _ => Err(anyhow::anyhow!(
             "Unsupported file extension: .{}",
             extension
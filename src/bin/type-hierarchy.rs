@@ -0,0 +1,411 @@
+//! Example demonstrating how to find the supertypes and subtypes of all classes in a project.
+//!
+//! Usage: cargo run --bin type-hierarchy -- <project_path> --language <language>
+
+use anyhow::Result;
+use lsp_types::TypeHierarchyItem;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::Path;
+use tree_sitter_lsp_experiment::file_search::workspace_root;
+use tree_sitter_lsp_experiment::location::{lsp_to_display, ts_point_to_lsp_position};
+use tree_sitter_lsp_experiment::parser::{get_class_definitions, parse_file_content};
+use tree_sitter_lsp_experiment::report::{Render, escape_csv_field};
+use tree_sitter_lsp_experiment::uri::display_uri;
+use tree_sitter_lsp_experiment::{
+    Args, ErrorPolicy, FileSearchConfig, GoLang, Language, LspServer, LspServerConfig,
+    OutputFormat, PhpLang, PythonLang, Reporter, RustLang, SwiftLang, TypeScriptLang, init_tracing,
+};
+
+/// How deep the supertype and subtype trees are followed
+const MAX_DEPTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Supertypes,
+    Subtypes,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Supertypes => "supertypes",
+            Direction::Subtypes => "subtypes",
+        }
+    }
+}
+
+/// A supertype or subtype found for a class
+#[derive(Debug, Serialize)]
+struct TypeRelation {
+    /// The name of the class the type hierarchy was prepared for
+    class: String,
+    /// The file of the class
+    class_file: String,
+    direction: Direction,
+    /// 1 for direct supertypes and subtypes
+    depth: usize,
+    /// The name of the supertype or subtype
+    name: String,
+    file: String,
+    line: usize,
+    column: usize,
+}
+
+/// The CSV header, matching the fields of `TypeRelation`
+const CSV_HEADER: &str = "class,class_file,direction,depth,name,file,line,column";
+
+/// The supertypes and subtypes of all classes in a project
+struct TypeHierarchyReport {
+    relations: Vec<TypeRelation>,
+}
+
+impl Render for TypeHierarchyReport {
+    fn render(&self, format: OutputFormat, writer: &mut dyn Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                for relation in &self.relations {
+                    writeln!(
+                        writer,
+                        "{} {} {}: {} ({}:{}:{})",
+                        relation.class,
+                        relation.direction.as_str(),
+                        relation.depth,
+                        relation.name,
+                        relation.file,
+                        relation.line,
+                        relation.column
+                    )?;
+                }
+            }
+            OutputFormat::Json => {
+                for relation in &self.relations {
+                    serde_json::to_writer(&mut *writer, relation)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            OutputFormat::Csv => {
+                writeln!(writer, "{}", CSV_HEADER)?;
+                for relation in &self.relations {
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{},{}",
+                        escape_csv_field(&relation.class),
+                        escape_csv_field(&relation.class_file),
+                        relation.direction.as_str(),
+                        relation.depth,
+                        escape_csv_field(&relation.name),
+                        escape_csv_field(&relation.file),
+                        relation.line,
+                        relation.column
+                    )?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Prints the supertypes or subtypes of `item` as an indented tree, and adds
+/// them to `found` with their depth
+fn print_hierarchy(
+    lsp_server: &mut LspServer<impl Language>,
+    item: TypeHierarchyItem,
+    direction: Direction,
+    depth: usize,
+    visited: &mut HashSet<(String, u32, String)>,
+    found: &mut Vec<(usize, TypeHierarchyItem)>,
+    reporter: Reporter,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Ok(());
+    }
+    let items = match direction {
+        Direction::Supertypes => lsp_server.type_hierarchy_supertypes(item)?,
+        Direction::Subtypes => lsp_server.type_hierarchy_subtypes(item)?,
+    };
+    for item in items {
+        let indent = "  ".repeat(depth + 1);
+        let key = (
+            item.uri.as_str().to_string(),
+            item.selection_range.start.line,
+            item.name.clone(),
+        );
+        reporter.detail(format_args!(
+            "{}{} ({}:{})",
+            indent,
+            item.name,
+            display_uri(&item.uri),
+            lsp_to_display(item.selection_range.start)
+        ));
+        found.push((depth, item.clone()));
+        // Stop at types that have already been printed on this path to avoid cycles
+        if visited.insert(key.clone()) {
+            print_hierarchy(
+                lsp_server,
+                item,
+                direction,
+                depth + 1,
+                visited,
+                found,
+                reporter,
+            )?;
+            visited.remove(&key);
+        }
+    }
+    Ok(())
+}
+
+fn process_files<L: Language>(
+    language: L,
+    project_path: &Path,
+    config: &FileSearchConfig,
+    lsp_config: LspServerConfig,
+    reporter: Reporter,
+    error_policy: ErrorPolicy,
+) -> Result<Vec<TypeRelation>> {
+    let matching_files = config.find_language_files(project_path, language)?;
+
+    if matching_files.is_empty() {
+        reporter.status(format_args!(
+            "No matching files found in {}",
+            project_path.display()
+        ));
+        return Ok(Vec::new());
+    }
+
+    reporter.status(format_args!(
+        "Found {} matching files",
+        matching_files.len()
+    ));
+
+    tracing::info!("Starting LSP server for {}...", language);
+    let mut lsp_server = LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    )?;
+
+    let mut total_classes = 0;
+    let mut relations = Vec::new();
+    for file_path in &matching_files {
+        let Some(absolute_path) = error_policy.handle(file_path.canonicalize(), || {
+            format!("Failed to canonicalize path {}", file_path.display())
+        })?
+        else {
+            continue;
+        };
+        let Some(file_content) = error_policy
+            .handle(std::fs::read_to_string(&absolute_path), || {
+                format!("Failed to read file {}", absolute_path.display())
+            })?
+        else {
+            continue;
+        };
+        let Some(tree) = error_policy
+            .handle(parse_file_content(&file_content, language), || {
+                format!("Failed to parse file {}", absolute_path.display())
+            })?
+        else {
+            continue;
+        };
+
+        let class_names: Vec<_> = get_class_definitions(&tree, language).collect();
+        if class_names.is_empty() {
+            continue;
+        }
+
+        reporter.detail(format_args!("\n{}", "=".repeat(80)));
+        reporter.detail(format_args!("File: {}", absolute_path.display()));
+        lsp_server.open_file(&absolute_path, &file_content)?;
+
+        for name in class_names {
+            total_classes += 1;
+            let position = ts_point_to_lsp_position(
+                name.start_position(),
+                &file_content,
+                &lsp_server.position_encoding,
+            );
+            let items = match lsp_server.prepare_type_hierarchy(&absolute_path, position) {
+                Ok(items) => items,
+                Err(e) => {
+                    tracing::warn!("Failed to prepare type hierarchy: {}", e);
+                    continue;
+                }
+            };
+            let Some(item) = items.into_iter().next() else {
+                reporter.detail(format_args!(
+                    "\n{}: no type hierarchy available",
+                    name.utf8_text(file_content.as_bytes())?
+                ));
+                continue;
+            };
+
+            reporter.detail(format_args!("\n{}", item.name));
+            let mut visited = HashSet::new();
+            for direction in [Direction::Supertypes, Direction::Subtypes] {
+                reporter.detail(match direction {
+                    Direction::Supertypes => "  Supertypes:",
+                    Direction::Subtypes => "  Subtypes:",
+                });
+                let mut found = Vec::new();
+                print_hierarchy(
+                    &mut lsp_server,
+                    item.clone(),
+                    direction,
+                    1,
+                    &mut visited,
+                    &mut found,
+                    reporter,
+                )?;
+                relations.extend(found.into_iter().map(|(depth, related)| {
+                    let position = lsp_to_display(related.selection_range.start);
+                    TypeRelation {
+                        class: item.name.clone(),
+                        class_file: display_uri(&item.uri),
+                        direction,
+                        depth,
+                        name: related.name,
+                        file: display_uri(&related.uri),
+                        line: position.line,
+                        column: position.column,
+                    }
+                }));
+            }
+        }
+
+        lsp_server.close_file(&absolute_path)?;
+    }
+
+    reporter.status(format_args!("\nAnalyzed {} classes", total_classes));
+    Ok(relations)
+}
+
+fn main() -> Result<()> {
+    // Parse and validate command-line arguments
+    let args = Args::parse_and_validate()?;
+    init_tracing(args.log_level());
+    let config = args.create_file_search_config()?;
+    let lsp_config = args.create_lsp_server_config()?;
+    let reporter = args.reporter();
+    let error_policy = args.error_policy();
+
+    reporter.status(format_args!(
+        "Finding the type hierarchy of all classes in {}",
+        args.project_path.display()
+    ));
+
+    let start_time = std::time::Instant::now();
+
+    let relations = match args.language() {
+        "rust" => process_files(
+            RustLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "python" => process_files(
+            PythonLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "typescript" => process_files(
+            TypeScriptLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "go" => process_files(
+            GoLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "swift" => process_files(
+            SwiftLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        "php" => process_files(
+            PhpLang,
+            &args.project_path,
+            &config,
+            lsp_config,
+            reporter,
+            error_policy,
+        )?,
+        _ => unreachable!(),
+    };
+
+    reporter.status(format_args!("\nCompleted in {:.2?}", start_time.elapsed()));
+
+    // The text details are printed while the hierarchies are found
+    let format = args.output_format();
+    if format != OutputFormat::Text || args.output.is_some() {
+        TypeHierarchyReport { relations }.render(format, &mut args.output_writer()?)?;
+        if let Some(output_path) = &args.output {
+            reporter.status(format_args!(
+                "Wrote the type hierarchies to {}",
+                output_path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_type_hierarchy_report() -> Result<()> {
+        let report = TypeHierarchyReport {
+            relations: vec![TypeRelation {
+                class: "Square".to_string(),
+                class_file: "/project/src/square.ts".to_string(),
+                direction: Direction::Supertypes,
+                depth: 1,
+                name: "Shape".to_string(),
+                file: "/project/src/shape, base.ts".to_string(),
+                line: 1,
+                column: 14,
+            }],
+        };
+        let render = |format| -> Result<String> {
+            let mut buffer = Vec::new();
+            report.render(format, &mut buffer)?;
+            Ok(String::from_utf8(buffer)?)
+        };
+
+        assert_eq!(
+            render(OutputFormat::Text)?,
+            "Square supertypes 1: Shape (/project/src/shape, base.ts:1:14)\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(render(OutputFormat::Json)?.trim_end())?;
+        assert_eq!(json["direction"], "supertypes");
+        assert_eq!(json["depth"], 1);
+        assert_eq!(
+            render(OutputFormat::Csv)?,
+            format!(
+                "{CSV_HEADER}\nSquare,/project/src/square.ts,supertypes,1,Shape,\"/project/src/shape, base.ts\",1,14\n"
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+//! Call graphs built by following LSP call hierarchy requests, or approximated
+//! with tree-sitter alone.
+
+use anyhow::Result;
+use lsp_types::{CallHierarchyItem, PositionEncodingKind, SymbolKind};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use tree_sitter::Node;
+
+use crate::Language;
+use crate::location::byte_range_to_lsp_range;
+use crate::lsp::LspServer;
+use crate::parser::{enclosing_function, get_calls, get_function_declarations, parse_file_content};
+use crate::uri::to_file_uri;
+
+/// A directed graph of functions and the functions they call
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// The items in the graph keyed by `item_key`
+    pub items: HashMap<String, CallHierarchyItem>,
+    /// The keys of the items called by each item
+    pub edges: HashMap<String, Vec<String>>,
+}
+
+impl CallGraph {
+    /// Returns the items called by `item`, in the order the server reported them
+    pub fn callees(&self, item: &CallHierarchyItem) -> Vec<&CallHierarchyItem> {
+        self.edges
+            .get(&item_key(item))
+            .into_iter()
+            .flatten()
+            .filter_map(|key| self.items.get(key))
+            .collect()
+    }
+
+    /// Adds the items and edges of `other` to this graph
+    pub fn merge(&mut self, other: CallGraph) {
+        self.items.extend(other.items);
+        for (key, callees) in other.edges {
+            let existing = self.edges.entry(key).or_default();
+            for callee in callees {
+                if !existing.contains(&callee) {
+                    existing.push(callee);
+                }
+            }
+        }
+    }
+
+    /// Returns the graph in the DOT format of Graphviz
+    ///
+    /// Nodes are identified by their `item_key` and labelled with their name.
+    /// Nodes and edges are sorted to make the output deterministic.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        let mut keys: Vec<_> = self.items.keys().collect();
+        keys.sort();
+        for key in &keys {
+            dot.push_str(&format!(
+                "  {} [label={}];\n",
+                dot_string(key),
+                dot_string(&self.items[*key].name)
+            ));
+        }
+        for key in &keys {
+            for callee in self.edges.get(*key).into_iter().flatten() {
+                dot.push_str(&format!(
+                    "  {} -> {};\n",
+                    dot_string(key),
+                    dot_string(callee)
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes a string for use as an identifier or label in DOT
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns a key that identifies a call hierarchy item by its URI and range
+pub fn item_key(item: &CallHierarchyItem) -> String {
+    format!(
+        "{}:{}:{}-{}:{}",
+        item.uri.as_str(),
+        item.range.start.line,
+        item.range.start.character,
+        item.range.end.line,
+        item.range.end.character
+    )
+}
+
+/// Builds the call graph reachable from `root_item` by following outgoing calls
+///
+/// Items more than `max_depth` calls away from the root are not expanded. Each
+/// item is expanded once, so recursive and mutually recursive functions do not
+/// cause the traversal to loop.
+pub fn build_call_graph(
+    lsp_server: &mut LspServer<impl Language>,
+    root_item: CallHierarchyItem,
+    max_depth: usize,
+) -> Result<CallGraph> {
+    let mut graph = CallGraph::default();
+    let mut queue = VecDeque::new();
+    graph.items.insert(item_key(&root_item), root_item.clone());
+    queue.push_back((root_item, 0));
+
+    while let Some((item, depth)) = queue.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        let key = item_key(&item);
+        let mut callees = Vec::new();
+        for call in lsp_server.outgoing_calls(item)? {
+            let callee_key = item_key(&call.to);
+            if !graph.items.contains_key(&callee_key) {
+                graph.items.insert(callee_key.clone(), call.to.clone());
+                queue.push_back((call.to, depth + 1));
+            }
+            if !callees.contains(&callee_key) {
+                callees.push(callee_key);
+            }
+        }
+        graph.edges.insert(key, callees);
+    }
+
+    Ok(graph)
+}
+
+/// Builds an approximate call graph of `files` without an LSP server
+///
+/// Each call is resolved by name to every function or method with that name
+/// declared in `files`. This is fast and works offline but is best-effort:
+/// there is no scope, import, overload or receiver type resolution, so calls
+/// to a common name like `new` get an edge to all declarations of it, and
+/// calls to functions outside `files` are dropped. Calls in top-level code
+/// have no caller and are skipped.
+///
+/// The ranges of the items are in UTF-16, the default LSP position encoding.
+pub fn build_tree_sitter_call_graph(
+    files: &[PathBuf],
+    language: impl Language,
+) -> Result<CallGraph> {
+    let mut parsed = Vec::new();
+    for file_path in files {
+        let source = std::fs::read_to_string(file_path)?;
+        let tree = parse_file_content(&source, language)?;
+        parsed.push((file_path, source, tree));
+    }
+
+    let mut graph = CallGraph::default();
+    // The keys of the declarations by name, and by file and name node start
+    let mut keys_by_name: HashMap<String, Vec<String>> = HashMap::new();
+    let mut keys_by_name_node = HashMap::new();
+    for (index, (file_path, source, tree)) in parsed.iter().enumerate() {
+        let uri = to_file_uri(file_path)?;
+        for (declaration, name_node) in get_function_declarations(tree, language) {
+            let name = name_node.utf8_text(source.as_bytes())?.to_string();
+            let item = CallHierarchyItem {
+                name: name.clone(),
+                kind: SymbolKind::FUNCTION,
+                tags: None,
+                detail: None,
+                uri: uri.clone(),
+                range: byte_range_to_lsp_range(
+                    source,
+                    declaration.range(),
+                    &PositionEncodingKind::UTF16,
+                ),
+                selection_range: byte_range_to_lsp_range(
+                    source,
+                    name_node.range(),
+                    &PositionEncodingKind::UTF16,
+                ),
+                data: None,
+            };
+            let key = item_key(&item);
+            keys_by_name.entry(name).or_default().push(key.clone());
+            keys_by_name_node.insert((index, name_node.start_byte()), key.clone());
+            graph.items.insert(key, item);
+        }
+    }
+
+    for (index, (_, source, tree)) in parsed.iter().enumerate() {
+        for call in get_calls(tree, language) {
+            let Some(caller) = enclosing_function(call.call_node, language)
+                .and_then(|name_node| keys_by_name_node.get(&(index, name_node.start_byte())))
+            else {
+                continue;
+            };
+            let Some(callees) = callee_name(call.goto_definition_node, source.as_bytes())
+                .and_then(|name| keys_by_name.get(name))
+            else {
+                continue;
+            };
+            let edges = graph.edges.entry(caller.clone()).or_default();
+            for callee in callees {
+                if !edges.contains(callee) {
+                    edges.push(callee.clone());
+                }
+            }
+        }
+    }
+
+    Ok(graph)
+}
+
+/// Returns the name of the function called by a call, e.g. `bar` for `foo.bar()`
+///
+/// Follows the callee of the call down to a leaf node through the fields that
+/// the grammars use for called functions and member names.
+fn callee_name<'a>(call: Node<'a>, source: &'a [u8]) -> Option<&'a str> {
+    const CALLEE_FIELDS: &[&str] = &[
+        "function",
+        "constructor",
+        "macro",
+        "name",
+        "field",
+        "property",
+        "attribute",
+        "suffix",
+    ];
+    let mut node = call;
+    while node.named_child_count() > 0 {
+        node = CALLEE_FIELDS
+            .iter()
+            .find_map(|field| node.child_by_field_name(field))
+            .or_else(|| node.named_child(0))?;
+    }
+    node.utf8_text(source).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StubServer;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn item(name: &str, line: u32) -> serde_json::Value {
+        let range = json!({"start": {"line": line, "character": 0}, "end": {"line": line + 2, "character": 1}});
+        json!({
+            "name": name,
+            "kind": 12,
+            "uri": "file:///project/main.rs",
+            "range": range,
+            "selectionRange": range,
+        })
+    }
+
+    fn outgoing_call(to: serde_json::Value) -> serde_json::Value {
+        json!([{"to": to, "fromRanges": []}])
+    }
+
+    #[test]
+    fn test_build_call_graph_with_mutual_recursion() -> Result<()> {
+        // `is_even` calls `is_odd` which calls `is_even` again
+        let stub = StubServer::new()
+            .on_request(
+                "callHierarchy/outgoingCalls",
+                outgoing_call(item("is_odd", 4)),
+            )
+            .on_request(
+                "callHierarchy/outgoingCalls",
+                outgoing_call(item("is_even", 0)),
+            );
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let root: CallHierarchyItem = serde_json::from_value(item("is_even", 0))?;
+
+        let graph = build_call_graph(&mut server, root.clone(), 10)?;
+
+        assert_eq!(graph.items.len(), 2);
+        let callees = graph.callees(&root);
+        assert_eq!(callees.len(), 1);
+        assert_eq!(callees[0].name, "is_odd");
+        assert_eq!(graph.callees(callees[0])[0].name, "is_even");
+        assert_eq!(
+            stub.received_with_method("callHierarchy/outgoingCalls")
+                .len(),
+            2
+        );
+
+        let dot = graph.to_dot();
+        let even = "\"file:///project/main.rs:0:0-2:1\"";
+        let odd = "\"file:///project/main.rs:4:0-6:1\"";
+        assert!(dot.starts_with("digraph calls {\n"));
+        assert!(dot.contains(&format!("  {} [label=\"is_odd\"];\n", odd)));
+        assert!(dot.contains(&format!("  {} -> {};\n", even, odd)));
+        assert!(dot.contains(&format!("  {} -> {};\n", odd, even)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_tree_sitter_call_graph_across_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.rs");
+        let file2 = temp_dir.path().join("file2.rs");
+        std::fs::write(
+            &file1,
+            "fn a() {\n    b();\n    std::process::exit(0);\n}\n",
+        )?;
+        std::fs::write(&file2, "pub fn b() {\n    c::d();\n}\n\nfn d() {}\n")?;
+
+        let graph = build_tree_sitter_call_graph(&[file1, file2.clone()], crate::RustLang)?;
+
+        let item = |name: &str| graph.items.values().find(|item| item.name == name).unwrap();
+        assert_eq!(graph.items.len(), 3);
+        let callees = graph.callees(item("a"));
+        assert_eq!(callees.len(), 1);
+        assert_eq!(callees[0].name, "b");
+        assert_eq!(callees[0].uri, to_file_uri(&file2)?);
+        assert_eq!(callees[0].selection_range.start.character, 7);
+        // Matched by name only, so the path `c::` is ignored
+        assert_eq!(graph.callees(item("b"))[0].name, "d");
+
+        Ok(())
+    }
+}
@@ -1,4 +1,37 @@
-use tree_sitter::Node;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::language::Language;
+use crate::location::ts_point_to_display;
+
+/// A call node detached from its syntax tree
+///
+/// Unlike `Node` this does not borrow from the tree, so it can be stored after
+/// the tree has been dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedCallNode {
+    /// The kind of the tree-sitter node
+    pub kind: String,
+    /// The source text of the node
+    pub text: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_position: Point,
+    pub end_position: Point,
+}
+
+impl OwnedCallNode {
+    /// Copies the data of `node` from the tree, `source` is the source it was parsed from
+    pub fn from_node(node: Node<'_>, source: &[u8]) -> OwnedCallNode {
+        OwnedCallNode {
+            kind: node.kind().to_string(),
+            text: node.utf8_text(source).unwrap_or_default().to_string(),
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            start_position: node.start_position(),
+            end_position: node.end_position(),
+        }
+    }
+}
 
 pub struct CallNode<'tree> {
     // The node representing the function/method call
@@ -8,6 +41,115 @@ pub struct CallNode<'tree> {
 }
 
 impl<'tree> CallNode<'tree> {
+    /// Finds the innermost call that contains the given 0-based `row` and `col`
+    ///
+    /// This is useful for mapping an LSP cursor position to the call under the
+    /// cursor. Returns None if the position is not inside a call.
+    pub fn at_position(
+        tree: &'tree Tree,
+        language: impl Language,
+        row: usize,
+        col: usize,
+    ) -> Option<CallNode<'tree>> {
+        let point = Point::new(row, col);
+        let mut node = tree.root_node().descendant_for_point_range(point, point);
+        while let Some(current) = node {
+            if language.call_node_kinds().contains(&current.kind())
+                && let Some(goto_definition_node) = language.find_call(current)
+            {
+                return Some(CallNode {
+                    call_node: current,
+                    goto_definition_node,
+                });
+            }
+            node = current.parent();
+        }
+        None
+    }
+
+    /// Returns true if the call is a macro invocation rather than a function call
+    pub fn is_macro(&self, language: impl Language) -> bool {
+        language
+            .macro_call_node_kinds()
+            .contains(&self.call_node.kind())
+    }
+
+    /// Returns true if the call constructs an object, like `new Foo()`
+    ///
+    /// Decided by `Language::constructor_node_kinds`, so this is always false
+    /// for languages where constructors are called like functions.
+    pub fn is_constructor_call(&self, language: impl Language) -> bool {
+        language
+            .constructor_node_kinds()
+            .contains(&self.call_node.kind())
+    }
+
+    /// Returns true if the result of the call is awaited, like `await foo()`
+    ///
+    /// The call must be the awaited expression itself, possibly in parentheses,
+    /// as decided by `Language::async_call_node_kinds`. In `foo().await.bar()`
+    /// only `foo()` is async.
+    pub fn is_async_call(&self, language: impl Language) -> bool {
+        let mut parent = self.call_node.parent();
+        let parenthesized_kinds = language.parenthesized_node_kinds();
+        while let Some(node) = parent.filter(|node| parenthesized_kinds.contains(&node.kind())) {
+            parent = node.parent();
+        }
+        parent.is_some_and(|node| language.async_call_node_kinds().contains(&node.kind()))
+    }
+
+    /// Returns true if the called function is given explicit type arguments
+    ///
+    /// Looks for the node kinds of `Language::generic_type_node_kinds` in the
+    /// callee, e.g. `Foo::<T>::bar()` or `Array<string>.from()`. Type arguments
+    /// inside the arguments or in nested calls of the callee are not counted.
+    pub fn has_type_arguments(&self, language: impl Language) -> bool {
+        let generic_kinds = language.generic_type_node_kinds();
+        let mut stack = vec![self.call_node];
+        while let Some(node) = stack.pop() {
+            if generic_kinds.contains(&node.kind()) {
+                return true;
+            }
+            let mut cursor = node.walk();
+            if !cursor.goto_first_child() {
+                continue;
+            }
+            loop {
+                let child = cursor.node();
+                if cursor.field_name() != Some("arguments")
+                    && !language.call_node_kinds().contains(&child.kind())
+                {
+                    stack.push(child);
+                }
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns the positions of the arguments that are number literals, like in `scale(x, 2.0)`
+    ///
+    /// Negated literals like `-1` and literal values of keyword arguments like
+    /// `round(x, digits=2)` count too. The literal kinds come from
+    /// `Language::number_literal_node_kinds`. Calls without an `arguments`
+    /// field, such as macro invocations, have no literal arguments.
+    pub fn literal_argument_positions(&self, language: impl Language) -> Vec<usize> {
+        let literal_kinds = language.number_literal_node_kinds();
+        let Some(arguments) = self.call_node.child_by_field_name("arguments") else {
+            return vec![];
+        };
+        let mut cursor = arguments.walk();
+        arguments
+            .named_children(&mut cursor)
+            .filter(|argument| !argument.is_extra())
+            .enumerate()
+            .filter(|(_, argument)| is_number_literal(*argument, literal_kinds))
+            .map(|(position, _)| position)
+            .collect()
+    }
+
     /// Pretty prints the call node with visual indicators for the call and goto definition ranges
     ///
     /// This method displays the source line with underline markers showing where the call
@@ -37,7 +179,8 @@ impl<'tree> CallNode<'tree> {
             let mut output = Vec::new();
 
             // Source line with line number
-            output.push(format!("{}: {}", line_num + 1, source_line));
+            let display_line = ts_point_to_display(self.call_node.start_position()).line;
+            output.push(format!("{}: {}", display_line, source_line));
 
             // Create underline for call node
             let mut call_underline = String::new();
@@ -50,7 +193,7 @@ impl<'tree> CallNode<'tree> {
             goto_underline.push_str("~".repeat(goto_end_col - goto_start_col).as_str());
 
             // Print with proper indentation (matching line number width)
-            let indent = " ".repeat(format!("{}", line_num + 1).len() + 2);
+            let indent = " ".repeat(format!("{}", display_line).len() + 2);
             output.push(format!("{}{} call", indent, call_underline));
             output.push(format!("{}{} goto definition", indent, goto_underline));
 
@@ -60,3 +203,159 @@ impl<'tree> CallNode<'tree> {
         }
     }
 }
+
+/// Returns true if the node is a number literal, possibly negated or as the value of a keyword argument
+fn is_number_literal(node: Node<'_>, literal_kinds: &[&str]) -> bool {
+    if literal_kinds.contains(&node.kind()) {
+        return true;
+    }
+    if let Some(value) = node.child_by_field_name("value") {
+        return is_number_literal(value, literal_kinds);
+    }
+    // A unary minus is the `-` token followed by the operand in all the grammars
+    node.child_count() == 2
+        && node.child(0).is_some_and(|operator| operator.kind() == "-")
+        && node
+            .child(1)
+            .is_some_and(|operand| literal_kinds.contains(&operand.kind()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{get_calls, parse_file_content};
+
+    #[test]
+    fn test_owned_call_node_outlives_tree() -> anyhow::Result<()> {
+        let source = "fn main() {\n    foo(1);\n}";
+        let owned = {
+            let tree = parse_file_content(source, crate::RustLang)?;
+            let call = get_calls(&tree, crate::RustLang).next().unwrap();
+            OwnedCallNode::from_node(call.call_node, source.as_bytes())
+        };
+
+        assert_eq!(owned.kind, "call_expression");
+        assert_eq!(owned.text, "foo(1)");
+        assert_eq!(owned.start_position, Point::new(1, 4));
+        assert_eq!(owned.end_position, Point::new(1, 10));
+        assert_eq!(&source[owned.start_byte..owned.end_byte], "foo(1)");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_node_at_position() -> anyhow::Result<()> {
+        let source = "fn main() {\n    foo(bar(1));\n}";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let text = |call: CallNode| {
+            call.call_node
+                .utf8_text(source.as_bytes())
+                .unwrap()
+                .to_string()
+        };
+
+        let inner = CallNode::at_position(&tree, crate::RustLang, 1, 12).unwrap();
+        assert_eq!(text(inner), "bar(1)");
+        let outer = CallNode::at_position(&tree, crate::RustLang, 1, 5).unwrap();
+        assert_eq!(
+            outer.goto_definition_node.start_position(),
+            Point::new(1, 4)
+        );
+        assert_eq!(text(outer), "foo(bar(1))");
+        assert!(CallNode::at_position(&tree, crate::RustLang, 0, 3).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_type_arguments() -> anyhow::Result<()> {
+        fn generic_calls(source: &str, language: impl Language) -> anyhow::Result<Vec<bool>> {
+            let tree = parse_file_content(source, language)?;
+            Ok(get_calls(&tree, language)
+                .map(|call| call.has_type_arguments(language))
+                .collect())
+        }
+
+        assert_eq!(
+            generic_calls(
+                "fn f() { Foo::<T>::bar(); foo::<u8>(); x.parse::<i32>(); foo(Vec::<u8>::new()); }",
+                crate::RustLang
+            )?,
+            [true, true, true, false, true]
+        );
+        assert_eq!(
+            generic_calls(
+                "Array<string>.from(x); foo<number>(1); new Map<string, number>(); a.b(c<T>());",
+                crate::TypeScriptLang
+            )?,
+            [true, true, true, false, true]
+        );
+        assert_eq!(
+            generic_calls(
+                "package main\nfunc f() { Map[int, string](); foo(x) }",
+                crate::GoLang
+            )?,
+            [true, false]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_constructor_call() -> anyhow::Result<()> {
+        fn constructor_calls(source: &str, language: impl Language) -> anyhow::Result<Vec<bool>> {
+            let tree = parse_file_content(source, language)?;
+            Ok(get_calls(&tree, language)
+                .map(|call| call.is_constructor_call(language))
+                .collect())
+        }
+
+        assert_eq!(
+            constructor_calls("new Map(); create(); new a.B();", crate::TypeScriptLang)?,
+            [true, false, true]
+        );
+        assert_eq!(
+            constructor_calls("<?php\n$c = new Calc();\n$c->add(1);\n", crate::PhpLang)?,
+            [true, false]
+        );
+        assert_eq!(
+            constructor_calls("Calc()\ncreate()\n", crate::PythonLang)?,
+            [false, false]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_literal_argument_positions() -> anyhow::Result<()> {
+        fn literal_positions(source: &str, language: impl Language) -> anyhow::Result<Vec<usize>> {
+            let tree = parse_file_content(source, language)?;
+            let call = get_calls(&tree, language).next().unwrap();
+            Ok(call.literal_argument_positions(language))
+        }
+
+        assert_eq!(
+            literal_positions(
+                "fn f() { g(-1, 2.5, x + 1, 0x10, \"3\"); }",
+                crate::RustLang
+            )?,
+            [0, 1, 3]
+        );
+        assert_eq!(
+            literal_positions("g(-1, x, 2.5, y=3, z=-4, w=v)\n", crate::PythonLang)?,
+            [0, 2, 3, 4]
+        );
+        assert_eq!(
+            literal_positions("package m\nfunc f() { g(-1, x, 2.5) }", crate::GoLang)?,
+            [0, 2]
+        );
+        assert_eq!(
+            literal_positions("g(-1, x, 2.5, 10n, -y);", crate::TypeScriptLang)?,
+            [0, 2, 3]
+        );
+        // Macro arguments are token trees
+        assert!(literal_positions("fn f() { println!(\"{}\", 1); }", crate::RustLang)?.is_empty());
+
+        Ok(())
+    }
+}
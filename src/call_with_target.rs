@@ -1,42 +1,160 @@
-use lsp_types::Location;
+use anyhow::Result;
+use lsp_types::{Location, Position, Range};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+use crate::call_node::OwnedCallNode;
+use crate::location::{lsp_to_display, ts_point_to_display};
+use crate::lsp::response_locations;
+use crate::uri::display_uri;
 
 /// A call and its definition
 #[derive(Debug, Clone)]
 pub struct CallWithTarget {
     /// The path to the file containing the call
     pub file_path: std::path::PathBuf,
-    /// The tree-sitter node representing the call
-    pub call_node: tree_sitter::Node<'static>,
+    /// The call, detached from the syntax tree it was found in
+    pub call_node: OwnedCallNode,
+    /// The start of the name of the function or method containing the call,
+    /// see `enclosing_function`, or None for top-level code
+    pub caller: Option<tree_sitter::Point>,
     /// The LSP definition response for the call
     pub definition: lsp_types::GotoDefinitionResponse,
+    /// The number of calls this entry represents, greater than one when
+    /// identical call edges have been collapsed
+    pub count: usize,
+}
+
+/// A serializable form of `CallWithTarget` that does not borrow from a syntax tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializableCallDefinition {
+    /// The path to the file containing the call
+    pub file_path: std::path::PathBuf,
+    /// The range of the call node (0-based lines, byte columns)
+    pub call_range: Range,
+    /// The LSP definition response for the call, missing for calls found
+    /// without an LSP server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition: Option<lsp_types::GotoDefinitionResponse>,
+    /// The number of calls this entry represents
+    pub count: usize,
+}
+
+impl SerializableCallDefinition {
+    /// Returns an entry without a definition for a call found without an LSP server
+    pub fn without_definition(file_path: &std::path::Path, call_node: &OwnedCallNode) -> Self {
+        SerializableCallDefinition {
+            file_path: file_path.to_path_buf(),
+            call_range: call_range(call_node),
+            definition: None,
+            count: 1,
+        }
+    }
+}
+
+/// Returns the range of a call node (0-based lines, byte columns)
+fn call_range(call_node: &OwnedCallNode) -> Range {
+    let start = call_node.start_position;
+    let end = call_node.end_position;
+    Range {
+        start: Position::new(start.row as u32, start.column as u32),
+        end: Position::new(end.row as u32, end.column as u32),
+    }
+}
+
+impl From<&CallWithTarget> for SerializableCallDefinition {
+    fn from(call: &CallWithTarget) -> Self {
+        SerializableCallDefinition {
+            file_path: call.file_path.clone(),
+            call_range: call_range(&call.call_node),
+            definition: Some(call.definition.clone()),
+            count: call.count,
+        }
+    }
 }
 
 fn pretty_print_location(call: &CallWithTarget, location: &Location) -> String {
-    let call_pos = call.call_node.start_position();
+    let count = if call.count > 1 {
+        format!(" ({} calls)", call.count)
+    } else {
+        String::new()
+    };
     format!(
-        "Call {}:{}:{} targets {}:{}:{}",
+        "Call {}:{} targets {}:{}{}",
         call.file_path.display(),
-        call_pos.row + 1,
-        call_pos.column + 1,
-        location.uri.path(),
-        location.range.start.line + 1,
-        location.range.start.character + 1
+        ts_point_to_display(call.call_node.start_position),
+        display_uri(&location.uri),
+        lsp_to_display(location.range.start),
+        count
     )
 }
 
 impl CallWithTarget {
-    pub fn pretty_print(&self) -> Vec<String> {
-        match &self.definition {
-            lsp_types::GotoDefinitionResponse::Scalar(location) => {
-                vec![pretty_print_location(self, location)]
-            }
-            lsp_types::GotoDefinitionResponse::Array(locations) => locations
-                .iter()
-                .map(|loc| pretty_print_location(self, loc))
-                .collect(),
-            lsp_types::GotoDefinitionResponse::Link(_links) => {
-                panic!("Definition links are not supported for pretty printing")
-            }
+    /// Writes calls as newline-delimited JSON, one `SerializableCallDefinition` per line
+    pub fn write_ndjson(
+        writer: &mut impl Write,
+        calls: impl Iterator<Item = CallWithTarget>,
+    ) -> std::io::Result<()> {
+        for call in calls {
+            serde_json::to_writer(&mut *writer, &SerializableCallDefinition::from(&call))?;
+            writer.write_all(b"\n")?;
         }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads calls written by `write_ndjson`, skipping blank lines
+    pub fn read_ndjson(
+        reader: &mut impl BufRead,
+    ) -> impl Iterator<Item = Result<SerializableCallDefinition>> + '_ {
+        reader
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+    }
+
+    pub fn pretty_print(&self) -> Vec<String> {
+        response_locations(self.definition.clone())
+            .iter()
+            .map(|loc| pretty_print_location(self, loc))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{get_calls, parse_file_content};
+
+    #[test]
+    fn test_ndjson_round_trip() -> Result<()> {
+        let source = "fn main() {\n    foo();\n    bar();\n}";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let location = Location {
+            uri: "file:///project/src/lib.rs".parse()?,
+            range: Range::new(Position::new(3, 0), Position::new(3, 6)),
+        };
+        let calls: Vec<CallWithTarget> = get_calls(&tree, crate::RustLang)
+            .map(|call| CallWithTarget {
+                file_path: "src/main.rs".into(),
+                call_node: OwnedCallNode::from_node(call.call_node, source.as_bytes()),
+                caller: None,
+                definition: lsp_types::GotoDefinitionResponse::Scalar(location.clone()),
+                count: 1,
+            })
+            .collect();
+        let expected: Vec<SerializableCallDefinition> =
+            calls.iter().map(SerializableCallDefinition::from).collect();
+
+        let mut buffer = Vec::new();
+        CallWithTarget::write_ndjson(&mut buffer, calls.into_iter())?;
+        assert_eq!(String::from_utf8(buffer.clone())?.lines().count(), 2);
+
+        let read =
+            CallWithTarget::read_ndjson(&mut buffer.as_slice()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(read, expected);
+        assert_eq!(read[1].call_range.start, Position::new(2, 4));
+
+        Ok(())
     }
 }
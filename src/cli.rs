@@ -1,21 +1,31 @@
 //! Shared command-line argument parsing for all binaries.
 
 use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{ArgAction, Parser, ValueEnum};
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::level_filters::LevelFilter;
 
-use crate::FileSearchConfig;
+use crate::file_search::workspace_root;
+use crate::project_config::ProjectConfig;
+use crate::{
+    ErrorPolicy, FileSearchConfig, GoLang, LanguageCounter, LspServerConfig, PhpLang, PythonLang,
+    RustLang, SwiftLang, TypeScriptLang,
+};
 
 /// Common command-line arguments for all LSP experiment binaries
 #[derive(Parser, Debug)]
 #[command(version, about)]
 pub struct Args {
-    /// Path to the project directory to analyze
+    /// Path to the project directory to analyze, or a single file to analyze
+    /// with its parent directory as the workspace root
     pub project_path: PathBuf,
 
-    /// Programming language to analyze
+    /// Programming language to analyze, detected from the file extension
+    /// when a single file is given
     #[arg(short, long, value_name = "LANGUAGE")]
-    pub language: String,
+    pub language: Option<String>,
 
     /// Glob pattern to include specific files (e.g., '**/src/**')
     #[arg(long, value_name = "PATTERN")]
@@ -25,53 +35,262 @@ pub struct Args {
     #[arg(long, value_name = "PATTERN")]
     pub exclude: Vec<String>,
 
-    /// Optional path to write JSON output to
+    /// Maximum directory depth to search, 1 only searches the project directory itself
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Path to a config file, defaults to `.ts-lsp.toml` in the project root if it exists
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Optional path to write JSON or CSV output to
     #[arg(long, value_name = "PATH")]
     pub output: Option<PathBuf>,
+
+    /// Collapse calls from the same function that resolve to the same target into a
+    /// single entry with a count
+    #[arg(long)]
+    pub dedup_edges: bool,
+
+    /// Write results as newline-delimited JSON (to --output if given, otherwise stdout),
+    /// same as `--format json`
+    #[arg(long)]
+    pub streaming: bool,
+
+    /// Format to write results in, JSON and CSV are written to --output if given,
+    /// otherwise stdout
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Log more, can be repeated (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log less and only print summaries, can be repeated (-q errors only, -qq no logs)
+    #[arg(short, long, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Only print the aggregate counts and timings, without changing what is logged
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// Analyze with tree-sitter only, without starting an LSP server
+    #[arg(long)]
+    pub no_lsp: bool,
+
+    /// Append all messages exchanged with the LSP server to this file
+    #[arg(long, value_name = "FILE")]
+    pub protocol_log: Option<PathBuf>,
+
+    /// Stop at the first file that can not be read or parsed instead of skipping it
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// The config file, loaded once by `parse_and_validate`
+    #[arg(skip)]
+    project_config: ProjectConfig,
+}
+
+/// The formats results can be written in
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// Newline-delimited JSON
+    Json,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+/// Initializes logging with the given maximum level
+///
+/// `RUST_LOG` takes precedence over `level` when it is set.
+pub fn init_tracing(level: LevelFilter) {
+    let filter = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+}
+
+/// Prints the human readable output of a binary to stdout
+///
+/// In summary-only mode the details printed for each file and item are left
+/// out so that only the summaries remain. When results are written as JSON or
+/// CSV everything is printed to stderr instead, so that stdout only holds the
+/// results.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reporter {
+    pub summary_only: bool,
+    pub to_stderr: bool,
+}
+
+impl Reporter {
+    /// Returns true if details about individual items are printed
+    pub fn shows_details(&self) -> bool {
+        !self.summary_only
+    }
+
+    /// Prints a line of detail about an individual item, unless in summary-only mode
+    pub fn detail(&self, line: impl Display) {
+        if self.to_stderr {
+            self.write_detail(&mut std::io::stderr().lock(), line)
+        } else {
+            self.write_detail(&mut std::io::stdout().lock(), line)
+        }
+        .expect("Failed to write the output");
+    }
+
+    /// Prints a line that is shown even in summary-only mode, like a banner or a summary
+    pub fn status(&self, line: impl Display) {
+        if self.to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    /// Writes a line of detail to `writer`, unless in summary-only mode
+    pub fn write_detail(&self, writer: &mut impl Write, line: impl Display) -> std::io::Result<()> {
+        if self.shows_details() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
 }
 
 impl Args {
     /// Parse command-line arguments and validate inputs
     pub fn parse_and_validate() -> Result<Self> {
-        let args = Self::parse();
+        Self::parse().validate()
+    }
 
+    /// Validates the arguments, makes the project path absolute, detects
+    /// the language of a single file and loads the config file
+    fn validate(mut self) -> Result<Self> {
         // Verify the project path exists
-        if !args.project_path.exists() {
+        if !self.project_path.exists() {
             anyhow::bail!(
                 "Project path does not exist: {}",
-                args.project_path.display()
-            );
-        }
-
-        if !args.project_path.is_dir() {
-            anyhow::bail!(
-                "Project path is not a directory: {}",
-                args.project_path.display()
+                self.project_path.display()
             );
         }
+        // File URIs can only be created for absolute paths
+        self.project_path = self.project_path.canonicalize().map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to canonicalize project path {}: {}",
+                self.project_path.display(),
+                e
+            )
+        })?;
 
         // Validate language
-        match args.language.as_str() {
-            "rust" | "python" | "typescript" | "go" | "swift" => {}
+        let language = match self.language.take() {
+            Some(language) => language,
+            None => detect_language(&self.project_path)?,
+        };
+        match language.as_str() {
+            "rust" | "python" | "typescript" | "go" | "swift" | "php" => {}
             _ => anyhow::bail!(
-                "Unsupported language: '{}'. Supported languages: rust, python, typescript, go, swift",
-                args.language
+                "Unsupported language: '{}'. Supported languages: rust, python, typescript, go, swift, php",
+                language
             ),
         }
+        self.language = Some(language);
+        self.project_config = self.load_project_config()?;
+
+        Ok(self)
+    }
+
+    /// Returns the language to analyze, given with `--language` or detected
+    /// by `parse_and_validate`
+    pub fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the log level selected with `-v` and `-q`, warnings by default
+    pub fn log_level(&self) -> LevelFilter {
+        match (self.verbose, self.quiet) {
+            (0, 0) => LevelFilter::WARN,
+            (0, 1) => LevelFilter::ERROR,
+            (0, _) => LevelFilter::OFF,
+            (1, _) => LevelFilter::INFO,
+            (2, _) => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Returns the reporter for printing results, only printing summaries
+    /// with `--summary-only` or `-q` and printing to stderr with `--format json|csv`
+    pub fn reporter(&self) -> Reporter {
+        Reporter {
+            summary_only: self.summary_only || self.quiet > 0,
+            to_stderr: self.output_format() != OutputFormat::Text,
+        }
+    }
+
+    /// Returns the output format, taking `--streaming` into account
+    pub fn output_format(&self) -> OutputFormat {
+        match self.format {
+            OutputFormat::Text if self.streaming => OutputFormat::Json,
+            format => format,
+        }
+    }
+
+    /// Returns how files that can not be analyzed are handled, see `--fail-fast`
+    pub fn error_policy(&self) -> ErrorPolicy {
+        if self.fail_fast {
+            ErrorPolicy::FailFast
+        } else {
+            ErrorPolicy::Continue
+        }
+    }
 
-        Ok(args)
+    /// Returns a writer for the file given with `--output`, or stdout
+    pub fn output_writer(&self) -> Result<Box<dyn Write>> {
+        Ok(match &self.output {
+            Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+            None => Box::new(std::io::stdout().lock()),
+        })
     }
 
-    /// Create a FileSearchConfig from the include/exclude patterns
+    /// Returns the config file given with `--config` or found in the project root,
+    /// or the default config if there is none
+    pub fn project_config(&self) -> &ProjectConfig {
+        &self.project_config
+    }
+
+    /// Loads the config file given with `--config` or found in the project root
+    fn load_project_config(&self) -> Result<ProjectConfig> {
+        let path = match &self.config {
+            Some(path) => Some(path.clone()),
+            None => ProjectConfig::find(workspace_root(&self.project_path)),
+        };
+        match path {
+            Some(path) => {
+                tracing::info!("Using config file: {}", path.display());
+                ProjectConfig::load(&path)
+            }
+            None => Ok(ProjectConfig::default()),
+        }
+    }
+
+    /// Create a FileSearchConfig from the config file and the include/exclude patterns
+    ///
+    /// Command-line flags take precedence over the config file.
     pub fn create_file_search_config(&self) -> Result<FileSearchConfig> {
-        let mut config = FileSearchConfig::default();
+        let mut config = self.project_config.file_search_config()?;
+        if self.max_depth.is_some() {
+            config.max_depth = self.max_depth;
+        }
 
         if let Some(pattern) = &self.include {
             let glob_pattern = glob::Pattern::new(pattern).map_err(|e| {
                 anyhow::anyhow!("Invalid include glob pattern '{}': {}", pattern, e)
             })?;
             config.include_glob = Some(glob_pattern);
-            println!("Using include pattern: {}", pattern);
+            self.reporter()
+                .status(format_args!("Using include pattern: {}", pattern));
         }
 
         if !self.exclude.is_empty() {
@@ -81,11 +300,204 @@ impl Args {
                     anyhow::anyhow!("Invalid exclude glob pattern '{}': {}", pattern, e)
                 })?;
                 exclude_patterns.push(glob_pattern);
-                println!("Using exclude pattern: {}", pattern);
+                self.reporter()
+                    .status(format_args!("Using exclude pattern: {}", pattern));
             }
             config.exclude_globs = exclude_patterns;
         }
 
         Ok(config)
     }
+
+    /// Create an LspServerConfig from the config file
+    pub fn create_lsp_server_config(&self) -> Result<LspServerConfig> {
+        let mut config = self.project_config.lsp_server_config();
+        config.disabled |= self.no_lsp;
+        if let Some(path) = &self.protocol_log {
+            config.protocol_log_path = Some(path.clone());
+        }
+        Ok(config)
+    }
+}
+
+/// Returns the `cli_name` of the language of a single file from its extension
+fn detect_language(path: &Path) -> Result<String> {
+    if !path.is_file() {
+        anyhow::bail!("--language is required when analyzing a directory");
+    }
+    let languages: [&dyn LanguageCounter; 6] = [
+        &RustLang,
+        &PythonLang,
+        &TypeScriptLang,
+        &GoLang,
+        &SwiftLang,
+        &PhpLang,
+    ];
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    for language in languages {
+        if language.counter_file_regex()?.is_match(file_name) {
+            return Ok(language.counter_name().to_string());
+        }
+    }
+    anyhow::bail!(
+        "Could not detect the language of {}, please pass --language",
+        path.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_max_depth_excludes_nested_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::create_dir_all(temp_path.join("src/nested"))?;
+        fs::write(temp_path.join("build.rs"), "fn main() {}")?;
+        fs::write(temp_path.join("src/lib.rs"), "pub fn hello() {}")?;
+        fs::write(temp_path.join("src/nested/deep.rs"), "pub fn deep() {}")?;
+
+        let args = Args::try_parse_from([
+            "test",
+            temp_path.to_str().unwrap(),
+            "--language",
+            "rust",
+            "--max-depth",
+            "2",
+        ])?;
+        let config = args.create_file_search_config()?;
+        assert_eq!(config.max_depth, Some(2));
+
+        let mut files = config.find_language_files(temp_path, crate::RustLang)?;
+        files.sort();
+        assert_eq!(
+            files,
+            vec![temp_path.join("build.rs"), temp_path.join("src/lib.rs")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verbosity_flags() -> Result<()> {
+        let parse = |flags: &[&str]| {
+            Args::try_parse_from(["test", ".", "--language", "rust"].iter().chain(flags))
+        };
+
+        let args = parse(&["-q"])?;
+        assert_eq!(args.log_level(), LevelFilter::ERROR);
+        assert!(!args.reporter().shows_details());
+        assert_eq!(parse(&["-qq"])?.log_level(), LevelFilter::OFF);
+
+        let args = parse(&[])?;
+        assert_eq!(args.log_level(), LevelFilter::WARN);
+        assert!(args.reporter().shows_details());
+        assert!(!args.reporter().to_stderr);
+        assert!(parse(&["--format", "json"])?.reporter().to_stderr);
+        assert_eq!(parse(&["-vv"])?.log_level(), LevelFilter::DEBUG);
+        assert!(parse(&["-v", "-q"]).is_err());
+
+        let args = parse(&["--summary-only"])?;
+        assert_eq!(args.log_level(), LevelFilter::WARN);
+        assert!(!args.reporter().shows_details());
+
+        assert_eq!(parse(&[])?.output_format(), OutputFormat::Text);
+        assert_eq!(parse(&["--streaming"])?.output_format(), OutputFormat::Json);
+        assert_eq!(
+            parse(&["--format", "csv"])?.output_format(),
+            OutputFormat::Csv
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_only_emits_no_details() -> Result<()> {
+        let mut output = Vec::new();
+        let summary_only = Reporter {
+            summary_only: true,
+            ..Default::default()
+        };
+        summary_only.write_detail(&mut output, "  1. src/lib.rs:1:1")?;
+        assert!(output.is_empty());
+
+        Reporter::default().write_detail(&mut output, "  1. src/lib.rs:1:1")?;
+        assert_eq!(String::from_utf8(output)?, "  1. src/lib.rs:1:1\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_detected_for_single_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::write(temp_path.join("main.py"), "print(1)")?;
+        fs::write(temp_path.join("notes.txt"), "")?;
+        let validate = |path: &Path, flags: &[&str]| {
+            Args::try_parse_from(["test", path.to_str().unwrap()].iter().chain(flags))?.validate()
+        };
+
+        assert_eq!(
+            validate(&temp_path.join("main.py"), &[])?.language(),
+            "python"
+        );
+        // An explicit --language takes precedence
+        assert_eq!(
+            validate(&temp_path.join("main.py"), &["--language", "rust"])?.language(),
+            "rust"
+        );
+        assert!(
+            validate(&temp_path.join("notes.txt"), &[])
+                .unwrap_err()
+                .to_string()
+                .starts_with("Could not detect the language")
+        );
+        assert!(validate(temp_path, &[]).is_err());
+        assert_eq!(validate(temp_path, &["--language", "go"])?.language(), "go");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_project_path_is_made_absolute() -> Result<()> {
+        for path in [".", "src", "src/main.rs"] {
+            let args = Args::try_parse_from(["test", path, "--language", "rust"])?.validate()?;
+            assert!(args.project_path.is_absolute());
+            assert_eq!(args.project_path, Path::new(path).canonicalize()?);
+            crate::uri::to_file_uri(workspace_root(&args.project_path))?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_flags_override_config_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::write(
+            temp_path.join(crate::project_config::PROJECT_CONFIG_FILE_NAME),
+            "include = \"**/src/**\"\nmax_depth = 1\n\n[server]\nargs = [\"--verbose\"]\n",
+        )?;
+
+        let args = Args::try_parse_from([
+            "test",
+            temp_path.to_str().unwrap(),
+            "--language",
+            "rust",
+            "--max-depth",
+            "3",
+        ])?
+        .validate()?;
+        // The config file is read once, when the arguments are validated
+        fs::remove_file(temp_path.join(crate::project_config::PROJECT_CONFIG_FILE_NAME))?;
+        let config = args.create_file_search_config()?;
+        assert_eq!(config.max_depth, Some(3));
+        assert_eq!(config.include_glob.unwrap().as_str(), "**/src/**");
+        assert_eq!(args.create_lsp_server_config()?.args, vec!["--verbose"]);
+
+        Ok(())
+    }
 }
@@ -2,10 +2,13 @@
 
 use anyhow::Result;
 use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 
-use crate::language::Language;
+use crate::language::{Language, LanguageCounter};
 
 /// Configuration for file searching behavior
 #[derive(Debug, Clone)]
@@ -18,6 +21,8 @@ pub struct FileSearchConfig {
     pub include_glob: Option<glob::Pattern>,
     /// Glob patterns to exclude matching files (empty = no filtering)
     pub exclude_globs: Vec<glob::Pattern>,
+    /// Skip files that the language identifies as generated code
+    pub skip_generated: bool,
 }
 
 impl Default for FileSearchConfig {
@@ -38,12 +43,82 @@ impl Default for FileSearchConfig {
             max_depth: None,
             include_glob: None,
             exclude_globs: Vec::new(),
+            skip_generated: true,
         }
     }
 }
 
+/// The fields of a `FileSearchConfig` as they appear in a JSON configuration
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSearchSettings {
+    skip_dirs: Option<Vec<String>>,
+    max_depth: Option<usize>,
+    include_glob: Option<String>,
+    #[serde(alias = "exclude_glob")]
+    exclude_globs: Vec<String>,
+    skip_generated: Option<bool>,
+}
+
+/// Returns the directory to use as the workspace root when analyzing `path`
+///
+/// This is `path` itself for directories and the parent directory for files,
+/// which is `.` for a bare file name.
+pub fn workspace_root(path: &Path) -> &Path {
+    if path.is_file() {
+        path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    } else {
+        path
+    }
+}
+
 impl FileSearchConfig {
+    /// Creates a configuration from a JSON object
+    ///
+    /// The object may contain `skip_dirs`, `max_depth`, `include_glob`,
+    /// `exclude_globs` and `skip_generated`. Missing fields keep their defaults.
+    pub fn from_json(value: &serde_json::Value) -> Result<Self> {
+        let settings = FileSearchSettings::deserialize(value)
+            .map_err(|e| anyhow::anyhow!("Invalid file search config: {}", e))?;
+
+        let mut config = FileSearchConfig::default();
+        if let Some(skip_dirs) = settings.skip_dirs {
+            config.skip_dirs = skip_dirs;
+        }
+        config.max_depth = settings.max_depth;
+        if let Some(pattern) = settings.include_glob {
+            config.include_glob = Some(glob::Pattern::new(&pattern).map_err(|e| {
+                anyhow::anyhow!("Invalid include glob pattern '{}': {}", pattern, e)
+            })?);
+        }
+        for pattern in settings.exclude_globs {
+            config
+                .exclude_globs
+                .push(glob::Pattern::new(&pattern).map_err(|e| {
+                    anyhow::anyhow!("Invalid exclude glob pattern '{}': {}", pattern, e)
+                })?);
+        }
+        if let Some(skip_generated) = settings.skip_generated {
+            config.skip_generated = skip_generated;
+        }
+        Ok(config)
+    }
+
+    /// Reads a configuration from a JSON file, see `from_json` for the format
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON in {}: {}", path.display(), e))?;
+        Self::from_json(&value).map_err(|e| anyhow::anyhow!("{} in {}", e, path.display()))
+    }
+
     /// Recursively finds all files in the given directory that match the language's file pattern
+    ///
+    /// If `dir_path` is a file it is the only result, provided that it is a file
+    /// of the language.
     pub fn find_language_files(
         &self,
         dir_path: &Path,
@@ -52,8 +127,22 @@ impl FileSearchConfig {
         let mut matching_files = Vec::new();
         let file_regex = language.file_regex()?;
 
+        if dir_path.is_file() {
+            let file_name = dir_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !file_regex.is_match(file_name) {
+                anyhow::bail!(
+                    "{} is not a {} file (expected {})",
+                    dir_path.display(),
+                    language,
+                    language.extensions()
+                );
+            }
+            return Ok(vec![dir_path.to_path_buf()]);
+        }
+
         self.find_files_recursive(
             dir_path,
+            language,
             &file_regex,
             &self.include_glob,
             &self.exclude_globs,
@@ -64,6 +153,90 @@ impl FileSearchConfig {
         Ok(matching_files)
     }
 
+    /// Counts the files of each of the given languages in a single pass
+    ///
+    /// The counts are keyed by the languages' `cli_name` and include languages
+    /// without any files. A file that matches several languages is counted for
+    /// each of them.
+    pub fn count_by_language(
+        &self,
+        dir_path: &Path,
+        languages: &[&dyn LanguageCounter],
+    ) -> Result<HashMap<String, usize>> {
+        let regexes = languages
+            .iter()
+            .map(|language| language.counter_file_regex())
+            .collect::<Result<Vec<_>>>()?;
+        let mut counts: HashMap<String, usize> = languages
+            .iter()
+            .map(|language| (language.counter_name().to_string(), 0))
+            .collect();
+
+        let mut count_file = |path: &Path| {
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                return;
+            };
+            if !is_path_included(path, &self.include_glob, &self.exclude_globs) {
+                return;
+            }
+            let mut first_lines = None;
+            for (language, regex) in languages.iter().zip(&regexes) {
+                if !regex.is_match(file_name) {
+                    continue;
+                }
+                if self.skip_generated {
+                    let first_lines = first_lines.get_or_insert_with(|| read_first_lines(path));
+                    if language.counter_is_generated_file(path, first_lines) {
+                        continue;
+                    }
+                }
+                *counts
+                    .entry(language.counter_name().to_string())
+                    .or_default() += 1;
+            }
+        };
+
+        if dir_path.is_file() {
+            count_file(dir_path);
+        } else {
+            self.visit_files_recursive(dir_path, 0, &mut count_file)?;
+        }
+
+        Ok(counts)
+    }
+
+    /// Calls `visit` for every file below `dir` that is not in a skipped directory
+    fn visit_files_recursive(
+        &self,
+        dir: &Path,
+        current_depth: usize,
+        visit: &mut impl FnMut(&Path),
+    ) -> Result<()> {
+        if self
+            .max_depth
+            .is_some_and(|max_depth| current_depth >= max_depth)
+        {
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to read directory {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry =
+                entry.map_err(|e| anyhow::anyhow!("Failed to read directory entry: {}", e))?;
+            let path = entry.path();
+            if path.is_dir() {
+                if !self.is_dir_skipped(&path) {
+                    self.visit_files_recursive(&path, current_depth + 1, visit)?;
+                }
+            } else if path.is_file() {
+                visit(&path);
+            }
+        }
+
+        Ok(())
+    }
+
     fn is_dir_skipped(&self, dir: &Path) -> bool {
         if let Some(dir_name) = dir.file_name().and_then(|n| n.to_str()) {
             self.skip_dirs.contains(&dir_name.to_string())
@@ -72,10 +245,25 @@ impl FileSearchConfig {
         }
     }
 
+    /// Returns true if generated files are skipped and `path` is one
+    fn is_file_skipped(&self, path: &Path, language: impl Language) -> bool {
+        if !self.skip_generated {
+            return false;
+        }
+        let first_lines = read_first_lines(path);
+        let generated = language.is_generated_file(path, &first_lines);
+        if generated {
+            tracing::debug!("Skipping generated file {}", path.display());
+        }
+        generated
+    }
+
     /// Helper function to recursively traverse directories and find matching files
+    #[allow(clippy::too_many_arguments)]
     fn find_files_recursive(
         &self,
         dir: &Path,
+        language: impl Language,
         regex: &Regex,
         include_glob: &Option<glob::Pattern>,
         exclude_globs: &[glob::Pattern],
@@ -106,6 +294,7 @@ impl FileSearchConfig {
                 // Recursively search subdirectories
                 self.find_files_recursive(
                     &path,
+                    language,
                     regex,
                     include_glob,
                     exclude_globs,
@@ -115,28 +304,10 @@ impl FileSearchConfig {
             } else if path.is_file()
                 && let Some(file_name) = path.file_name().and_then(|n| n.to_str())
                 && regex.is_match(file_name)
+                && is_path_included(&path, include_glob, exclude_globs)
+                && !self.is_file_skipped(&path, language)
             {
-                let path_str = path.to_str();
-
-                // Check exclude patterns first - if any match, skip this file
-                if let Some(path_str) = path_str
-                    && exclude_globs
-                        .iter()
-                        .any(|pattern| pattern.matches(path_str))
-                {
-                    continue;
-                }
-
-                // Check include pattern if one is specified
-                if let Some(include_pattern) = include_glob
-                    && let Some(path_str) = path_str
-                {
-                    if include_pattern.matches(path_str) {
-                        results.push(path);
-                    }
-                } else {
-                    results.push(path);
-                }
+                results.push(path);
             }
         }
 
@@ -144,6 +315,36 @@ impl FileSearchConfig {
     }
 }
 
+/// Returns true if `path` matches the include pattern, if any, and none of the exclude patterns
+fn is_path_included(
+    path: &Path,
+    include_glob: &Option<glob::Pattern>,
+    exclude_globs: &[glob::Pattern],
+) -> bool {
+    let Some(path_str) = path.to_str() else {
+        return true;
+    };
+    !exclude_globs
+        .iter()
+        .any(|pattern| pattern.matches(path_str))
+        && include_glob
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches(path_str))
+}
+
+/// Returns the first few lines of a file, or an empty string if it can not be read
+fn read_first_lines(path: &Path) -> String {
+    let Ok(file) = fs::File::open(path) else {
+        return String::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .take(5)
+        .map_while(Result::ok)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use crate::RustLang;
@@ -188,4 +389,113 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_file_search_skips_generated_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::write(temp_path.join("main.rs"), "fn main() {}")?;
+        fs::write(temp_path.join("schema_generated.rs"), "pub struct A;")?;
+        fs::write(
+            temp_path.join("bindings.rs"),
+            "// @generated by bindgen\npub struct B;",
+        )?;
+
+        let config = FileSearchConfig::default();
+        let rust_files = config.find_language_files(temp_path, RustLang)?;
+        assert_eq!(rust_files, vec![temp_path.join("main.rs")]);
+
+        let config = FileSearchConfig {
+            skip_generated: false,
+            ..Default::default()
+        };
+        assert_eq!(config.find_language_files(temp_path, RustLang)?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_search_config_from_json() -> Result<()> {
+        let config = FileSearchConfig::from_json(&serde_json::json!({
+            "skip_dirs": ["out"],
+            "max_depth": 2,
+            "include_glob": "**/src/**",
+            "exclude_glob": ["**/*_test.rs"],
+            "skip_generated": false,
+        }))?;
+        assert_eq!(config.skip_dirs, vec!["out"]);
+        assert_eq!(config.max_depth, Some(2));
+        assert_eq!(config.include_glob.unwrap().as_str(), "**/src/**");
+        assert_eq!(config.exclude_globs[0].as_str(), "**/*_test.rs");
+        assert!(!config.skip_generated);
+
+        let config = FileSearchConfig::from_json(&serde_json::json!({}))?;
+        assert_eq!(config.skip_dirs, FileSearchConfig::default().skip_dirs);
+        assert!(config.skip_generated);
+
+        assert!(FileSearchConfig::from_json(&serde_json::json!({"include_glob": "[a"})).is_err());
+        assert!(FileSearchConfig::from_json(&serde_json::json!({"max_dept": 2})).is_err());
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join(".tree-sitter-lsp.json");
+        fs::write(&path, r#"{"max_depth": 3}"#)?;
+        assert_eq!(FileSearchConfig::from_file(&path)?.max_depth, Some(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_search_single_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::write(temp_path.join("main.rs"), "fn main() {}")?;
+        fs::write(temp_path.join("lib.rs"), "pub fn hello() {}")?;
+        fs::write(temp_path.join("README.md"), "# Project")?;
+
+        let config = FileSearchConfig::default();
+        let file_path = temp_path.join("main.rs");
+        let rust_files = config.find_language_files(&file_path, RustLang)?;
+
+        assert_eq!(rust_files, vec![file_path.clone()]);
+        assert_eq!(workspace_root(&file_path), temp_path);
+        assert_eq!(workspace_root(temp_path), temp_path);
+        // The parent of a bare file name is empty, Cargo.toml is in the working directory
+        assert_eq!(workspace_root(Path::new("Cargo.toml")), Path::new("."));
+        assert!(
+            config
+                .find_language_files(&temp_path.join("README.md"), RustLang)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_by_language() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+        fs::create_dir_all(temp_path.join("src"))?;
+        fs::create_dir_all(temp_path.join("scripts"))?;
+        fs::create_dir_all(temp_path.join("node_modules/pkg"))?;
+        fs::write(temp_path.join("src/main.rs"), "fn main() {}")?;
+        fs::write(temp_path.join("src/lib.rs"), "pub fn hello() {}")?;
+        fs::write(
+            temp_path.join("src/bindings.rs"),
+            "// @generated\npub struct B;",
+        )?;
+        fs::write(temp_path.join("scripts/build.py"), "print('hi')")?;
+        fs::write(temp_path.join("node_modules/pkg/index.ts"), "export {}")?;
+
+        let config = FileSearchConfig::default();
+        let counts = config.count_by_language(
+            temp_path,
+            &[&RustLang, &crate::PythonLang, &crate::TypeScriptLang],
+        )?;
+        assert_eq!(counts["rust"], 2);
+        assert_eq!(counts["python"], 1);
+        assert_eq!(counts["typescript"], 0);
+        assert_eq!(counts.len(), 3);
+
+        Ok(())
+    }
 }
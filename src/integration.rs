@@ -2,16 +2,25 @@
 
 use anyhow::Result;
 use lsp_types::{
-    GotoDefinitionParams, Position, TextDocumentIdentifier, TextDocumentPositionParams,
+    CallHierarchyIncomingCall, CodeActionContext, CodeActionOrCommand, GotoDefinitionParams,
+    Location, TextDocumentIdentifier, TextDocumentPositionParams,
 };
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tree_sitter::Node;
 
 use crate::Language;
-use crate::call_node::CallNode;
+use crate::call_node::{CallNode, OwnedCallNode};
 use crate::call_with_target::CallWithTarget;
-use crate::lsp::{LspServer, uri_from_path};
-use crate::parser::{display_node_location, get_calls, parse_file_content};
+use crate::file_search::workspace_root;
+use crate::location::{byte_range_to_lsp_range, ts_point_to_lsp_position};
+use crate::lsp::{LspServer, LspServerUnavailable, RetryPolicy, response_locations};
+use crate::parser::{
+    display_node_location, enclosing_function, get_calls, get_function_declarations,
+    parse_file_content,
+};
+use crate::uri::{display_uri, to_file_uri};
 
 /// Results from analyzing calls in a project
 #[derive(Debug, Clone)]
@@ -20,27 +29,128 @@ pub struct CallAnalysisResults {
     pub calls_with_targets: Vec<CallWithTarget>,
     /// Total number of calls found (including those without definitions)
     pub total_calls: usize,
+    /// False if the analysis ran with tree-sitter only, because the LSP server
+    /// was disabled or could not be started
+    pub lsp_enabled: bool,
+    /// The calls found when the LSP server was not enabled, with their files
+    pub calls_without_lsp: Vec<(PathBuf, OwnedCallNode)>,
+}
+
+/// A function declaration and the calls to it, found through the call hierarchy
+#[derive(Debug, Clone)]
+pub struct DeclarationCallers {
+    /// The file that contains the declaration
+    pub file_path: PathBuf,
+    /// The name of the declared function
+    pub name: String,
+    /// The position of the name of the declaration
+    pub position: tree_sitter::Point,
+    /// The functions that call the declaration, with the ranges of their calls
+    pub callers: Vec<CallHierarchyIncomingCall>,
+}
+
+/// What the project-wide analyses do when a single file can not be analyzed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Logs a warning and goes on with the next file
+    #[default]
+    Continue,
+    /// Stops the analysis and returns the first error
+    FailFast,
+}
+
+impl ErrorPolicy {
+    /// Returns the value of a per-file step, or `None` if it failed and the file is to be skipped
+    ///
+    /// `context` describes the step, like "Failed to read file main.rs". It
+    /// prefixes the warning under `Continue` and is added to the returned
+    /// error under `FailFast`.
+    pub fn handle<T, E: Into<anyhow::Error>>(
+        self,
+        result: std::result::Result<T, E>,
+        context: impl FnOnce() -> String,
+    ) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                let e = e.into();
+                match self {
+                    ErrorPolicy::Continue => {
+                        tracing::warn!("{}: {}", context(), e);
+                        Ok(None)
+                    }
+                    ErrorPolicy::FailFast => Err(e.context(context())),
+                }
+            }
+        }
+    }
+}
+
+/// Groups the calls of a report by the file that their definition resides in
+///
+/// This shows which files the analyzed code depends on. A call with
+/// definitions in several files appears in the group of each of them.
+pub fn group_targets_by_file(
+    report: &CallAnalysisResults,
+) -> HashMap<PathBuf, Vec<CallWithTarget>> {
+    let mut groups: HashMap<PathBuf, Vec<CallWithTarget>> = HashMap::new();
+    for call in &report.calls_with_targets {
+        let target_files: BTreeSet<PathBuf> = response_locations(call.definition.clone())
+            .iter()
+            .map(|location| PathBuf::from(display_uri(&location.uri)))
+            .collect();
+        for target_file in target_files {
+            groups.entry(target_file).or_default().push(call.clone());
+        }
+    }
+    groups
 }
 
-fn point_to_position(point: tree_sitter::Point) -> Position {
-    Position {
-        line: point.row as u32,
-        character: point.column as u32,
+/// Collapses calls from the same caller that resolve to the same target locations
+///
+/// The caller is the enclosing function of the call, all top-level calls of a
+/// file share a caller. Each collapsed entry keeps the first call site and
+/// accumulates the counts of the calls it replaces. The order of first
+/// occurrence is preserved.
+pub fn dedup_call_edges(calls: Vec<CallWithTarget>) -> Vec<CallWithTarget> {
+    type Edge = (
+        PathBuf,
+        Option<tree_sitter::Point>,
+        Vec<(String, lsp_types::Range)>,
+    );
+    let mut edge_indices: HashMap<Edge, usize> = HashMap::new();
+    let mut edges: Vec<CallWithTarget> = Vec::new();
+    for call in calls {
+        let targets = response_locations(call.definition.clone())
+            .into_iter()
+            .map(|location| (location.uri.as_str().to_string(), location.range))
+            .collect();
+        let key = (call.file_path.clone(), call.caller, targets);
+        match edge_indices.get(&key) {
+            Some(&index) => edges[index].count += call.count,
+            None => {
+                edge_indices.insert(key, edges.len());
+                edges.push(call);
+            }
+        }
     }
+    edges
 }
 
 /// Requests go-to-definition from an LSP server for a tree-sitter node
 ///
 /// # Arguments
 /// * `lsp_server` - A running LSP server instance
-/// * `node` - The tree-sitter node to get the definition for
 /// * `file_path` - The path to the file containing the node
+/// * `source` - The contents of the file, used for position encoding conversion
+/// * `node` - The tree-sitter node to get the definition for
 ///
 /// # Returns
 /// The LSP GotoDefinition response, which may be None if no definition is found
 pub fn goto_definition_for_node<L: crate::language::Language>(
     lsp_server: &mut LspServer<L>,
     file_path: &Path,
+    source: &str,
     node: Node,
 ) -> Result<Option<lsp_types::GotoDefinitionResponse>> {
     let start_time = std::time::Instant::now();
@@ -50,16 +160,20 @@ pub fn goto_definition_for_node<L: crate::language::Language>(
     let params = GotoDefinitionParams {
         text_document_position_params: TextDocumentPositionParams {
             text_document: TextDocumentIdentifier {
-                uri: uri_from_path(file_path)?,
+                uri: to_file_uri(file_path)?,
             },
-            position: point_to_position(node.start_position()),
+            position: ts_point_to_lsp_position(
+                node.start_position(),
+                source,
+                &lsp_server.position_encoding,
+            ),
         },
         work_done_progress_params: Default::default(),
         partial_result_params: Default::default(),
     };
 
     // Send the request and get the response
-    let result = lsp_server.request::<lsp_types::request::GotoDefinition>(params);
+    let result = lsp_server.request_retrying::<lsp_types::request::GotoDefinition>(params);
     let elapsed = start_time.elapsed();
     tracing::info!(
         "LSP go-to-definition request took {:.2?} for node at {}",
@@ -69,6 +183,178 @@ pub fn goto_definition_for_node<L: crate::language::Language>(
     result
 }
 
+/// Requests go-to-declaration from an LSP server for a tree-sitter node
+///
+/// This differs from go-to-definition for languages with separate declaration
+/// and definition sites, such as forward declarations or C headers.
+///
+/// # Arguments
+/// * `lsp_server` - A running LSP server instance
+/// * `file_path` - The path to the file containing the node
+/// * `source` - The contents of the file, used for position encoding conversion
+/// * `node` - The tree-sitter node to get the declaration for
+///
+/// # Returns
+/// The locations of the declaration, which is empty if no declaration is found
+pub fn goto_declaration_for_node<L: crate::language::Language>(
+    lsp_server: &mut LspServer<L>,
+    file_path: &Path,
+    source: &str,
+    node: Node,
+) -> Result<Vec<Location>> {
+    let start_time = std::time::Instant::now();
+    let position =
+        ts_point_to_lsp_position(node.start_position(), source, &lsp_server.position_encoding);
+    let result = lsp_server.goto_declaration(file_path, position);
+    let elapsed = start_time.elapsed();
+    tracing::info!(
+        "LSP go-to-declaration request took {:.2?} for node at {}",
+        elapsed,
+        display_node_location(file_path, node)
+    );
+    result
+}
+
+/// Requests the code actions available for the range of a call
+///
+/// This can be used to discover the quick-fixes and refactorings, such as
+/// inlining the called function, that a server offers at a call.
+///
+/// # Arguments
+/// * `lsp_server` - A running LSP server instance
+/// * `file_path` - The path to the file containing the call
+/// * `source` - The contents of the file, used for position encoding conversion
+/// * `call` - The call to request the code actions for
+pub fn code_actions_for_call<L: Language>(
+    lsp_server: &mut LspServer<L>,
+    file_path: &Path,
+    source: &str,
+    call: &CallNode,
+) -> Result<Vec<CodeActionOrCommand>> {
+    let range = byte_range_to_lsp_range(
+        source,
+        call.call_node.range(),
+        &lsp_server.position_encoding,
+    );
+    let start_time = std::time::Instant::now();
+    let actions = lsp_server.code_actions(file_path, range, CodeActionContext::default())?;
+    tracing::debug!(
+        "Code actions for {} took {:?}",
+        display_node_location(file_path, call.call_node),
+        start_time.elapsed()
+    );
+    Ok(actions)
+}
+
+/// Finds the callers of every function declared in a file
+///
+/// This is the inverse of `goto_definition_for_node`. The declarations are found
+/// with tree-sitter, see `get_function_declarations`, and for each of them the
+/// call hierarchy is prepared at its name and its incoming calls are requested.
+/// Declarations the server has no call hierarchy item for are left out.
+///
+/// # Arguments
+/// * `lsp_server` - A running LSP server instance, with the file open
+/// * `file_path` - The path to the file to find the declarations in
+/// * `source` - The contents of the file
+pub fn find_callers_in_file<L: Language>(
+    lsp_server: &mut LspServer<L>,
+    file_path: &Path,
+    source: &str,
+) -> Result<Vec<DeclarationCallers>> {
+    let tree = parse_file_content(source, lsp_server.language)?;
+    let mut declarations = Vec::new();
+    for (i, (_, name_node)) in get_function_declarations(&tree, lsp_server.language).enumerate() {
+        let position = ts_point_to_lsp_position(
+            name_node.start_position(),
+            source,
+            &lsp_server.position_encoding,
+        );
+        // The server might not have finished loading the file for the first declaration
+        let retry = if i == 0 {
+            RetryPolicy::new(5, Duration::from_millis(100))
+        } else {
+            RetryPolicy::NONE
+        };
+        let Some(item) = lsp_server.prepare_call_hierarchy(file_path, position, retry)? else {
+            tracing::debug!(
+                "No call hierarchy item for declaration at {}",
+                display_node_location(file_path, name_node)
+            );
+            continue;
+        };
+        declarations.push(DeclarationCallers {
+            file_path: file_path.to_path_buf(),
+            name: name_node.utf8_text(source.as_bytes())?.to_string(),
+            position: name_node.start_position(),
+            callers: lsp_server.incoming_calls(item)?,
+        });
+    }
+    Ok(declarations)
+}
+
+/// Finds the callers of every function declared in a project, see `find_callers_in_file`
+///
+/// Unlike `find_all_call_targets` there is no tree-sitter fallback, since
+/// callers can only be found by the LSP server.
+pub fn find_all_callers<L: Language>(
+    language: L,
+    project_path: &Path,
+    config: &crate::file_search::FileSearchConfig,
+    lsp_config: crate::lsp::LspServerConfig,
+    error_policy: ErrorPolicy,
+) -> Result<Vec<DeclarationCallers>> {
+    let matching_files = config.find_language_files(project_path, language)?;
+    tracing::info!("Found {} {} files", matching_files.len(), language);
+    if matching_files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(mut lsp_server) = start_lsp_server_or_fallback(language, project_path, lsp_config)?
+    else {
+        anyhow::bail!("Finding callers requires an LSP server");
+    };
+    let results = find_callers_in_files(&mut lsp_server, &matching_files, error_policy);
+
+    if let Err(e) = lsp_server.stop() {
+        tracing::error!("Error stopping LSP server: {}", e);
+    }
+    results
+}
+
+/// Finds the callers of the functions declared in each of `files`, see `find_callers_in_file`
+fn find_callers_in_files<L: Language>(
+    lsp_server: &mut LspServer<L>,
+    files: &[PathBuf],
+    error_policy: ErrorPolicy,
+) -> Result<Vec<DeclarationCallers>> {
+    let mut results = Vec::new();
+    for file_path in files {
+        let Some(file_content) = error_policy.handle(std::fs::read_to_string(file_path), || {
+            format!("Failed to read file {}", file_path.display())
+        })?
+        else {
+            continue;
+        };
+        let Some(()) = error_policy
+            .handle(lsp_server.open_file(file_path, &file_content), || {
+                format!("Failed to open file {}", file_path.display())
+            })?
+        else {
+            continue;
+        };
+        let declarations = error_policy.handle(
+            find_callers_in_file(lsp_server, file_path, &file_content),
+            || format!("Failed to find callers in {}", file_path.display()),
+        );
+        error_policy.handle(lsp_server.close_file(file_path), || {
+            format!("Failed to close file {}", file_path.display())
+        })?;
+        results.extend(declarations?.unwrap_or_default());
+    }
+    Ok(results)
+}
+
 /// Finds all function calls in a project and retrieves their definitions from the LSP server
 ///
 /// This function:
@@ -80,6 +366,10 @@ pub fn goto_definition_for_node<L: crate::language::Language>(
 /// # Arguments
 /// * `language` - The programming language to analyze
 /// * `project_path` - The root directory of the project to analyze
+/// * `config` - Configuration for finding the files to analyze
+/// * `lsp_config` - Configuration for starting the LSP server
+/// * `dedup_edges` - Collapse calls with identical targets into a single entry with a count
+/// * `error_policy` - Whether a file that can not be read or parsed is skipped or stops the analysis
 ///
 /// # Returns
 /// A CallAnalysisResults struct containing:
@@ -88,7 +378,15 @@ pub fn goto_definition_for_node<L: crate::language::Language>(
 ///
 /// # Example
 /// ```ignore
-/// let results = find_all_call_definitions(&RustLang, &PathBuf::from("./my-project"))?
+/// let config = FileSearchConfig::default();
+/// let results = find_all_call_targets(
+///     RustLang,
+///     &PathBuf::from("./my-project"),
+///     &config,
+///     LspServerConfig::default(),
+///     false,
+///     ErrorPolicy::Continue,
+/// )?;
 /// println!("Found {} definitions out of {} total calls", results.calls_with_targets.len(), results.total_calls);
 /// for call in &results.calls_with_targets {
 ///     println!("Call in {}: {:?}", call.file_path.display(), call.definition);
@@ -98,11 +396,43 @@ pub fn find_all_call_targets<L: Language>(
     language: L,
     project_path: &Path,
     config: &crate::file_search::FileSearchConfig,
+    lsp_config: crate::lsp::LspServerConfig,
+    dedup_edges: bool,
+    error_policy: ErrorPolicy,
 ) -> Result<CallAnalysisResults> {
-    use std::fs;
-
-    let mut results = Vec::new();
+    let mut calls = Vec::new();
+    let mut results = stream_call_targets(
+        language,
+        project_path,
+        config,
+        lsp_config,
+        error_policy,
+        |call| {
+            calls.push(call);
+            Ok(())
+        },
+    )?;
+    results.calls_with_targets = if dedup_edges {
+        dedup_call_edges(calls)
+    } else {
+        calls
+    };
+    Ok(results)
+}
 
+/// Like `find_all_call_targets`, but passes each call with a definition to
+/// `on_call` as soon as it is found
+///
+/// This lets results be written while the analysis runs. The calls are not
+/// kept, so `calls_with_targets` of the returned results is empty.
+pub fn stream_call_targets<L: Language>(
+    language: L,
+    project_path: &Path,
+    config: &crate::file_search::FileSearchConfig,
+    lsp_config: crate::lsp::LspServerConfig,
+    error_policy: ErrorPolicy,
+    on_call: impl FnMut(CallWithTarget) -> Result<()>,
+) -> Result<CallAnalysisResults> {
     // Find all files matching the language
     tracing::info!("Scanning for {} files in project...", language);
     let matching_files = config.find_language_files(project_path, language)?;
@@ -111,15 +441,28 @@ pub fn find_all_call_targets<L: Language>(
     if matching_files.is_empty() {
         tracing::warn!("No files found for language {}", language);
         return Ok(CallAnalysisResults {
-            calls_with_targets: results,
+            calls_with_targets: Vec::new(),
             total_calls: 0,
+            lsp_enabled: !lsp_config.disabled,
+            calls_without_lsp: Vec::new(),
         });
     }
 
-    // Start and initialize LSP server
-    tracing::info!("Starting LSP server for {}...", language);
-    let mut lsp_server = LspServer::start_and_init(language, project_path.to_path_buf())?;
+    let lsp_server = start_lsp_server_or_fallback(language, project_path, lsp_config)?;
+    stream_call_targets_in_files(language, &matching_files, lsp_server, error_policy, on_call)
+}
 
+/// Finds the calls in `matching_files` and their definitions, see `stream_call_targets`
+///
+/// Without an LSP server the calls are found with tree-sitter only.
+fn stream_call_targets_in_files<L: Language>(
+    language: L,
+    matching_files: &[PathBuf],
+    mut lsp_server: Option<LspServer<L>>,
+    error_policy: ErrorPolicy,
+    mut on_call: impl FnMut(CallWithTarget) -> Result<()>,
+) -> Result<CallAnalysisResults> {
+    let mut calls_without_lsp = Vec::new();
     let mut total_calls = 0;
 
     // Process each file
@@ -131,25 +474,39 @@ pub fn find_all_call_targets<L: Language>(
         );
 
         // Read the file content
-        let file_content = match fs::read_to_string(file_path) {
-            Ok(content) => content,
-            Err(e) => {
-                tracing::warn!("Failed to read file {}: {}", file_path.display(), e);
-                continue;
-            }
+        let Some(file_content) = error_policy.handle(std::fs::read_to_string(file_path), || {
+            format!("Failed to read file {}", file_path.display())
+        })?
+        else {
+            continue;
         };
 
         // Parse the file with tree-sitter
-        let tree = match parse_file_content(&file_content, language) {
-            Ok(tree) => tree,
-            Err(e) => {
-                tracing::warn!("Failed to parse file {}: {}", file_path.display(), e);
-                continue;
-            }
+        let Some(tree) = error_policy
+            .handle(parse_file_content(&file_content, language), || {
+                format!("Failed to parse file {}", file_path.display())
+            })?
+        else {
+            continue;
+        };
+
+        let Some(lsp_server) = lsp_server.as_mut() else {
+            calls_without_lsp.extend(get_calls(&tree, language).map(|call| {
+                let call_node = OwnedCallNode::from_node(call.call_node, file_content.as_bytes());
+                (file_path.clone(), call_node)
+            }));
+            total_calls = calls_without_lsp.len();
+            continue;
         };
 
         // Open the document in the LSP server
-        lsp_server.open_file(file_path, &file_content)?;
+        let Some(()) = error_policy
+            .handle(lsp_server.open_file(file_path, &file_content), || {
+                format!("Failed to open file {}", file_path.display())
+            })?
+        else {
+            continue;
+        };
 
         // Some LSP servers seem to require a bit of time before they're ready
         // tracing::info!("Waiting for LSP server to index the project...");
@@ -170,18 +527,21 @@ pub fn find_all_call_targets<L: Language>(
                 goto_definition_node,
             } = call;
             // Query the LSP server for the definition
-            match goto_definition_for_node(&mut lsp_server, file_path, goto_definition_node) {
+            match goto_definition_for_node(
+                lsp_server,
+                file_path,
+                &file_content,
+                goto_definition_node,
+            ) {
                 Ok(Some(definition)) => {
-                    // We need to convert the node to a 'static lifetime by storing the tree
-                    // Since we can't easily do that here, we'll use unsafe to extend the lifetime
-                    // This is safe because we're only storing the node data, not the reference
-                    let static_node: Node<'static> = unsafe { std::mem::transmute(call_node) };
-
-                    results.push(CallWithTarget {
+                    on_call(CallWithTarget {
                         file_path: file_path.clone(),
-                        call_node: static_node,
+                        call_node: OwnedCallNode::from_node(call_node, file_content.as_bytes()),
+                        caller: enclosing_function(call_node, language)
+                            .map(|name| name.start_position()),
                         definition,
-                    });
+                        count: 1,
+                    })?;
                     tracing::debug!(
                         "Found definition for call at {}",
                         display_node_location(file_path, call_node)
@@ -209,7 +569,9 @@ pub fn find_all_call_targets<L: Language>(
         }
 
         // Close the document in the LSP server
-        lsp_server.close_file(file_path)?;
+        error_policy.handle(lsp_server.close_file(file_path), || {
+            format!("Failed to close file {}", file_path.display())
+        })?;
     }
 
     tracing::info!(
@@ -219,27 +581,520 @@ pub fn find_all_call_targets<L: Language>(
     );
 
     // Stop the LSP server
-    tracing::info!("Stopping LSP server...");
-    if let Err(e) = lsp_server.stop() {
-        tracing::error!("Error stopping LSP server: {}", e);
+    if let Some(lsp_server) = &mut lsp_server {
+        tracing::info!("Stopping LSP server...");
+        if let Err(e) = lsp_server.stop() {
+            tracing::error!("Error stopping LSP server: {}", e);
+        }
     }
 
     Ok(CallAnalysisResults {
-        calls_with_targets: results,
+        calls_with_targets: Vec::new(),
         total_calls,
+        lsp_enabled: lsp_server.is_some(),
+        calls_without_lsp,
     })
 }
 
+/// Starts the LSP server, or returns None if it is disabled or not installed
+///
+/// Other errors, like a server that fails to initialize, are returned.
+fn start_lsp_server_or_fallback<L: Language>(
+    language: L,
+    project_path: &Path,
+    lsp_config: crate::lsp::LspServerConfig,
+) -> Result<Option<LspServer<L>>> {
+    if lsp_config.disabled {
+        tracing::info!("LSP features are disabled, analyzing with tree-sitter only");
+        return Ok(None);
+    }
+    tracing::info!("Starting LSP server for {}...", language);
+    match LspServer::start_and_init_with_config(
+        language,
+        workspace_root(project_path).to_path_buf(),
+        lsp_config,
+    ) {
+        Ok(lsp_server) => Ok(Some(lsp_server)),
+        Err(e) if e.is::<LspServerUnavailable>() => {
+            tracing::warn!("{} LSP features are disabled.", e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::parser::{get_calls, parse_file};
     use lsp_types::{
-        InitializeParams, InitializedParams, notification::Initialized, request::Initialize,
+        InitializeParams, InitializedParams, Position, notification::Initialized,
+        request::Initialize,
     };
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_group_targets_by_file() -> Result<()> {
+        let source = "fn main() { a(); b(); c(); }";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let location = |path: &str| lsp_types::Location {
+            uri: to_file_uri(Path::new(path)).unwrap(),
+            range: Default::default(),
+        };
+        let definitions = [
+            lsp_types::GotoDefinitionResponse::Scalar(location("/project/src/a.rs")),
+            // Definitions in the same file are not necessarily adjacent
+            lsp_types::GotoDefinitionResponse::Array(vec![
+                location("/project/src/b.rs"),
+                location("/project/src/a.rs"),
+                location("/project/src/b.rs"),
+            ]),
+            lsp_types::GotoDefinitionResponse::Link(vec![lsp_types::LocationLink {
+                origin_selection_range: None,
+                target_uri: "file:///project/src/a.rs".parse()?,
+                target_range: Default::default(),
+                target_selection_range: Default::default(),
+            }]),
+        ];
+        let calls_with_targets = get_calls(&tree, crate::RustLang)
+            .zip(definitions)
+            .map(|(call, definition)| CallWithTarget {
+                file_path: PathBuf::from("/project/src/main.rs"),
+                call_node: OwnedCallNode::from_node(call.call_node, source.as_bytes()),
+                caller: None,
+                definition,
+                count: 1,
+            })
+            .collect();
+        let report = CallAnalysisResults {
+            calls_with_targets,
+            total_calls: 3,
+            lsp_enabled: true,
+            calls_without_lsp: Vec::new(),
+        };
+
+        let groups = group_targets_by_file(&report);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[Path::new("/project/src/a.rs")].len(), 3);
+        assert_eq!(groups[Path::new("/project/src/b.rs")].len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_call_edges() -> Result<()> {
+        let source = "fn main() { foo(); foo(); bar(); foo(); }\nfn other() { foo(); }";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let location = |line| lsp_types::Location {
+            uri: "file:///project/src/lib.rs".parse().unwrap(),
+            range: lsp_types::Range::new(Position::new(line, 3), Position::new(line, 6)),
+        };
+        let calls = get_calls(&tree, crate::RustLang)
+            .zip([1, 1, 5, 1, 1])
+            .map(|(call, line)| CallWithTarget {
+                file_path: PathBuf::from("/project/src/main.rs"),
+                call_node: OwnedCallNode::from_node(call.call_node, source.as_bytes()),
+                caller: enclosing_function(call.call_node, crate::RustLang)
+                    .map(|name| name.start_position()),
+                definition: lsp_types::GotoDefinitionResponse::Scalar(location(line)),
+                count: 1,
+            })
+            .collect();
+
+        let edges = dedup_call_edges(calls);
+
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges[0].count, 3);
+        assert_eq!(edges[0].call_node.start_position.column, 12);
+        assert_eq!(edges[1].count, 1);
+        // The same target called from another function is another edge
+        assert_eq!(edges[2].count, 1);
+        assert_eq!(edges[2].call_node.start_position.row, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_all_call_targets_falls_back_without_server() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir(temp_dir.path().join("src"))?;
+        fs::write(
+            temp_dir.path().join("src/main.rs"),
+            "fn foo() {}\nfn main() { foo(); foo(); }",
+        )?;
+        let lsp_config = crate::lsp::LspServerConfig {
+            lsp_command_override: Some("no-such-language-server-xyz".to_string()),
+            ..Default::default()
+        };
+
+        // Starting the server directly fails with an error the fallback recognizes
+        let error = LspServer::start(
+            crate::RustLang,
+            temp_dir.path().to_path_buf(),
+            lsp_config.clone(),
+        )
+        .err()
+        .unwrap();
+        assert!(error.is::<LspServerUnavailable>());
+
+        // Disabling LSP features refuses to start even an installed server
+        let error = LspServer::start(
+            crate::RustLang,
+            temp_dir.path().to_path_buf(),
+            crate::lsp::LspServerConfig {
+                lsp_command_override: Some("sh".to_string()),
+                disabled: true,
+                ..Default::default()
+            },
+        )
+        .err()
+        .unwrap();
+        let unavailable = error.downcast_ref::<LspServerUnavailable>().unwrap();
+        assert!(unavailable.disabled);
+        assert!(error.to_string().starts_with("LSP features are disabled"));
+
+        let results = find_all_call_targets(
+            crate::RustLang,
+            temp_dir.path(),
+            &crate::FileSearchConfig::default(),
+            lsp_config,
+            false,
+            ErrorPolicy::Continue,
+        )?;
+
+        assert!(!results.lsp_enabled);
+        assert!(results.calls_with_targets.is_empty());
+        assert_eq!(results.total_calls, 2);
+        let texts: Vec<_> = results
+            .calls_without_lsp
+            .iter()
+            .map(|(file_path, call_node)| {
+                assert!(file_path.ends_with("src/main.rs"));
+                call_node.text.as_str()
+            })
+            .collect();
+        assert_eq!(texts, ["foo()", "foo()"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_all_call_targets_error_policy() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join("a.rs"),
+            "fn foo() {}\nfn main() { foo(); }",
+        )?;
+        // Not valid UTF-8, so the file can not be read into a string
+        std::fs::write(temp_dir.path().join("b.rs"), b"fn main() { \xff(); }")?;
+        let analyze = |error_policy| {
+            find_all_call_targets(
+                crate::RustLang,
+                temp_dir.path(),
+                &crate::FileSearchConfig::default(),
+                crate::lsp::LspServerConfig {
+                    disabled: true,
+                    ..Default::default()
+                },
+                false,
+                error_policy,
+            )
+        };
+
+        let results = analyze(ErrorPolicy::Continue)?;
+        assert_eq!(results.total_calls, 1);
+        assert!(results.calls_without_lsp[0].0.ends_with("a.rs"));
+
+        let error = analyze(ErrorPolicy::FailFast).unwrap_err();
+        assert!(
+            error.to_string().starts_with("Failed to read file")
+                && error.to_string().ends_with("b.rs"),
+            "{error}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_policy_skips_files_that_can_not_be_opened() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let file_path = temp_dir.path().join("a.rs");
+        std::fs::write(&file_path, "fn foo() {}\nfn main() { foo(); }")?;
+        // A relative path can be read, but has no file URI to open it with
+        let files = [PathBuf::from("src/main.rs"), file_path.clone()];
+        let definition = serde_json::json!({
+            "uri": crate::uri::to_file_uri(&file_path)?.as_str(),
+            "range": {"start": {"line": 0, "character": 3}, "end": {"line": 0, "character": 6}},
+        });
+        let stub = || {
+            crate::testing::StubServer::new()
+                .on_request("textDocument/definition", definition.clone())
+                .on_request(
+                    "textDocument/prepareCallHierarchy",
+                    serde_json::json!([{
+                        "name": "foo",
+                        "kind": 12,
+                        "uri": definition["uri"],
+                        "range": definition["range"],
+                        "selectionRange": definition["range"],
+                    }]),
+                )
+                .on_request("callHierarchy/incomingCalls", serde_json::json!([]))
+        };
+        let stream = |error_policy| -> Result<Vec<CallWithTarget>> {
+            let server = stub().start_and_init(crate::RustLang, temp_dir.path().to_path_buf())?;
+            let mut calls = Vec::new();
+            stream_call_targets_in_files(
+                crate::RustLang,
+                &files,
+                Some(server),
+                error_policy,
+                |call| {
+                    calls.push(call);
+                    Ok(())
+                },
+            )?;
+            Ok(calls)
+        };
+        let find_callers = |error_policy| -> Result<Vec<DeclarationCallers>> {
+            let mut server =
+                stub().start_and_init(crate::RustLang, temp_dir.path().to_path_buf())?;
+            find_callers_in_files(&mut server, &files, error_policy)
+        };
+
+        let calls = stream(ErrorPolicy::Continue)?;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].file_path, file_path);
+        let declarations = find_callers(ErrorPolicy::Continue)?;
+        assert!(
+            declarations
+                .iter()
+                .all(|declaration| declaration.file_path == file_path)
+        );
+        assert!(!declarations.is_empty());
+
+        for error in [
+            stream(ErrorPolicy::FailFast).unwrap_err(),
+            find_callers(ErrorPolicy::FailFast).unwrap_err(),
+        ] {
+            assert!(
+                error.to_string().starts_with("Failed to open file"),
+                "{error}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_goto_definition_for_node_with_stub_server() -> Result<()> {
+        let source = "fn foo() {}\nfn main() { foo(); }";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let call = get_calls(&tree, crate::RustLang).next().unwrap();
+        let definition = serde_json::json!({
+            "uri": "file:///project/src/main.rs",
+            "range": {
+                "start": { "line": 0, "character": 3 },
+                "end": { "line": 0, "character": 6 }
+            }
+        });
+        let stub =
+            crate::testing::StubServer::new().on_request("textDocument/definition", definition);
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let response = goto_definition_for_node(
+            &mut server,
+            Path::new("/project/src/main.rs"),
+            source,
+            call.call_node,
+        )?;
+
+        let locations = crate::lsp::response_locations(response.unwrap());
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].range.start, Position::new(0, 3));
+        let requests = stub.received_with_method("textDocument/definition");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["params"]["position"]["line"], 1);
+        assert_eq!(requests[0]["params"]["position"]["character"], 12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_goto_definition_for_node_with_utf8_encoding() -> Result<()> {
+        let source = "fn foo() {}\nfn main() { let s = \"h\u{e9}llo \u{1f600}\"; foo(); }";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let call = get_calls(&tree, crate::RustLang).next().unwrap();
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "initialize",
+                serde_json::json!({"capabilities": {"positionEncoding": "utf-8"}}),
+            )
+            .on_request("textDocument/definition", serde_json::Value::Null);
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+        assert_eq!(
+            server.position_encoding,
+            lsp_types::PositionEncodingKind::UTF8
+        );
+
+        goto_definition_for_node(
+            &mut server,
+            Path::new("/project/src/main.rs"),
+            source,
+            call.call_node,
+        )?;
+
+        // With UTF-8 the tree-sitter byte column is sent unchanged
+        let column = call.call_node.start_position().column;
+        let utf16_column = source.lines().nth(1).unwrap()[..column]
+            .encode_utf16()
+            .count();
+        assert_ne!(column, utf16_column);
+        let requests = stub.received_with_method("textDocument/definition");
+        assert_eq!(requests[0]["params"]["position"]["line"], 1);
+        assert_eq!(requests[0]["params"]["position"]["character"], column);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_callers_in_file() -> Result<()> {
+        let source = "fn helper() {}\n\nfn main() {\n    helper();\n}\n";
+        let item = |name: &str, line: u32| {
+            serde_json::json!({
+                "name": name,
+                "kind": 12,
+                "uri": "file:///project/src/main.rs",
+                "range": {"start": {"line": line, "character": 0}, "end": {"line": line, "character": 14}},
+                "selectionRange": {"start": {"line": line, "character": 3}, "end": {"line": line, "character": 9}},
+            })
+        };
+        let call_range = serde_json::json!({"start": {"line": 3, "character": 4}, "end": {"line": 3, "character": 10}});
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "textDocument/prepareCallHierarchy",
+                serde_json::json!([item("helper", 0)]),
+            )
+            .on_request(
+                "callHierarchy/incomingCalls",
+                serde_json::json!([{"from": item("main", 2), "fromRanges": [call_range]}]),
+            )
+            .on_request(
+                "textDocument/prepareCallHierarchy",
+                serde_json::json!([item("main", 2)]),
+            )
+            .on_request("callHierarchy/incomingCalls", serde_json::json!([]));
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let declarations =
+            find_callers_in_file(&mut server, Path::new("/project/src/main.rs"), source)?;
+
+        let callers: Vec<_> = declarations
+            .iter()
+            .map(|declaration| {
+                let names: Vec<_> = declaration
+                    .callers
+                    .iter()
+                    .map(|call| call.from.name.as_str())
+                    .collect();
+                (declaration.name.as_str(), declaration.position, names)
+            })
+            .collect();
+        assert_eq!(
+            callers,
+            [
+                ("helper", tree_sitter::Point::new(0, 3), vec!["main"]),
+                ("main", tree_sitter::Point::new(2, 3), vec![]),
+            ]
+        );
+        let prepares = stub.received_with_method("textDocument/prepareCallHierarchy");
+        assert_eq!(prepares[1]["params"]["position"]["line"], 2);
+        assert_eq!(prepares[1]["params"]["position"]["character"], 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_actions_for_call() -> Result<()> {
+        let source = "fn helper() {}\nfn main() {\n    helper();\n}\n";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let call = get_calls(&tree, crate::RustLang).next().unwrap();
+        let stub = crate::testing::StubServer::new().on_request(
+            "textDocument/codeAction",
+            serde_json::json!([{
+                "title": "Inline `helper`",
+                "kind": "refactor.inline",
+            }]),
+        );
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let actions = code_actions_for_call(
+            &mut server,
+            Path::new("/project/src/main.rs"),
+            source,
+            &call,
+        )?;
+
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("Expected a code action, got {:?}", actions[0]);
+        };
+        assert_eq!(action.title, "Inline `helper`");
+        assert_eq!(
+            action.kind,
+            Some(lsp_types::CodeActionKind::REFACTOR_INLINE)
+        );
+        let request = &stub.received_with_method("textDocument/codeAction")[0];
+        assert_eq!(
+            request["params"]["range"],
+            serde_json::json!({
+                "start": {"line": 2, "character": 4},
+                "end": {"line": 2, "character": 12}
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_goto_definition_for_file_with_space() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file_path = temp_dir.path().join("my file.rs");
+        let source = "fn foo() {}\nfn main() { foo(); }";
+        fs::write(&file_path, source)?;
+        let file_uri = to_file_uri(&file_path)?;
+        assert!(file_uri.as_str().ends_with("/my%20file.rs"));
+
+        let stub = crate::testing::StubServer::new().on_request(
+            "textDocument/definition",
+            serde_json::json!({
+                "uri": file_uri.as_str(),
+                "range": {
+                    "start": { "line": 0, "character": 3 },
+                    "end": { "line": 0, "character": 6 }
+                }
+            }),
+        );
+        let mut server = stub.start_and_init(crate::RustLang, temp_dir.path().to_path_buf())?;
+        server.open_file(&file_path, source)?;
+
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let call = get_calls(&tree, crate::RustLang).next().unwrap();
+        let response = goto_definition_for_node(&mut server, &file_path, source, call.call_node)?;
+
+        let locations = crate::lsp::response_locations(response.unwrap());
+        assert_eq!(crate::uri::from_file_uri(&locations[0].uri)?, file_path);
+        let opened = stub.received_with_method("textDocument/didOpen");
+        assert_eq!(
+            opened[0]["params"]["textDocument"]["uri"],
+            file_uri.as_str()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_goto_definition_for_node() -> Result<()> {
         // Create a temporary directory for the Swift file
@@ -287,7 +1142,7 @@ func main() {
         let initialize_params = InitializeParams {
             process_id: Some(std::process::id()),
             workspace_folders: Some(vec![lsp_types::WorkspaceFolder {
-                uri: uri_from_path(temp_dir.path())?,
+                uri: to_file_uri(temp_dir.path())?,
                 name: "test".to_string(),
             }]),
             ..Default::default()
@@ -300,8 +1155,12 @@ func main() {
         lsp_server.open_file(&file_path, swift_code)?;
 
         // Request go-to-definition for the call node
-        let result =
-            goto_definition_for_node(&mut lsp_server, &file_path, greet_call.goto_definition_node)?;
+        let result = goto_definition_for_node(
+            &mut lsp_server,
+            &file_path,
+            swift_code,
+            greet_call.goto_definition_node,
+        )?;
 
         // Verify the definition points to the correct location
         let response = result.expect("Should find definition for greet function call");
@@ -312,8 +1171,7 @@ func main() {
         let location = locations.first().expect("Should have a location");
 
         // Check that the URI path matches our file
-        let location_path_str = location.uri.path().as_str();
-        let location_path = std::path::PathBuf::from(location_path_str);
+        let location_path = crate::uri::from_file_uri(&location.uri)?;
         let canonical_location = location_path.canonicalize().ok();
         let canonical_expected = file_path.canonicalize().ok();
         assert_eq!(
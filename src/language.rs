@@ -1,6 +1,8 @@
 //! Programming language definitions and configurations.
 
 use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 use regex::Regex;
@@ -20,32 +22,389 @@ pub trait Language: Debug + Display + Copy {
     /// Returns the display name for this language
     fn display_name(&self) -> &'static str;
 
+    /// Returns the language identifier sent to the LSP server in `textDocument/didOpen`
+    ///
+    /// The file path is used for languages where the identifier depends on the
+    /// kind of file, such as TypeScript with JSX.
+    fn to_lsp_language_id(&self, _file_path: Option<&Path>) -> &'static str {
+        self.cli_name()
+    }
+
     /// Returns the LSP server command and arguments for this language
     fn lsp_server_command(&self) -> (&'static str, Vec<String>);
 
+    /// Returns the longest time to wait for the LSP server to index a project
+    ///
+    /// `LspServer::wait_for_indexing` returns early when the server reports
+    /// that it is done, so this is an upper bound rather than a fixed delay.
+    fn indexing_warmup(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
     /// Returns the Tree Sitter language grammar for the given language
     fn tree_sitter_language(&self) -> tree_sitter::Language;
 
     /// Returns the node kinds that represent calls in this language
     fn call_node_kinds(&self) -> &'static [&'static str];
 
+    /// Returns the subset of call node kinds that represent macro invocations
+    fn macro_call_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the subset of call node kinds that construct a new object, like `new Foo()`
+    ///
+    /// Only languages with a dedicated syntax for construction have any, in
+    /// Python or Swift `Foo()` is an ordinary call.
+    fn constructor_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of expressions that await a call, like `await foo()`
+    ///
+    /// Used by `CallNode::is_async_call`.
+    fn async_call_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of parenthesized expressions, like `(foo())`
+    ///
+    /// Used by `CallNode::is_async_call` to look through the parentheses
+    /// around an awaited call.
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of explicit type arguments, like `::<T>` in Rust
+    ///
+    /// Used by `CallNode::has_type_arguments` to find calls of generic functions.
+    fn generic_type_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of number literals, like `42` or `1.5`
+    ///
+    /// Used by `CallNode::literal_argument_positions` to find calls with constant arguments.
+    fn number_literal_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds that represent traits, interfaces or protocols in this language
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of import statements, one node per imported module
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of attributes and decorators, e.g. `#[test]` or `@staticmethod`
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the file or directory the module imported by `import_node` is in
+    /// Returns None for modules outside `project_root`, such as dependencies
+    fn resolve_import_to_path<'a>(
+        &self,
+        _import_node: Node<'a>,
+        _source: &[u8],
+        _project_root: &Path,
+    ) -> Option<PathBuf> {
+        None
+    }
+
+    /// Returns the node kinds that declare variables in this language
+    fn variable_declaration_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of explicitly written types, such as annotations
+    fn type_annotation_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node kinds of comments that can hold documentation
+    fn documentation_comment_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns true if the comment node is a documentation comment
+    /// The default accepts every node of the documentation comment kinds
+    fn is_documentation_comment<'a>(&self, node: Node<'a>, _source: &[u8]) -> bool {
+        self.documentation_comment_node_kinds()
+            .contains(&node.kind())
+    }
+
+    /// Returns how `get_calls` treats the `ERROR` nodes of source that fails to parse
+    ///
+    /// The default skips them, the calls tree-sitter recovers inside an error
+    /// often span the wrong text.
+    fn error_node_strategy(&self) -> ErrorRecoveryStrategy {
+        ErrorRecoveryStrategy::Skip
+    }
+
     /// Finds the appropriate node for goto definition within a call node
     /// For method calls, this returns the method name node; otherwise returns the call node itself
     /// Returns None if the node is not a call node for this language
-    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>>;
+    ///
+    /// The default returns any node of `call_node_kinds` itself.
+    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if self.call_node_kinds().contains(&node.kind()) {
+            Some(node)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the source text of the receiver of a method call (e.g. `obj` in `obj.method()`)
+    /// Returns None if the call is not a method call or receivers are not supported
+    fn method_receiver_text<'a>(&self, _call_node: Node<'a>, _source: &'a [u8]) -> Option<&'a str> {
+        None
+    }
+
+    /// Returns true if the call is to a builtin or standard library function,
+    /// e.g. `len()` in Python or `fmt.Println()` in Go
+    fn is_builtin_call<'a>(&self, _call_node: Node<'a>, _source: &[u8]) -> bool {
+        false
+    }
+
+    /// Returns true if the call is an explicit call of an operator method,
+    /// like `a.add(b)` for `a + b` in Rust
+    ///
+    /// Such calls go to operator implementations rather than named functions
+    /// and are skipped with `CallIteratorConfig::skip_operators`.
+    fn should_skip_as_operator<'a>(&self, _call_node: Node<'a>, _source: &[u8]) -> bool {
+        false
+    }
+
+    /// Returns the 0-based positions of the arguments of a call that are
+    /// closures or lambdas, e.g. `[1]` for `sorted(xs, key=lambda x: -x)`
+    fn closure_argument_positions<'a>(&self, _call_node: Node<'a>) -> Vec<usize> {
+        vec![]
+    }
 
     /// Finds the identifier node of a function or method declaration
     /// Returns Some(identifier_node) if the node is a function/method declaration, None otherwise
     fn find_function_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>>;
 
+    /// Finds the identifier node of a class (or struct/type) declaration
+    /// Returns Some(identifier_node) if the node is a class declaration, None otherwise
+    fn find_class_declaration<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
+        None
+    }
+
+    /// Returns true if the node is a test function
+    /// The node is the function declaration itself, not its identifier
+    fn is_test_node<'a>(&self, _node: Node<'a>, _source: &[u8]) -> bool {
+        false
+    }
+
+    /// Returns the return type of a function declaration
+    /// Returns None if the node is not a function or its return type is not written out
+    fn return_type_node<'a>(&self, _function_node: Node<'a>) -> Option<Node<'a>> {
+        None
+    }
+
+    /// Returns the node kinds of individual parameters in a parameter list
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the node holding the parameters of a function declaration
+    /// Returns None if the node is not a function. The parameters are the
+    /// children with a kind in `parameter_node_kinds`.
+    fn parameter_list_node<'a>(&self, _function_node: Node<'a>) -> Option<Node<'a>> {
+        None
+    }
+
     /// Determines if a node is a valid target for LSP call hierarchy requests
     /// Returns Some(target_node) if call hierarchy makes sense for this node, None otherwise
     /// This includes function/method declarations and other relevant constructs like trait methods
     fn call_hierarchy_target<'a>(&self, node: Node<'a>) -> Option<Node<'a>>;
 
+    /// Returns the prefix of a line comment, e.g. `//` or `#`
+    fn line_comment_prefix(&self) -> &'static str {
+        "//"
+    }
+
+    /// Returns `source` with the lines spanned by `range` commented out, e.g.
+    /// to show what removing a call would look like without changing the file
+    ///
+    /// Line comments can only comment out whole lines, so every line the range
+    /// touches is commented out. The prefix is inserted at the smallest
+    /// indentation of those lines and blank lines are left as they are. A range
+    /// that ends at the start of a line does not include that line.
+    fn comment_out_range(&self, source: &str, range: lsp_types::Range) -> String {
+        let first = range.start.line as usize;
+        let mut last = range.end.line as usize;
+        if range.end.character == 0 && last > first {
+            last -= 1;
+        }
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let is_commented =
+            |i: usize, line: &str| (first..=last).contains(&i) && !line.trim().is_empty();
+        let indent = lines
+            .iter()
+            .enumerate()
+            .filter(|&(i, line)| is_commented(i, line))
+            .map(|(_, line)| line.len() - line.trim_start_matches([' ', '\t']).len())
+            .min()
+            .unwrap_or(0);
+
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                if is_commented(i, line) {
+                    let (indentation, code) = line.split_at(indent);
+                    format!("{}{} {}", indentation, self.line_comment_prefix(), code)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns true if the file looks like generated code
+    ///
+    /// `first_lines` holds the first few lines of the file. The default
+    /// implementation looks for the common "generated" header comments.
+    fn is_generated_file(&self, _file_path: &Path, first_lines: &str) -> bool {
+        has_generated_header(first_lines)
+    }
+
     /// Creates a compiled regex for matching files of this language
     fn file_regex(&self) -> Result<Regex> {
         Regex::new(self.file_pattern())
             .map_err(|e| anyhow::anyhow!("Failed to compile regex: {}", e))
     }
 }
+
+/// How the calls in and around syntax errors are treated, see `Language::error_node_strategy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ErrorRecoveryStrategy {
+    /// Skips the subtrees rooted at `ERROR` nodes and the calls that contain a
+    /// syntax error, like `foo(1 2)`
+    ///
+    /// A file that fails to parse as a whole has an `ERROR` root, so no calls
+    /// are found in it.
+    #[default]
+    Skip,
+    /// Treats `ERROR` nodes like any other node
+    Include,
+    /// Skips the subtrees rooted at `ERROR` nodes but keeps the calls that contain them
+    IncludeParent,
+}
+
+/// An object-safe view of a `Language` for handling several languages at once
+///
+/// `Language` itself can not be used as a trait object, this trait is
+/// implemented for every language and exposes what is needed to classify files.
+pub trait LanguageCounter {
+    /// Returns the name of the language, see `Language::cli_name`
+    fn counter_name(&self) -> &'static str;
+
+    /// Returns the compiled regex for matching files of the language
+    fn counter_file_regex(&self) -> Result<Regex>;
+
+    /// Returns true if the file looks like generated code, see `Language::is_generated_file`
+    fn counter_is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool;
+}
+
+impl<L: Language> LanguageCounter for L {
+    fn counter_name(&self) -> &'static str {
+        self.cli_name()
+    }
+
+    fn counter_file_regex(&self) -> Result<Regex> {
+        self.file_regex()
+    }
+
+    fn counter_is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        self.is_generated_file(file_path, first_lines)
+    }
+}
+
+/// Returns the positions of the arguments in an argument list node that are
+/// of one of `closure_kinds`
+///
+/// Arguments wrapped in another node, like keyword arguments, are closures if
+/// the last child of the wrapper is. Comments are not counted as arguments.
+pub fn closure_positions(arguments: Node<'_>, closure_kinds: &[&str]) -> Vec<usize> {
+    let mut cursor = arguments.walk();
+    arguments
+        .named_children(&mut cursor)
+        .filter(|argument| !argument.is_extra())
+        .enumerate()
+        .filter(|(_, argument)| {
+            let value = argument
+                .named_child(argument.named_child_count().saturating_sub(1))
+                .unwrap_or(*argument);
+            closure_kinds.contains(&argument.kind()) || closure_kinds.contains(&value.kind())
+        })
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// Returns true if the text contains a header comment marking the file as generated
+pub fn has_generated_header(first_lines: &str) -> bool {
+    first_lines.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("// Code generated")
+            || line.starts_with("# Code generated")
+            || line.contains("@generated")
+            || line.contains("DO NOT EDIT")
+            || line.to_lowercase().contains("auto-generated")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GoLang, PhpLang, PythonLang, RustLang, SwiftLang, TypeScriptLang};
+
+    #[test]
+    fn test_indexing_warmup_defaults() {
+        let warmups = [
+            RustLang.indexing_warmup(),
+            PythonLang.indexing_warmup(),
+            TypeScriptLang.indexing_warmup(),
+            GoLang.indexing_warmup(),
+            SwiftLang.indexing_warmup(),
+            PhpLang.indexing_warmup(),
+        ];
+        for warmup in warmups {
+            assert!(warmup >= Duration::from_millis(100), "{warmup:?}");
+            assert!(warmup <= Duration::from_secs(30), "{warmup:?}");
+        }
+        // rust-analyzer has to load the whole cargo workspace before it can answer
+        assert!(RustLang.indexing_warmup() > PythonLang.indexing_warmup());
+    }
+
+    #[test]
+    fn test_comment_out_range() {
+        let range = |start: (u32, u32), end: (u32, u32)| lsp_types::Range {
+            start: lsp_types::Position::new(start.0, start.1),
+            end: lsp_types::Position::new(end.0, end.1),
+        };
+
+        let source = "fn main() {\n    let x = compute(\n        1,\n\n    );\n    done();\n}\n";
+        assert_eq!(
+            RustLang.comment_out_range(source, range((1, 12), (4, 5))),
+            "fn main() {\n    // let x = compute(\n    //     1,\n\n    // );\n    done();\n}\n"
+        );
+        // The line where a range ends at the first column is not commented out
+        assert_eq!(
+            GoLang.comment_out_range(source, range((5, 0), (6, 0))),
+            "fn main() {\n    let x = compute(\n        1,\n\n    );\n    // done();\n}\n"
+        );
+
+        let source = "def main():\n    print(greet())\n";
+        assert_eq!(
+            PythonLang.comment_out_range(source, range((1, 4), (1, 18))),
+            "def main():\n    # print(greet())\n"
+        );
+    }
+}
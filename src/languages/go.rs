@@ -1,8 +1,54 @@
 //! Go language implementation.
 
-use crate::language::Language;
+use crate::language::{Language, closure_positions, has_generated_header};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tree_sitter::Node;
 
+/// Names of the Go grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ARGUMENT_LIST = "argument_list";
+        CALL_EXPRESSION = "call_expression";
+        COMMENT = "comment";
+        FIELD_IDENTIFIER = "field_identifier";
+        FLOAT_LITERAL = "float_literal";
+        FUNCTION_DECLARATION = "function_declaration";
+        FUNC_LITERAL = "func_literal";
+        IDENTIFIER = "identifier";
+        IMPORT_SPEC = "import_spec";
+        INTERFACE_TYPE = "interface_type";
+        INT_LITERAL = "int_literal";
+        METHOD_DECLARATION = "method_declaration";
+        METHOD_ELEM = "method_elem";
+        PARAMETER_DECLARATION = "parameter_declaration";
+        PARAMETER_LIST = "parameter_list";
+        PARENTHESIZED_EXPRESSION = "parenthesized_expression";
+        SELECTOR_EXPRESSION = "selector_expression";
+        SHORT_VAR_DECLARATION = "short_var_declaration";
+        SOURCE_FILE = "source_file";
+        STRUCT_TYPE = "struct_type";
+        TYPE_ARGUMENTS = "type_arguments";
+        TYPE_DECLARATION = "type_declaration";
+        TYPE_IDENTIFIER = "type_identifier";
+        TYPE_SPEC = "type_spec";
+        VARIADIC_PARAMETER_DECLARATION = "variadic_parameter_declaration";
+        VAR_DECLARATION = "var_declaration";
+    }
+}
+
+/// The predeclared functions of Go
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "append", "cap", "clear", "close", "complex", "copy", "delete", "imag", "len", "make", "max",
+    "min", "new", "panic", "print", "println", "real", "recover",
+];
+
+/// Commonly used standard library packages whose functions are treated as builtins
+const STANDARD_PACKAGES: &[&str] = &[
+    "bytes", "errors", "fmt", "io", "log", "math", "os", "sort", "strconv", "strings", "sync",
+    "time",
+];
+
 /// Go language implementation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GoLang;
@@ -28,32 +74,174 @@ impl Language for GoLang {
         ("gopls", vec![])
     }
 
+    fn indexing_warmup(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
     fn tree_sitter_language(&self) -> tree_sitter::Language {
         tree_sitter_go::LANGUAGE.into()
     }
 
     fn call_node_kinds(&self) -> &'static [&'static str] {
-        &["call_expression"]
+        &[kinds::CALL_EXPRESSION]
+    }
+
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARENTHESIZED_EXPRESSION]
+    }
+
+    fn generic_type_node_kinds(&self) -> &'static [&'static str] {
+        // A single type argument like `Sum[int](x)` parses as an index
+        // expression, which can't be told apart from indexing a slice of functions
+        &[kinds::TYPE_ARGUMENTS]
+    }
+
+    fn number_literal_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INT_LITERAL, kinds::FLOAT_LITERAL]
+    }
+
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INTERFACE_TYPE]
     }
 
-    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
-        if !self.call_node_kinds().contains(&node.kind()) {
+    fn variable_declaration_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::VAR_DECLARATION, kinds::SHORT_VAR_DECLARATION]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::IMPORT_SPEC]
+    }
+
+    fn resolve_import_to_path<'a>(
+        &self,
+        import_node: Node<'a>,
+        source: &[u8],
+        project_root: &Path,
+    ) -> Option<PathBuf> {
+        if import_node.kind() != kinds::IMPORT_SPEC {
             return None;
         }
-        // For Go, return the call node itself as goto definition target
-        Some(node)
+        let path = import_node
+            .child_by_field_name("path")?
+            .utf8_text(source)
+            .ok()?
+            .trim_matches(['"', '`']);
+        // Packages of the project are imported by the module path in `go.mod`
+        let go_mod = std::fs::read_to_string(project_root.join("go.mod")).ok()?;
+        let module = go_mod
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))?
+            .trim();
+        let package = path.strip_prefix(module)?;
+        if !package.is_empty() && !package.starts_with('/') {
+            return None;
+        }
+        let directory = project_root.join(package.trim_start_matches('/'));
+        directory.is_dir().then_some(directory)
+    }
+
+    fn type_annotation_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::TYPE_IDENTIFIER]
+    }
+
+    fn documentation_comment_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::COMMENT]
+    }
+
+    fn is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        let name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        name.ends_with(".pb.go") || name.ends_with("_gen.go") || has_generated_header(first_lines)
+    }
+
+    fn is_builtin_call<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        let Some(function) = call_node.child_by_field_name("function") else {
+            return false;
+        };
+        let text = |node: Option<Node<'a>>| node.and_then(|node| node.utf8_text(source).ok());
+        match function.kind() {
+            kinds::IDENTIFIER => {
+                text(Some(function)).is_some_and(|name| BUILTIN_FUNCTIONS.contains(&name))
+            }
+            // Qualified calls like `fmt.Println`, a local variable shadowing the
+            // package name is mistaken for the package
+            kinds::SELECTOR_EXPRESSION => {
+                let operand = function.child_by_field_name("operand");
+                operand.is_some_and(|operand| operand.kind() == kinds::IDENTIFIER)
+                    && text(operand).is_some_and(|package| STANDARD_PACKAGES.contains(&package))
+            }
+            _ => false,
+        }
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        call_node
+            .child_by_field_name("arguments")
+            .filter(|arguments| arguments.kind() == kinds::ARGUMENT_LIST)
+            .map(|arguments| closure_positions(arguments, &[kinds::FUNC_LITERAL]))
+            .unwrap_or_default()
     }
 
     fn find_function_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
         // Check if this is a function declaration
-        if node.kind() != "function_declaration" && node.kind() != "method_declaration" {
+        if node.kind() != kinds::FUNCTION_DECLARATION && node.kind() != kinds::METHOD_DECLARATION {
             return None;
         }
 
         // Find the identifier child
         let mut cursor = node.walk();
-        node.children(&mut cursor)
-            .find(|&child| child.kind() == "identifier" || child.kind() == "field_identifier")
+        node.children(&mut cursor).find(|&child| {
+            child.kind() == kinds::IDENTIFIER || child.kind() == kinds::FIELD_IDENTIFIER
+        })
+    }
+
+    fn find_class_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        // Go has no classes, struct type declarations are the closest equivalent.
+        // Each `type_spec` is matched on its own, since a grouped declaration
+        // like `type ( A struct{}; B struct{} )` has one for every type.
+        if node.kind() != kinds::TYPE_SPEC
+            || node
+                .child_by_field_name("type")
+                .is_none_or(|ty| ty.kind() != kinds::STRUCT_TYPE)
+        {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn is_test_node<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        node.kind() == kinds::FUNCTION_DECLARATION
+            && node
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(source).ok())
+                .is_some_and(|name| name.starts_with("Test"))
+    }
+
+    fn return_type_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The `result` after the `parameters`, a type or a parameter list
+        match function_node.kind() {
+            kinds::FUNCTION_DECLARATION | kinds::METHOD_DECLARATION => {
+                function_node.child_by_field_name("result")
+            }
+            _ => None,
+        }
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        // Parameters sharing a type, like `a, b int`, are a single declaration
+        &[
+            kinds::PARAMETER_DECLARATION,
+            kinds::VARIADIC_PARAMETER_DECLARATION,
+        ]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The `parameters` field, not the method `receiver` which is a parameter list too
+        function_node
+            .child_by_field_name("parameters")
+            .filter(|parameters| parameters.kind() == kinds::PARAMETER_LIST)
     }
 
     fn call_hierarchy_target<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
@@ -62,12 +250,14 @@ impl Language for GoLang {
         // - method_declaration (methods on types)
         // - method_elem (interface method elements)
         match node.kind() {
-            "function_declaration" | "method_declaration" => self.find_function_declaration(node),
-            "method_elem" => {
+            kinds::FUNCTION_DECLARATION | kinds::METHOD_DECLARATION => {
+                self.find_function_declaration(node)
+            }
+            kinds::METHOD_ELEM => {
                 // For interface method elements, find the field_identifier
                 let mut cursor = node.walk();
                 node.children(&mut cursor)
-                    .find(|&child| child.kind() == "field_identifier")
+                    .find(|&child| child.kind() == kinds::FIELD_IDENTIFIER)
             }
             _ => None,
         }
@@ -99,7 +289,7 @@ mod tests {
         let mut cursor = root.walk();
         let mut function_node = None;
         for child in root.children(&mut cursor) {
-            if child.kind() == "function_declaration" {
+            if child.kind() == kinds::FUNCTION_DECLARATION {
                 function_node = Some(child);
                 break;
             }
@@ -110,7 +300,7 @@ mod tests {
         let identifier = GoLang.find_function_declaration(function_node);
         assert!(identifier.is_some());
         let identifier = identifier.unwrap();
-        assert_eq!(identifier.kind(), "identifier");
+        assert_eq!(identifier.kind(), kinds::IDENTIFIER);
         assert_eq!(identifier.utf8_text(source.as_bytes()).unwrap(), "hello");
     }
 
@@ -129,7 +319,7 @@ mod tests {
         let mut cursor = root.walk();
         let mut method_node = None;
         for child in root.children(&mut cursor) {
-            if child.kind() == "method_declaration" {
+            if child.kind() == kinds::METHOD_DECLARATION {
                 method_node = Some(child);
                 break;
             }
@@ -174,7 +364,7 @@ mod tests {
         let mut cursor = root.walk();
         let mut function_node = None;
         for child in root.children(&mut cursor) {
-            if child.kind() == "function_declaration" {
+            if child.kind() == kinds::FUNCTION_DECLARATION {
                 function_node = Some(child);
                 break;
             }
@@ -184,7 +374,7 @@ mod tests {
         // Test call_hierarchy_target
         let target = GoLang.call_hierarchy_target(function_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "identifier");
+        assert_eq!(target.unwrap().kind(), kinds::IDENTIFIER);
     }
 
     #[test]
@@ -202,7 +392,7 @@ mod tests {
         let mut cursor = root.walk();
         let mut method_node = None;
         for child in root.children(&mut cursor) {
-            if child.kind() == "method_declaration" {
+            if child.kind() == kinds::METHOD_DECLARATION {
                 method_node = Some(child);
                 break;
             }
@@ -212,7 +402,7 @@ mod tests {
         // Test call_hierarchy_target
         let target = GoLang.call_hierarchy_target(method_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "field_identifier");
+        assert_eq!(target.unwrap().kind(), kinds::FIELD_IDENTIFIER);
     }
 
     #[test]
@@ -230,16 +420,16 @@ mod tests {
         let mut found_spec = None;
         let mut cursor = root.walk();
         for child in root.children(&mut cursor) {
-            if child.kind() == "type_declaration" {
+            if child.kind() == kinds::TYPE_DECLARATION {
                 let mut type_cursor = child.walk();
                 for type_child in child.children(&mut type_cursor) {
-                    if type_child.kind() == "type_spec" {
+                    if type_child.kind() == kinds::TYPE_SPEC {
                         let mut spec_cursor = type_child.walk();
                         for spec_child in type_child.children(&mut spec_cursor) {
-                            if spec_child.kind() == "interface_type" {
+                            if spec_child.kind() == kinds::INTERFACE_TYPE {
                                 let mut iface_cursor = spec_child.walk();
                                 for iface_child in spec_child.children(&mut iface_cursor) {
-                                    if iface_child.kind() == "method_elem" {
+                                    if iface_child.kind() == kinds::METHOD_ELEM {
                                         found_spec = Some(iface_child);
                                         break;
                                     }
@@ -256,7 +446,7 @@ mod tests {
         // Test call_hierarchy_target
         let target = GoLang.call_hierarchy_target(spec_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "field_identifier");
+        assert_eq!(target.unwrap().kind(), kinds::FIELD_IDENTIFIER);
     }
 
     #[test]
@@ -274,4 +464,54 @@ mod tests {
         let result = GoLang.call_hierarchy_target(root);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(GoLang.is_generated_file(Path::new("api/service.pb.go"), ""));
+        assert!(GoLang.is_generated_file(
+            Path::new("main.go"),
+            "// Code generated by stringer. DO NOT EDIT.\n"
+        ));
+        assert!(!GoLang.is_generated_file(Path::new("main.go"), "// Hand written\n"));
+    }
+
+    #[test]
+    fn test_return_type_node() -> anyhow::Result<()> {
+        let source = "package main\n\nfunc area() (float64, error) {}\n\nfunc main() {}\n";
+        let tree = crate::parser::parse_file_content(source, GoLang)?;
+        let function = tree.root_node().named_child(1).unwrap();
+        let return_type = GoLang.return_type_node(function).unwrap();
+        assert_eq!(
+            return_type.utf8_text(source.as_bytes())?,
+            "(float64, error)"
+        );
+
+        let function = tree.root_node().named_child(2).unwrap();
+        assert!(GoLang.return_type_node(function).is_none());
+        assert!(GoLang.return_type_node(tree.root_node()).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_import_to_path() -> anyhow::Result<()> {
+        let root = tempfile::TempDir::new()?;
+        std::fs::create_dir_all(root.path().join("internal/server"))?;
+        std::fs::write(
+            root.path().join("go.mod"),
+            "module example.com/app\n\ngo 1.22\n",
+        )?;
+
+        let source = "package main\n\nimport (\n\t\"fmt\"\n\t\"example.com/app/internal/server\"\n\t\"example.com/application\"\n)\n";
+        let tree = crate::parser::parse_file_content(source, GoLang)?;
+        let resolved: Vec<_> = crate::parser::get_imports(&tree, GoLang)
+            .map(|import| GoLang.resolve_import_to_path(import, source.as_bytes(), root.path()))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![None, Some(root.path().join("internal/server")), None]
+        );
+
+        Ok(())
+    }
 }
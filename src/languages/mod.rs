@@ -1,13 +1,91 @@
 //! Programming language implementations.
 
+/// Defines a constant for each node kind and `ALL`, a list of all of them
+macro_rules! node_kinds {
+    ($($name:ident = $kind:literal;)*) => {
+        $(pub const $name: &str = $kind;)*
+
+        /// All the kinds above
+        pub const ALL: &[&str] = &[$($name),*];
+    };
+}
+
 mod go;
+mod php;
 mod python;
 mod rust;
 mod swift;
 mod typescript;
 
 pub use go::GoLang;
+pub use php::PhpLang;
 pub use python::PythonLang;
 pub use rust::RustLang;
 pub use swift::SwiftLang;
 pub use typescript::TypeScriptLang;
+
+/// Node kind constants of each language's grammar
+///
+/// Referring to kinds through these constants turns a typo into a compile
+/// error instead of a node kind that silently never matches.
+pub mod kinds {
+    pub use super::go::kinds as go;
+    pub use super::php::kinds as php;
+    pub use super::python::kinds as python;
+    pub use super::rust::kinds as rust;
+    pub use super::swift::kinds as swift;
+    pub use super::typescript::kinds as typescript;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Language;
+
+    #[test]
+    fn test_node_kinds_exist_in_grammar() {
+        let grammars = [
+            (
+                GoLang.to_string(),
+                GoLang.tree_sitter_language(),
+                kinds::go::ALL,
+            ),
+            (
+                PhpLang.to_string(),
+                PhpLang.tree_sitter_language(),
+                kinds::php::ALL,
+            ),
+            (
+                PythonLang.to_string(),
+                PythonLang.tree_sitter_language(),
+                kinds::python::ALL,
+            ),
+            (
+                RustLang.to_string(),
+                RustLang.tree_sitter_language(),
+                kinds::rust::ALL,
+            ),
+            (
+                SwiftLang.to_string(),
+                SwiftLang.tree_sitter_language(),
+                kinds::swift::ALL,
+            ),
+            (
+                TypeScriptLang.to_string(),
+                TypeScriptLang.tree_sitter_language(),
+                kinds::typescript::ALL,
+            ),
+        ];
+        for (language, grammar, kinds) in grammars {
+            for kind in kinds {
+                assert_ne!(
+                    grammar.id_for_node_kind(kind, true),
+                    0,
+                    "'{}' is not a node kind of the {} grammar",
+                    kind,
+                    language
+                );
+            }
+        }
+    }
+}
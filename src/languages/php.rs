@@ -0,0 +1,232 @@
+//! PHP language implementation.
+
+use crate::language::{Language, closure_positions};
+use std::time::Duration;
+use tree_sitter::Node;
+
+/// Names of the PHP grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ANONYMOUS_FUNCTION = "anonymous_function";
+        ARGUMENTS = "arguments";
+        ARROW_FUNCTION = "arrow_function";
+        ATTRIBUTE_LIST = "attribute_list";
+        CLASS_DECLARATION = "class_declaration";
+        COMMENT = "comment";
+        FORMAL_PARAMETERS = "formal_parameters";
+        FUNCTION_CALL_EXPRESSION = "function_call_expression";
+        FUNCTION_DEFINITION = "function_definition";
+        INTERFACE_DECLARATION = "interface_declaration";
+        MEMBER_CALL_EXPRESSION = "member_call_expression";
+        METHOD_DECLARATION = "method_declaration";
+        NAMESPACE_USE_DECLARATION = "namespace_use_declaration";
+        NULLSAFE_MEMBER_CALL_EXPRESSION = "nullsafe_member_call_expression";
+        OBJECT_CREATION_EXPRESSION = "object_creation_expression";
+        PARENTHESIZED_EXPRESSION = "parenthesized_expression";
+        PROPERTY_PROMOTION_PARAMETER = "property_promotion_parameter";
+        SCOPED_CALL_EXPRESSION = "scoped_call_expression";
+        SIMPLE_PARAMETER = "simple_parameter";
+        TRAIT_DECLARATION = "trait_declaration";
+        VARIADIC_PARAMETER = "variadic_parameter";
+    }
+}
+
+/// PHP language implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhpLang;
+
+impl Language for PhpLang {
+    fn cli_name(&self) -> &'static str {
+        "php"
+    }
+
+    fn file_pattern(&self) -> &'static str {
+        r"\.php$"
+    }
+
+    fn extensions(&self) -> &'static str {
+        ".php"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "PHP"
+    }
+
+    fn lsp_server_command(&self) -> (&'static str, Vec<String>) {
+        ("intelephense", vec!["--stdio".to_string()])
+    }
+
+    fn indexing_warmup(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn tree_sitter_language(&self) -> tree_sitter::Language {
+        tree_sitter_php::LANGUAGE_PHP.into()
+    }
+
+    fn call_node_kinds(&self) -> &'static [&'static str] {
+        &[
+            kinds::FUNCTION_CALL_EXPRESSION,
+            kinds::MEMBER_CALL_EXPRESSION,
+            kinds::NULLSAFE_MEMBER_CALL_EXPRESSION,
+            kinds::SCOPED_CALL_EXPRESSION,
+            kinds::OBJECT_CREATION_EXPRESSION,
+        ]
+    }
+
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARENTHESIZED_EXPRESSION]
+    }
+
+    fn constructor_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::OBJECT_CREATION_EXPRESSION]
+    }
+
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INTERFACE_DECLARATION, kinds::TRAIT_DECLARATION]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::NAMESPACE_USE_DECLARATION]
+    }
+
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::ATTRIBUTE_LIST]
+    }
+
+    fn documentation_comment_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::COMMENT]
+    }
+
+    fn is_documentation_comment<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        // PHPDoc comments start with `/**`
+        node.kind() == kinds::COMMENT
+            && node
+                .utf8_text(source)
+                .unwrap_or_default()
+                .starts_with("/**")
+    }
+
+    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        match node.kind() {
+            // For method calls like `$obj->add()` and `Calc::make()`, the method
+            // name is the goto definition target
+            kinds::MEMBER_CALL_EXPRESSION
+            | kinds::NULLSAFE_MEMBER_CALL_EXPRESSION
+            | kinds::SCOPED_CALL_EXPRESSION => node.child_by_field_name("name"),
+            kinds::FUNCTION_CALL_EXPRESSION | kinds::OBJECT_CREATION_EXPRESSION => Some(node),
+            _ => None,
+        }
+    }
+
+    fn method_receiver_text<'a>(&self, call_node: Node<'a>, source: &'a [u8]) -> Option<&'a str> {
+        let receiver = match call_node.kind() {
+            kinds::MEMBER_CALL_EXPRESSION | kinds::NULLSAFE_MEMBER_CALL_EXPRESSION => {
+                call_node.child_by_field_name("object")?
+            }
+            kinds::SCOPED_CALL_EXPRESSION => call_node.child_by_field_name("scope")?,
+            _ => return None,
+        };
+        receiver.utf8_text(source).ok()
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        call_node
+            .child_by_field_name("arguments")
+            .filter(|arguments| arguments.kind() == kinds::ARGUMENTS)
+            .map(|arguments| {
+                closure_positions(
+                    arguments,
+                    &[kinds::ARROW_FUNCTION, kinds::ANONYMOUS_FUNCTION],
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    fn find_function_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if node.kind() != kinds::FUNCTION_DEFINITION && node.kind() != kinds::METHOD_DECLARATION {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn find_class_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if node.kind() != kinds::CLASS_DECLARATION {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn is_test_node<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        // PHPUnit runs the public methods whose names start with `test`
+        node.kind() == kinds::METHOD_DECLARATION
+            && node
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(source).ok())
+                .is_some_and(|name| name.starts_with("test"))
+    }
+
+    fn return_type_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        function_node.child_by_field_name("return_type")
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        &[
+            kinds::SIMPLE_PARAMETER,
+            kinds::VARIADIC_PARAMETER,
+            kinds::PROPERTY_PROMOTION_PARAMETER,
+        ]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        function_node
+            .child_by_field_name("parameters")
+            .filter(|parameters| parameters.kind() == kinds::FORMAL_PARAMETERS)
+    }
+
+    fn call_hierarchy_target<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        // Functions and methods, including the abstract methods of interfaces
+        self.find_function_declaration(node)
+    }
+}
+
+impl std::fmt::Display for PhpLang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{get_calls, parse_file_content};
+
+    #[test]
+    fn test_find_call() -> anyhow::Result<()> {
+        let source = r#"<?php
+greet("World");
+$calc = new Calc();
+$calc->add(1, 2);
+$calc?->reset();
+Calc::make();
+"#;
+        let tree = parse_file_content(source, PhpLang)?;
+        let calls: Vec<_> = get_calls(&tree, PhpLang).collect();
+        let targets: Vec<_> = calls
+            .iter()
+            .map(|call| call.goto_definition_node.utf8_text(source.as_bytes()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            targets,
+            [r#"greet("World")"#, "new Calc()", "add", "reset", "make"]
+        );
+
+        let receivers: Vec<_> = calls
+            .iter()
+            .filter_map(|call| PhpLang.method_receiver_text(call.call_node, source.as_bytes()))
+            .collect();
+        assert_eq!(receivers, ["$calc", "$calc", "Calc"]);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,116 @@
 //! Python language implementation.
 
-use crate::language::Language;
+use crate::language::{Language, closure_positions, has_generated_header};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tree_sitter::Node;
 
+/// Names of the Python grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ALIASED_IMPORT = "aliased_import";
+        ARGUMENT_LIST = "argument_list";
+        ASSIGNMENT = "assignment";
+        ATTRIBUTE = "attribute";
+        AWAIT = "await";
+        CALL = "call";
+        CLASS_DEFINITION = "class_definition";
+        DECORATOR = "decorator";
+        DEFAULT_PARAMETER = "default_parameter";
+        DICTIONARY_SPLAT_PATTERN = "dictionary_splat_pattern";
+        DOTTED_NAME = "dotted_name";
+        FLOAT = "float";
+        FUNCTION_DEFINITION = "function_definition";
+        IDENTIFIER = "identifier";
+        IMPORT_FROM_STATEMENT = "import_from_statement";
+        IMPORT_STATEMENT = "import_statement";
+        INTEGER = "integer";
+        LAMBDA = "lambda";
+        LIST_SPLAT_PATTERN = "list_splat_pattern";
+        MODULE = "module";
+        PARAMETERS = "parameters";
+        PARENTHESIZED_EXPRESSION = "parenthesized_expression";
+        TYPE = "type";
+        TYPED_DEFAULT_PARAMETER = "typed_default_parameter";
+        TYPED_PARAMETER = "typed_parameter";
+    }
+}
+
+/// The functions and types in `dir(builtins)`, leaving out exceptions and constants
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "__import__",
+    "abs",
+    "aiter",
+    "all",
+    "anext",
+    "any",
+    "ascii",
+    "bin",
+    "bool",
+    "breakpoint",
+    "bytearray",
+    "bytes",
+    "callable",
+    "chr",
+    "classmethod",
+    "compile",
+    "complex",
+    "delattr",
+    "dict",
+    "dir",
+    "divmod",
+    "enumerate",
+    "eval",
+    "exec",
+    "filter",
+    "float",
+    "format",
+    "frozenset",
+    "getattr",
+    "globals",
+    "hasattr",
+    "hash",
+    "help",
+    "hex",
+    "id",
+    "input",
+    "int",
+    "isinstance",
+    "issubclass",
+    "iter",
+    "len",
+    "list",
+    "locals",
+    "map",
+    "max",
+    "memoryview",
+    "min",
+    "next",
+    "object",
+    "oct",
+    "open",
+    "ord",
+    "pow",
+    "print",
+    "property",
+    "range",
+    "repr",
+    "reversed",
+    "round",
+    "set",
+    "setattr",
+    "slice",
+    "sorted",
+    "staticmethod",
+    "str",
+    "sum",
+    "super",
+    "tuple",
+    "type",
+    "vars",
+    "zip",
+];
+
 /// Python language implementation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PythonLang;
@@ -28,20 +136,116 @@ impl Language for PythonLang {
         ("pylsp", vec![])
     }
 
+    fn indexing_warmup(&self) -> Duration {
+        // pylsp analyzes files on demand and does not report progress
+        Duration::from_millis(500)
+    }
+
     fn tree_sitter_language(&self) -> tree_sitter::Language {
         tree_sitter_python::LANGUAGE.into()
     }
 
     fn call_node_kinds(&self) -> &'static [&'static str] {
-        &["call"]
+        &[kinds::CALL]
+    }
+
+    fn async_call_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::AWAIT]
     }
 
-    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
-        if !self.call_node_kinds().contains(&node.kind()) {
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARENTHESIZED_EXPRESSION]
+    }
+
+    fn number_literal_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INTEGER, kinds::FLOAT]
+    }
+
+    fn variable_declaration_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::ASSIGNMENT]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::IMPORT_STATEMENT, kinds::IMPORT_FROM_STATEMENT]
+    }
+
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::DECORATOR]
+    }
+
+    fn resolve_import_to_path<'a>(
+        &self,
+        import_node: Node<'a>,
+        source: &[u8],
+        project_root: &Path,
+    ) -> Option<PathBuf> {
+        // Only the first module of `import a, b` is resolved
+        let module = match import_node.kind() {
+            kinds::IMPORT_STATEMENT => import_node.child_by_field_name("name")?,
+            kinds::IMPORT_FROM_STATEMENT => import_node.child_by_field_name("module_name")?,
+            _ => return None,
+        };
+        let module = if module.kind() == kinds::ALIASED_IMPORT {
+            module.child_by_field_name("name")?
+        } else {
+            module
+        };
+        // Relative imports depend on the importing file
+        if module.kind() != kinds::DOTTED_NAME {
+            return None;
+        }
+        let module: PathBuf = module.utf8_text(source).ok()?.split('.').collect();
+        let candidates = [
+            project_root.join(&module).with_extension("py"),
+            project_root.join(&module).join("__init__.py"),
+        ];
+        candidates.into_iter().find(|file| file.is_file())
+    }
+
+    fn type_annotation_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::TYPE]
+    }
+
+    fn line_comment_prefix(&self) -> &'static str {
+        "#"
+    }
+
+    fn is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        let name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        name.ends_with("_pb2.py")
+            || name.ends_with("_pb2_grpc.py")
+            || has_generated_header(first_lines)
+    }
+
+    fn method_receiver_text<'a>(&self, call_node: Node<'a>, source: &'a [u8]) -> Option<&'a str> {
+        // Method calls have an `attribute` as the function, e.g. `obj.method()`
+        let function = call_node.child_by_field_name("function")?;
+        if function.kind() != kinds::ATTRIBUTE {
             return None;
         }
-        // For Python, return the call node itself as goto definition target
-        Some(node)
+        function
+            .child_by_field_name("object")?
+            .utf8_text(source)
+            .ok()
+    }
+
+    fn is_builtin_call<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        call_node
+            .child_by_field_name("function")
+            .filter(|function| function.kind() == kinds::IDENTIFIER)
+            .and_then(|function| function.utf8_text(source).ok())
+            .is_some_and(|name| BUILTIN_FUNCTIONS.contains(&name))
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        call_node
+            .child_by_field_name("arguments")
+            .filter(|arguments| arguments.kind() == kinds::ARGUMENT_LIST)
+            .map(|arguments| closure_positions(arguments, &[kinds::LAMBDA]))
+            .unwrap_or_default()
     }
 
     fn find_function_declaration<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
@@ -49,6 +253,48 @@ impl Language for PythonLang {
         None
     }
 
+    fn find_class_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if node.kind() != kinds::CLASS_DEFINITION {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn is_test_node<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        node.kind() == kinds::FUNCTION_DEFINITION
+            && node
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(source).ok())
+                .is_some_and(|name| name.starts_with("test_"))
+    }
+
+    fn return_type_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The expression after `->`
+        if function_node.kind() != kinds::FUNCTION_DEFINITION {
+            return None;
+        }
+        function_node.child_by_field_name("return_type")
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        // The `*` and `/` separators are not parameters
+        &[
+            kinds::IDENTIFIER,
+            kinds::TYPED_PARAMETER,
+            kinds::DEFAULT_PARAMETER,
+            kinds::TYPED_DEFAULT_PARAMETER,
+            kinds::LIST_SPLAT_PATTERN,
+            kinds::DICTIONARY_SPLAT_PATTERN,
+        ]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // Lambdas have `lambda_parameters` and are skipped
+        function_node
+            .child_by_field_name("parameters")
+            .filter(|parameters| parameters.kind() == kinds::PARAMETERS)
+    }
+
     fn call_hierarchy_target<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
         // Not implemented for Python
         None
@@ -60,3 +306,58 @@ impl std::fmt::Display for PythonLang {
         write!(f, "{}", self.display_name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(PythonLang.is_generated_file(Path::new("api/service_pb2.py"), ""));
+        assert!(PythonLang.is_generated_file(
+            Path::new("main.py"),
+            "# Code generated by a tool. DO NOT EDIT.\n"
+        ));
+        assert!(!PythonLang.is_generated_file(Path::new("main.py"), "# Hand written\n"));
+    }
+
+    #[test]
+    fn test_return_type_node() -> anyhow::Result<()> {
+        let source = "def area() -> float:\n    return 0.0\n\ndef main():\n    pass\n";
+        let tree = crate::parser::parse_file_content(source, PythonLang)?;
+        let function = tree.root_node().named_child(0).unwrap();
+        let return_type = PythonLang.return_type_node(function).unwrap();
+        assert_eq!(return_type.utf8_text(source.as_bytes())?, "float");
+
+        let function = tree.root_node().named_child(1).unwrap();
+        assert!(PythonLang.return_type_node(function).is_none());
+        assert!(PythonLang.return_type_node(tree.root_node()).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_import_to_path() -> anyhow::Result<()> {
+        let root = tempfile::TempDir::new()?;
+        std::fs::create_dir_all(root.path().join("app/models"))?;
+        std::fs::write(root.path().join("app/utils.py"), "")?;
+        std::fs::write(root.path().join("app/models/__init__.py"), "")?;
+
+        let source = "import app.utils as u\nfrom app.models import User\nimport os\nfrom . import sibling\n";
+        let tree = crate::parser::parse_file_content(source, PythonLang)?;
+        let resolved: Vec<_> = crate::parser::get_imports(&tree, PythonLang)
+            .map(|import| PythonLang.resolve_import_to_path(import, source.as_bytes(), root.path()))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                Some(root.path().join("app/utils.py")),
+                Some(root.path().join("app/models/__init__.py")),
+                None,
+                None,
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -1,8 +1,110 @@
 //! Rust language implementation.
 
-use crate::language::Language;
+use crate::language::{Language, closure_positions, has_generated_header};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tree_sitter::Node;
 
+/// Names of the Rust grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ARGUMENTS = "arguments";
+        ATTRIBUTE_ITEM = "attribute_item";
+        AWAIT_EXPRESSION = "await_expression";
+        BLOCK = "block";
+        CALL_EXPRESSION = "call_expression";
+        CLOSURE_EXPRESSION = "closure_expression";
+        DECLARATION_LIST = "declaration_list";
+        FIELD_EXPRESSION = "field_expression";
+        FLOAT_LITERAL = "float_literal";
+        FUNCTION_ITEM = "function_item";
+        FUNCTION_SIGNATURE_ITEM = "function_signature_item";
+        GENERIC_TYPE = "generic_type";
+        IDENTIFIER = "identifier";
+        INTEGER_LITERAL = "integer_literal";
+        LET_DECLARATION = "let_declaration";
+        LINE_COMMENT = "line_comment";
+        MACRO_INVOCATION = "macro_invocation";
+        PARAMETER = "parameter";
+        PARAMETERS = "parameters";
+        PARENTHESIZED_EXPRESSION = "parenthesized_expression";
+        SCOPED_IDENTIFIER = "scoped_identifier";
+        SELF_PARAMETER = "self_parameter";
+        SOURCE_FILE = "source_file";
+        TRAIT_ITEM = "trait_item";
+        TYPE_ARGUMENTS = "type_arguments";
+        TYPE_IDENTIFIER = "type_identifier";
+        USE_DECLARATION = "use_declaration";
+        VARIADIC_PARAMETER = "variadic_parameter";
+    }
+}
+
+/// Functions and enum variants from the prelude that are called unqualified
+const BUILTIN_FUNCTIONS: &[&str] = &["Some", "Ok", "Err", "drop"];
+
+/// The macros of the standard library
+const BUILTIN_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "dbg",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "eprint",
+    "eprintln",
+    "format",
+    "format_args",
+    "matches",
+    "panic",
+    "print",
+    "println",
+    "todo",
+    "unimplemented",
+    "unreachable",
+    "vec",
+    "write",
+    "writeln",
+];
+
+/// The methods of the operator traits in `std::ops` and `std::cmp`, with
+/// their trait and number of operands including `self`
+const OPERATOR_METHODS: &[(&str, &str, usize)] = &[
+    ("Add", "add", 2),
+    ("Sub", "sub", 2),
+    ("Mul", "mul", 2),
+    ("Div", "div", 2),
+    ("Rem", "rem", 2),
+    ("BitAnd", "bitand", 2),
+    ("BitOr", "bitor", 2),
+    ("BitXor", "bitxor", 2),
+    ("Shl", "shl", 2),
+    ("Shr", "shr", 2),
+    ("AddAssign", "add_assign", 2),
+    ("SubAssign", "sub_assign", 2),
+    ("MulAssign", "mul_assign", 2),
+    ("DivAssign", "div_assign", 2),
+    ("RemAssign", "rem_assign", 2),
+    ("BitAndAssign", "bitand_assign", 2),
+    ("BitOrAssign", "bitor_assign", 2),
+    ("BitXorAssign", "bitxor_assign", 2),
+    ("ShlAssign", "shl_assign", 2),
+    ("ShrAssign", "shr_assign", 2),
+    ("Neg", "neg", 1),
+    ("Not", "not", 1),
+    ("Deref", "deref", 1),
+    ("DerefMut", "deref_mut", 1),
+    ("Index", "index", 2),
+    ("IndexMut", "index_mut", 2),
+    ("PartialEq", "eq", 2),
+    ("PartialEq", "ne", 2),
+    ("PartialOrd", "partial_cmp", 2),
+    ("PartialOrd", "lt", 2),
+    ("PartialOrd", "le", 2),
+    ("PartialOrd", "gt", 2),
+    ("PartialOrd", "ge", 2),
+];
+
 /// Rust language implementation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RustLang;
@@ -28,32 +130,242 @@ impl Language for RustLang {
         ("rust-analyzer", vec![])
     }
 
+    fn indexing_warmup(&self) -> Duration {
+        // rust-analyzer loads the whole cargo workspace, including dependencies
+        Duration::from_secs(10)
+    }
+
     fn tree_sitter_language(&self) -> tree_sitter::Language {
         tree_sitter_rust::LANGUAGE.into()
     }
 
     fn call_node_kinds(&self) -> &'static [&'static str] {
-        &["call_expression", "macro_invocation"]
+        &[kinds::CALL_EXPRESSION, kinds::MACRO_INVOCATION]
+    }
+
+    fn macro_call_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::MACRO_INVOCATION]
+    }
+
+    fn async_call_node_kinds(&self) -> &'static [&'static str] {
+        // `foo().await`
+        &[kinds::AWAIT_EXPRESSION]
+    }
+
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARENTHESIZED_EXPRESSION]
+    }
+
+    fn generic_type_node_kinds(&self) -> &'static [&'static str] {
+        // Both `foo::<T>()` and `Foo::<T>::bar()`
+        &[kinds::TYPE_ARGUMENTS]
+    }
+
+    fn number_literal_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INTEGER_LITERAL, kinds::FLOAT_LITERAL]
+    }
+
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::TRAIT_ITEM]
     }
 
-    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
-        if !self.call_node_kinds().contains(&node.kind()) {
+    fn variable_declaration_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::LET_DECLARATION]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::USE_DECLARATION]
+    }
+
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::ATTRIBUTE_ITEM]
+    }
+
+    fn resolve_import_to_path<'a>(
+        &self,
+        import_node: Node<'a>,
+        source: &[u8],
+        project_root: &Path,
+    ) -> Option<PathBuf> {
+        if import_node.kind() != kinds::USE_DECLARATION {
             return None;
         }
-        // For Rust, return the call node itself as goto definition target
-        Some(node)
+        let argument = import_node
+            .child_by_field_name("argument")?
+            .utf8_text(source)
+            .ok()?;
+        // Only the path before a `{...}` list or an `as` rename leads to the module
+        let path = argument.split(['{', ' ']).next()?.trim_end_matches("::");
+        // Paths relative to `self` and `super` depend on the importing file
+        let mut segments: Vec<&str> = path.strip_prefix("crate::")?.split("::").collect();
+
+        // The last segments are usually items in the module rather than modules
+        let src = project_root.join("src");
+        while !segments.is_empty() {
+            let module: PathBuf = segments.iter().collect();
+            let candidates = [
+                src.join(&module).with_extension("rs"),
+                src.join(&module).join("mod.rs"),
+            ];
+            if let Some(file) = candidates.into_iter().find(|file| file.is_file()) {
+                return Some(file);
+            }
+            segments.pop();
+        }
+        // Items in the crate root
+        [src.join("lib.rs"), src.join("main.rs")]
+            .into_iter()
+            .find(|file| file.is_file())
+    }
+
+    fn type_annotation_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::TYPE_IDENTIFIER, kinds::GENERIC_TYPE]
+    }
+
+    fn documentation_comment_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::LINE_COMMENT]
+    }
+
+    fn is_documentation_comment<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        // Only outer doc comments (`///`) document the item that follows them
+        let text = node.utf8_text(source).unwrap_or_default();
+        node.kind() == kinds::LINE_COMMENT && text.starts_with("///") && !text.starts_with("////")
+    }
+
+    fn is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        let name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        name.ends_with("_generated.rs") || has_generated_header(first_lines)
+    }
+
+    fn is_builtin_call<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        let (field, builtins) = match call_node.kind() {
+            kinds::MACRO_INVOCATION => ("macro", BUILTIN_MACROS),
+            kinds::CALL_EXPRESSION => ("function", BUILTIN_FUNCTIONS),
+            _ => return false,
+        };
+        call_node
+            .child_by_field_name(field)
+            .filter(|name| name.kind() == kinds::IDENTIFIER)
+            .and_then(|name| name.utf8_text(source).ok())
+            .is_some_and(|name| builtins.contains(&name))
+    }
+
+    fn should_skip_as_operator<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        if call_node.kind() != kinds::CALL_EXPRESSION {
+            return false;
+        }
+        let (Some(function), Some(arguments)) = (
+            call_node.child_by_field_name("function"),
+            call_node.child_by_field_name("arguments"),
+        ) else {
+            return false;
+        };
+        let mut cursor = arguments.walk();
+        let argument_count = arguments
+            .named_children(&mut cursor)
+            .filter(|argument| !argument.is_extra())
+            .count();
+        let text = |node: Option<Node>| node.and_then(|node| node.utf8_text(source).ok());
+
+        match function.kind() {
+            // `a.add(b)`, the receiver is an operand
+            kinds::FIELD_EXPRESSION => {
+                let method = text(function.child_by_field_name("field"));
+                OPERATOR_METHODS.iter().any(|&(_, name, operands)| {
+                    method == Some(name) && argument_count + 1 == operands
+                })
+            }
+            // `Add::add(a, b)` and `std::ops::Add::add(a, b)`
+            kinds::SCOPED_IDENTIFIER => {
+                let method = text(function.child_by_field_name("name"));
+                let path = function.child_by_field_name("path");
+                let trait_name = match path {
+                    Some(path) if path.kind() == kinds::SCOPED_IDENTIFIER => {
+                        text(path.child_by_field_name("name"))
+                    }
+                    _ => text(path),
+                };
+                OPERATOR_METHODS
+                    .iter()
+                    .any(|&(operator_trait, name, operands)| {
+                        trait_name == Some(operator_trait)
+                            && method == Some(name)
+                            && argument_count == operands
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        call_node
+            .child_by_field_name("arguments")
+            .filter(|arguments| arguments.kind() == kinds::ARGUMENTS)
+            .map(|arguments| closure_positions(arguments, &[kinds::CLOSURE_EXPRESSION]))
+            .unwrap_or_default()
     }
 
     fn find_function_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
         // Check if this is a function item
-        if node.kind() != "function_item" {
+        if node.kind() != kinds::FUNCTION_ITEM {
             return None;
         }
 
         // Find the identifier child
         let mut cursor = node.walk();
         node.children(&mut cursor)
-            .find(|&child| child.kind() == "identifier")
+            .find(|&child| child.kind() == kinds::IDENTIFIER)
+    }
+
+    fn is_test_node<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        if node.kind() != kinds::FUNCTION_ITEM {
+            return false;
+        }
+
+        // Look for `#[test]`, `#[tokio::test]` etc. among the attributes of the function
+        let mut sibling = node.prev_sibling();
+        while let Some(attribute) = sibling.filter(|s| s.kind() == kinds::ATTRIBUTE_ITEM) {
+            let text = attribute.utf8_text(source).unwrap_or_default();
+            let path = text
+                .trim_start_matches("#[")
+                .split(['(', ']'])
+                .next()
+                .unwrap_or_default()
+                .trim();
+            if path.rsplit("::").next() == Some("test") {
+                return true;
+            }
+            sibling = attribute.prev_sibling();
+        }
+        false
+    }
+
+    fn return_type_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The type after `->`
+        match function_node.kind() {
+            kinds::FUNCTION_ITEM | kinds::FUNCTION_SIGNATURE_ITEM => {
+                function_node.child_by_field_name("return_type")
+            }
+            _ => None,
+        }
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        &[
+            kinds::PARAMETER,
+            kinds::SELF_PARAMETER,
+            kinds::VARIADIC_PARAMETER,
+        ]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // Closures have `closure_parameters` which are not functions we report
+        function_node
+            .child_by_field_name("parameters")
+            .filter(|parameters| parameters.kind() == kinds::PARAMETERS)
     }
 
     fn call_hierarchy_target<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
@@ -61,12 +373,12 @@ impl Language for RustLang {
         // - function_item (top-level functions and associated functions)
         // - function_signature_item (trait methods)
         match node.kind() {
-            "function_item" => self.find_function_declaration(node),
-            "function_signature_item" => {
+            kinds::FUNCTION_ITEM => self.find_function_declaration(node),
+            kinds::FUNCTION_SIGNATURE_ITEM => {
                 // For trait method signatures, find the identifier
                 let mut cursor = node.walk();
                 node.children(&mut cursor)
-                    .find(|&child| child.kind() == "identifier")
+                    .find(|&child| child.kind() == kinds::IDENTIFIER)
             }
             _ => None,
         }
@@ -100,14 +412,14 @@ mod tests {
         let mut cursor = root.walk();
         let function_node = root
             .children(&mut cursor)
-            .find(|n| n.kind() == "function_item")
+            .find(|n| n.kind() == kinds::FUNCTION_ITEM)
             .expect("Should find function_item");
 
         // Test find_function_declaration
         let identifier = RustLang.find_function_declaration(function_node);
         assert!(identifier.is_some());
         let identifier = identifier.unwrap();
-        assert_eq!(identifier.kind(), "identifier");
+        assert_eq!(identifier.kind(), kinds::IDENTIFIER);
         assert_eq!(identifier.utf8_text(source.as_bytes()).unwrap(), "hello");
     }
 
@@ -146,13 +458,13 @@ mod tests {
         let mut cursor = root.walk();
         let function_node = root
             .children(&mut cursor)
-            .find(|n| n.kind() == "function_item")
+            .find(|n| n.kind() == kinds::FUNCTION_ITEM)
             .expect("Should find function_item");
 
         // Test call_hierarchy_target
         let target = RustLang.call_hierarchy_target(function_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "identifier");
+        assert_eq!(target.unwrap().kind(), kinds::IDENTIFIER);
     }
 
     #[test]
@@ -172,13 +484,13 @@ mod tests {
         let mut found_signature = None;
         let mut cursor = root.walk();
         for child in root.children(&mut cursor) {
-            if child.kind() == "trait_item" {
+            if child.kind() == kinds::TRAIT_ITEM {
                 let mut trait_cursor = child.walk();
                 for trait_child in child.children(&mut trait_cursor) {
-                    if trait_child.kind() == "declaration_list" {
+                    if trait_child.kind() == kinds::DECLARATION_LIST {
                         let mut decl_cursor = trait_child.walk();
                         for decl_child in trait_child.children(&mut decl_cursor) {
-                            if decl_child.kind() == "function_signature_item" {
+                            if decl_child.kind() == kinds::FUNCTION_SIGNATURE_ITEM {
                                 found_signature = Some(decl_child);
                                 break;
                             }
@@ -193,7 +505,7 @@ mod tests {
         // Test call_hierarchy_target
         let target = RustLang.call_hierarchy_target(signature_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "identifier");
+        assert_eq!(target.unwrap().kind(), kinds::IDENTIFIER);
     }
 
     #[test]
@@ -213,4 +525,85 @@ mod tests {
         let result = RustLang.call_hierarchy_target(root);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(RustLang.is_generated_file(Path::new("src/schema_generated.rs"), ""));
+        assert!(RustLang.is_generated_file(Path::new("src/lib.rs"), "// @generated by build.rs\n"));
+        assert!(!RustLang.is_generated_file(Path::new("src/lib.rs"), "// Hand written\n"));
+    }
+
+    #[test]
+    fn test_return_type_node() -> anyhow::Result<()> {
+        let source = "fn area(&self) -> f64 { 0.0 }\nfn main() {}\n";
+        let tree = crate::parser::parse_file_content(source, RustLang)?;
+        let function = tree.root_node().named_child(0).unwrap();
+        let return_type = RustLang.return_type_node(function).unwrap();
+        assert_eq!(return_type.utf8_text(source.as_bytes())?, "f64");
+
+        let function = tree.root_node().named_child(1).unwrap();
+        assert!(RustLang.return_type_node(function).is_none());
+        assert!(RustLang.return_type_node(tree.root_node()).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_import_to_path() -> anyhow::Result<()> {
+        let root = tempfile::TempDir::new()?;
+        let src = root.path().join("src");
+        std::fs::create_dir_all(src.join("parser"))?;
+        std::fs::write(src.join("lib.rs"), "")?;
+        std::fs::write(src.join("location.rs"), "")?;
+        std::fs::write(src.join("parser/mod.rs"), "")?;
+
+        let source = "use crate::location::{lsp_to_display, Range};\nuse crate::parser::tests::helper as h;\nuse crate::Language;\nuse std::path::Path;\nuse super::kinds;\n";
+        let tree = crate::parser::parse_file_content(source, RustLang)?;
+        let resolved: Vec<_> = crate::parser::get_imports(&tree, RustLang)
+            .map(|import| RustLang.resolve_import_to_path(import, source.as_bytes(), root.path()))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                Some(src.join("location.rs")),
+                Some(src.join("parser/mod.rs")),
+                Some(src.join("lib.rs")),
+                None,
+                None,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_skip_as_operator() -> anyhow::Result<()> {
+        let source = "fn f() {\n    a.add(b);\n    std::ops::Add::add(a, b);\n    Mul::mul(a, b);\n    x.neg();\n    v.index(0);\n    list.add(a, b);\n    Parser::add(a, b);\n    add(a, b);\n    a.eq(&b);\n}\n";
+        let tree = crate::parser::parse_file_content(source, RustLang)?;
+        let operators: Vec<_> = crate::parser::get_calls(&tree, RustLang)
+            .filter(|call| RustLang.should_skip_as_operator(call.call_node, source.as_bytes()))
+            .map(|call| call.call_node.utf8_text(source.as_bytes()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            operators,
+            [
+                "a.add(b)",
+                "std::ops::Add::add(a, b)",
+                "Mul::mul(a, b)",
+                "x.neg()",
+                "v.index(0)",
+                "a.eq(&b)"
+            ]
+        );
+
+        let config = crate::parser::CallIteratorConfig {
+            skip_operators: true,
+            ..Default::default()
+        };
+        let calls =
+            crate::parser::get_calls_with_config(&tree, RustLang, source.as_bytes(), config);
+        assert_eq!(calls.count(), 3);
+
+        Ok(())
+    }
 }
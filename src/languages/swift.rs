@@ -1,8 +1,57 @@
 //! Swift language implementation.
 
-use crate::language::Language;
+use crate::language::{Language, closure_positions, has_generated_header};
+use std::path::Path;
+use std::time::Duration;
 use tree_sitter::Node;
 
+/// Names of the Swift grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ATTRIBUTE = "attribute";
+        AWAIT_EXPRESSION = "await_expression";
+        CALL_EXPRESSION = "call_expression";
+        CALL_SUFFIX = "call_suffix";
+        CLASS_BODY = "class_body";
+        CLASS_DECLARATION = "class_declaration";
+        FUNCTION_DECLARATION = "function_declaration";
+        IDENTIFIER = "identifier";
+        IMPORT_DECLARATION = "import_declaration";
+        LAMBDA_LITERAL = "lambda_literal";
+        MODIFIERS = "modifiers";
+        NAVIGATION_EXPRESSION = "navigation_expression";
+        NAVIGATION_SUFFIX = "navigation_suffix";
+        PARAMETER = "parameter";
+        PROTOCOL_BODY = "protocol_body";
+        PROTOCOL_DECLARATION = "protocol_declaration";
+        PROTOCOL_FUNCTION_DECLARATION = "protocol_function_declaration";
+        SIMPLE_IDENTIFIER = "simple_identifier";
+        SOURCE_FILE = "source_file";
+        TUPLE_EXPRESSION = "tuple_expression";
+        VALUE_ARGUMENTS = "value_arguments";
+    }
+}
+
+/// Global functions of the Swift standard library
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "abs",
+    "assert",
+    "assertionFailure",
+    "debugPrint",
+    "dump",
+    "fatalError",
+    "max",
+    "min",
+    "precondition",
+    "preconditionFailure",
+    "print",
+    "repeatElement",
+    "stride",
+    "swap",
+    "type",
+    "zip",
+];
+
 /// Swift language implementation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SwiftLang;
@@ -28,12 +77,48 @@ impl Language for SwiftLang {
         ("sourcekit-lsp", vec![])
     }
 
+    fn indexing_warmup(&self) -> Duration {
+        // sourcekit-lsp builds the package before it can index it
+        Duration::from_secs(10)
+    }
+
     fn tree_sitter_language(&self) -> tree_sitter::Language {
         tree_sitter_swift::LANGUAGE.into()
     }
 
     fn call_node_kinds(&self) -> &'static [&'static str] {
-        &["call_expression", "function_call_expression"]
+        &[kinds::CALL_EXPRESSION]
+    }
+
+    fn async_call_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::AWAIT_EXPRESSION]
+    }
+
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        // Parentheses are parsed as a tuple with a single element
+        &[kinds::TUPLE_EXPRESSION]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::IMPORT_DECLARATION]
+    }
+
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::ATTRIBUTE]
+    }
+
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PROTOCOL_DECLARATION]
+    }
+
+    fn is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        let name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        name.ends_with(".pb.swift")
+            || name.ends_with(".generated.swift")
+            || has_generated_header(first_lines)
     }
 
     fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
@@ -42,7 +127,7 @@ impl Language for SwiftLang {
         }
 
         // For Swift method calls, find the method name
-        if node.kind() == "call_expression" {
+        if node.kind() == kinds::CALL_EXPRESSION {
             // Look for a child that represents the method/function being called
             let mut cursor = node.walk();
 
@@ -54,15 +139,15 @@ impl Language for SwiftLang {
                 //     simple_identifier ("calc")
                 //     navigation_suffix
                 //       simple_identifier ("add") <- This is what we want
-                if child.kind() == "navigation_expression" {
+                if child.kind() == kinds::NAVIGATION_EXPRESSION {
                     // Find the navigation suffix which contains the method name
                     let mut nav_cursor = child.walk();
                     for nav_child in child.children(&mut nav_cursor) {
-                        if nav_child.kind() == "navigation_suffix" {
+                        if nav_child.kind() == kinds::NAVIGATION_SUFFIX {
                             // Find the identifier within the suffix
                             let mut suffix_cursor = nav_child.walk();
                             for suffix_child in nav_child.children(&mut suffix_cursor) {
-                                if suffix_child.kind() == "simple_identifier" {
+                                if suffix_child.kind() == kinds::SIMPLE_IDENTIFIER {
                                     return Some(suffix_child);
                                 }
                             }
@@ -70,7 +155,9 @@ impl Language for SwiftLang {
                     }
                 }
                 // For simple function calls (not method calls), look for the function name directly
-                else if child.kind() == "simple_identifier" || child.kind() == "identifier" {
+                else if child.kind() == kinds::SIMPLE_IDENTIFIER
+                    || child.kind() == kinds::IDENTIFIER
+                {
                     return Some(child);
                 }
             }
@@ -80,16 +167,98 @@ impl Language for SwiftLang {
         Some(node)
     }
 
+    fn method_receiver_text<'a>(&self, call_node: Node<'a>, source: &'a [u8]) -> Option<&'a str> {
+        if call_node.kind() != kinds::CALL_EXPRESSION {
+            return None;
+        }
+
+        // The receiver is the first identifier of the navigation expression,
+        // e.g. `calc` in `calc.add(2, 3)`
+        let mut cursor = call_node.walk();
+        let navigation = call_node
+            .children(&mut cursor)
+            .find(|child| child.kind() == kinds::NAVIGATION_EXPRESSION)?;
+        let mut nav_cursor = navigation.walk();
+        let receiver = navigation
+            .children(&mut nav_cursor)
+            .find(|child| child.kind() == kinds::SIMPLE_IDENTIFIER)?;
+        receiver.utf8_text(source).ok()
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        let mut cursor = call_node.walk();
+        let Some(suffix) = call_node
+            .named_children(&mut cursor)
+            .find(|child| child.kind() == kinds::CALL_SUFFIX)
+        else {
+            return vec![];
+        };
+        // Trailing closures like `xs.sorted { $0 < $1 }` follow the parenthesized arguments
+        let mut positions = Vec::new();
+        let mut argument_count = 0;
+        let mut cursor = suffix.walk();
+        for child in suffix.named_children(&mut cursor) {
+            match child.kind() {
+                kinds::VALUE_ARGUMENTS => {
+                    positions.extend(closure_positions(child, &[kinds::LAMBDA_LITERAL]));
+                    let mut cursor = child.walk();
+                    argument_count = child
+                        .named_children(&mut cursor)
+                        .filter(|argument| !argument.is_extra())
+                        .count();
+                }
+                kinds::LAMBDA_LITERAL => {
+                    positions.push(argument_count);
+                    argument_count += 1;
+                }
+                _ => {}
+            }
+        }
+        positions
+    }
+
     fn find_function_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
         // Check if this is a function declaration
-        if node.kind() != "function_declaration" {
+        if node.kind() != kinds::FUNCTION_DECLARATION {
             return None;
         }
 
         // Find the simple_identifier child
         let mut cursor = node.walk();
         node.children(&mut cursor)
-            .find(|&child| child.kind() == "simple_identifier")
+            .find(|&child| child.kind() == kinds::SIMPLE_IDENTIFIER)
+    }
+
+    fn find_class_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if node.kind() != kinds::CLASS_DECLARATION {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARAMETER]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The grammar has no parameter list node, the parameters are direct
+        // children of the function declaration
+        match function_node.kind() {
+            kinds::FUNCTION_DECLARATION | kinds::PROTOCOL_FUNCTION_DECLARATION => {
+                Some(function_node)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_builtin_call<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        // Global functions are called by a plain identifier, the first child
+        call_node.kind() == kinds::CALL_EXPRESSION
+            && call_node
+                .named_child(0)
+                .filter(|function| function.kind() == kinds::SIMPLE_IDENTIFIER)
+                .and_then(|function| function.utf8_text(source).ok())
+                .is_some_and(|name| BUILTIN_FUNCTIONS.contains(&name))
     }
 
     fn call_hierarchy_target<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
@@ -97,12 +266,12 @@ impl Language for SwiftLang {
         // - function_declaration (functions and methods)
         // - protocol_function_declaration (protocol method requirements)
         match node.kind() {
-            "function_declaration" => self.find_function_declaration(node),
-            "protocol_function_declaration" => {
+            kinds::FUNCTION_DECLARATION => self.find_function_declaration(node),
+            kinds::PROTOCOL_FUNCTION_DECLARATION => {
                 // For protocol function declarations, find the simple_identifier
                 let mut cursor = node.walk();
                 node.children(&mut cursor)
-                    .find(|&child| child.kind() == "simple_identifier")
+                    .find(|&child| child.kind() == kinds::SIMPLE_IDENTIFIER)
             }
             _ => None,
         }
@@ -136,14 +305,14 @@ mod tests {
         let mut cursor = root.walk();
         let function_node = root
             .children(&mut cursor)
-            .find(|n| n.kind() == "function_declaration")
+            .find(|n| n.kind() == kinds::FUNCTION_DECLARATION)
             .expect("Should find function_declaration");
 
         // Test find_function_declaration
         let identifier = SwiftLang.find_function_declaration(function_node);
         assert!(identifier.is_some());
         let identifier = identifier.unwrap();
-        assert_eq!(identifier.kind(), "simple_identifier");
+        assert_eq!(identifier.kind(), kinds::SIMPLE_IDENTIFIER);
         assert_eq!(identifier.utf8_text(source.as_bytes()).unwrap(), "hello");
     }
 
@@ -164,7 +333,7 @@ mod tests {
         let mut cursor = root.walk();
         let mut class_node = None;
         for child in root.children(&mut cursor) {
-            if child.kind() == "class_declaration" {
+            if child.kind() == kinds::CLASS_DECLARATION {
                 class_node = Some(child);
                 break;
             }
@@ -175,10 +344,10 @@ mod tests {
         let mut cursor = class_node.walk();
         let mut method_node = None;
         for child in class_node.children(&mut cursor) {
-            if child.kind() == "class_body" {
+            if child.kind() == kinds::CLASS_BODY {
                 let mut body_cursor = child.walk();
                 for body_child in child.children(&mut body_cursor) {
-                    if body_child.kind() == "function_declaration" {
+                    if body_child.kind() == kinds::FUNCTION_DECLARATION {
                         method_node = Some(body_child);
                         break;
                     }
@@ -192,7 +361,7 @@ mod tests {
         let identifier = SwiftLang.find_function_declaration(method_node);
         assert!(identifier.is_some());
         let identifier = identifier.unwrap();
-        assert_eq!(identifier.kind(), "simple_identifier");
+        assert_eq!(identifier.kind(), kinds::SIMPLE_IDENTIFIER);
         assert_eq!(identifier.utf8_text(source.as_bytes()).unwrap(), "myMethod");
     }
 
@@ -231,13 +400,13 @@ mod tests {
         let mut cursor = root.walk();
         let function_node = root
             .children(&mut cursor)
-            .find(|n| n.kind() == "function_declaration")
+            .find(|n| n.kind() == kinds::FUNCTION_DECLARATION)
             .expect("Should find function_declaration");
 
         // Test call_hierarchy_target
         let target = SwiftLang.call_hierarchy_target(function_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "simple_identifier");
+        assert_eq!(target.unwrap().kind(), kinds::SIMPLE_IDENTIFIER);
     }
 
     #[test]
@@ -257,13 +426,13 @@ mod tests {
         let mut found_protocol_func = None;
         let mut cursor = root.walk();
         for child in root.children(&mut cursor) {
-            if child.kind() == "protocol_declaration" {
+            if child.kind() == kinds::PROTOCOL_DECLARATION {
                 let mut protocol_cursor = child.walk();
                 for protocol_child in child.children(&mut protocol_cursor) {
-                    if protocol_child.kind() == "protocol_body" {
+                    if protocol_child.kind() == kinds::PROTOCOL_BODY {
                         let mut body_cursor = protocol_child.walk();
                         for body_child in protocol_child.children(&mut body_cursor) {
-                            if body_child.kind() == "protocol_function_declaration" {
+                            if body_child.kind() == kinds::PROTOCOL_FUNCTION_DECLARATION {
                                 found_protocol_func = Some(body_child);
                                 break;
                             }
@@ -279,7 +448,7 @@ mod tests {
         // Test call_hierarchy_target
         let target = SwiftLang.call_hierarchy_target(protocol_func_node);
         assert!(target.is_some());
-        assert_eq!(target.unwrap().kind(), "simple_identifier");
+        assert_eq!(target.unwrap().kind(), kinds::SIMPLE_IDENTIFIER);
     }
 
     #[test]
@@ -299,4 +468,11 @@ mod tests {
         let result = SwiftLang.call_hierarchy_target(root);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(SwiftLang.is_generated_file(Path::new("Sources/Api.pb.swift"), ""));
+        assert!(SwiftLang.is_generated_file(Path::new("Sources/main.swift"), "// DO NOT EDIT.\n"));
+        assert!(!SwiftLang.is_generated_file(Path::new("Sources/main.swift"), "// Hand written\n"));
+    }
 }
@@ -1,8 +1,83 @@
 //! TypeScript language implementation.
 
-use crate::language::Language;
+use crate::language::{Language, closure_positions, has_generated_header};
+use std::path::Path;
+use std::time::Duration;
 use tree_sitter::Node;
 
+/// Names of the TypeScript grammar node kinds used by this implementation
+pub mod kinds {
+    node_kinds! {
+        ARGUMENTS = "arguments";
+        ARROW_FUNCTION = "arrow_function";
+        AWAIT_EXPRESSION = "await_expression";
+        CALL_EXPRESSION = "call_expression";
+        CLASS_DECLARATION = "class_declaration";
+        COMMENT = "comment";
+        DECORATOR = "decorator";
+        FORMAL_PARAMETERS = "formal_parameters";
+        FUNCTION_EXPRESSION = "function_expression";
+        IDENTIFIER = "identifier";
+        IMPORT_STATEMENT = "import_statement";
+        INTERFACE_DECLARATION = "interface_declaration";
+        LEXICAL_DECLARATION = "lexical_declaration";
+        MEMBER_EXPRESSION = "member_expression";
+        METHOD_DEFINITION = "method_definition";
+        NEW_EXPRESSION = "new_expression";
+        NUMBER = "number";
+        OPTIONAL_PARAMETER = "optional_parameter";
+        PARENTHESIZED_EXPRESSION = "parenthesized_expression";
+        PROGRAM = "program";
+        REQUIRED_PARAMETER = "required_parameter";
+        TYPE_ANNOTATION = "type_annotation";
+        TYPE_ARGUMENTS = "type_arguments";
+        VARIABLE_DECLARATION = "variable_declaration";
+    }
+}
+
+/// Global functions of JavaScript and common runtimes
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "BigInt",
+    "Boolean",
+    "Number",
+    "String",
+    "Symbol",
+    "clearInterval",
+    "clearTimeout",
+    "decodeURI",
+    "decodeURIComponent",
+    "encodeURI",
+    "encodeURIComponent",
+    "isFinite",
+    "isNaN",
+    "parseFloat",
+    "parseInt",
+    "require",
+    "setInterval",
+    "setTimeout",
+    "structuredClone",
+];
+
+/// Global classes that are commonly constructed with `new`
+const BUILTIN_CLASSES: &[&str] = &[
+    "Array",
+    "Date",
+    "Error",
+    "Map",
+    "Promise",
+    "RegExp",
+    "Set",
+    "TypeError",
+    "URL",
+    "WeakMap",
+    "WeakSet",
+];
+
+/// Global objects whose methods are treated as builtins
+const BUILTIN_OBJECTS: &[&str] = &[
+    "Array", "JSON", "Math", "Number", "Object", "Promise", "Reflect", "String", "console",
+];
+
 /// TypeScript language implementation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TypeScriptLang;
@@ -24,24 +99,138 @@ impl Language for TypeScriptLang {
         "TypeScript"
     }
 
+    fn to_lsp_language_id(&self, file_path: Option<&Path>) -> &'static str {
+        match file_path.and_then(|path| path.extension()?.to_str()) {
+            Some("tsx") => "typescriptreact",
+            _ => "typescript",
+        }
+    }
+
     fn lsp_server_command(&self) -> (&'static str, Vec<String>) {
         ("typescript-language-server", vec!["--stdio".to_string()])
     }
 
+    fn indexing_warmup(&self) -> Duration {
+        Duration::from_secs(3)
+    }
+
     fn tree_sitter_language(&self) -> tree_sitter::Language {
         tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()
     }
 
     fn call_node_kinds(&self) -> &'static [&'static str] {
-        &["call_expression", "new_expression"]
+        &[kinds::CALL_EXPRESSION, kinds::NEW_EXPRESSION]
+    }
+
+    fn constructor_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::NEW_EXPRESSION]
+    }
+
+    fn async_call_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::AWAIT_EXPRESSION]
+    }
+
+    fn parenthesized_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::PARENTHESIZED_EXPRESSION]
+    }
+
+    fn generic_type_node_kinds(&self) -> &'static [&'static str] {
+        // Both `foo<T>()` and `Array<string>.from()`
+        &[kinds::TYPE_ARGUMENTS]
+    }
+
+    fn number_literal_node_kinds(&self) -> &'static [&'static str] {
+        // Also BigInt literals like `10n`
+        &[kinds::NUMBER]
+    }
+
+    fn trait_interface_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::INTERFACE_DECLARATION]
+    }
+
+    fn variable_declaration_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::LEXICAL_DECLARATION, kinds::VARIABLE_DECLARATION]
+    }
+
+    fn import_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::IMPORT_STATEMENT]
+    }
+
+    fn attribute_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::DECORATOR]
+    }
+
+    fn type_annotation_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::TYPE_ANNOTATION]
     }
 
-    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
-        if !self.call_node_kinds().contains(&node.kind()) {
+    fn documentation_comment_node_kinds(&self) -> &'static [&'static str] {
+        &[kinds::COMMENT]
+    }
+
+    fn is_documentation_comment<'a>(&self, node: Node<'a>, source: &[u8]) -> bool {
+        // JSDoc comments start with `/**`
+        node.kind() == kinds::COMMENT
+            && node
+                .utf8_text(source)
+                .unwrap_or_default()
+                .starts_with("/**")
+    }
+
+    fn is_generated_file(&self, file_path: &Path, first_lines: &str) -> bool {
+        let name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        name.ends_with(".generated.ts")
+            || name.ends_with("_pb.ts")
+            || has_generated_header(first_lines)
+    }
+
+    fn method_receiver_text<'a>(&self, call_node: Node<'a>, source: &'a [u8]) -> Option<&'a str> {
+        // Method calls have a `member_expression` as the function, e.g. `obj.method()`
+        let function = call_node.child_by_field_name("function")?;
+        if function.kind() != kinds::MEMBER_EXPRESSION {
             return None;
         }
-        // For TypeScript, return the call node itself as goto definition target
-        Some(node)
+        function
+            .child_by_field_name("object")?
+            .utf8_text(source)
+            .ok()
+    }
+
+    fn is_builtin_call<'a>(&self, call_node: Node<'a>, source: &[u8]) -> bool {
+        let text = |node: Node<'a>| node.utf8_text(source).ok();
+        let (field, builtins) = match call_node.kind() {
+            kinds::NEW_EXPRESSION => ("constructor", BUILTIN_CLASSES),
+            _ => ("function", BUILTIN_FUNCTIONS),
+        };
+        let Some(function) = call_node.child_by_field_name(field) else {
+            return false;
+        };
+        match function.kind() {
+            kinds::IDENTIFIER => text(function).is_some_and(|name| builtins.contains(&name)),
+            // Methods of global objects like `console.log` and `Math.max`
+            kinds::MEMBER_EXPRESSION => function
+                .child_by_field_name("object")
+                .filter(|object| object.kind() == kinds::IDENTIFIER)
+                .and_then(text)
+                .is_some_and(|object| BUILTIN_OBJECTS.contains(&object)),
+            _ => false,
+        }
+    }
+
+    fn closure_argument_positions<'a>(&self, call_node: Node<'a>) -> Vec<usize> {
+        call_node
+            .child_by_field_name("arguments")
+            .filter(|arguments| arguments.kind() == kinds::ARGUMENTS)
+            .map(|arguments| {
+                closure_positions(
+                    arguments,
+                    &[kinds::ARROW_FUNCTION, kinds::FUNCTION_EXPRESSION],
+                )
+            })
+            .unwrap_or_default()
     }
 
     fn find_function_declaration<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
@@ -49,6 +238,29 @@ impl Language for TypeScriptLang {
         None
     }
 
+    fn find_class_declaration<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if node.kind() != kinds::CLASS_DECLARATION {
+            return None;
+        }
+        node.child_by_field_name("name")
+    }
+
+    fn return_type_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        // The `type_annotation` following the parameter list
+        function_node.child_by_field_name("return_type")
+    }
+
+    fn parameter_node_kinds(&self) -> &'static [&'static str] {
+        // Rest parameters are required parameters with a `rest_pattern`
+        &[kinds::REQUIRED_PARAMETER, kinds::OPTIONAL_PARAMETER]
+    }
+
+    fn parameter_list_node<'a>(&self, function_node: Node<'a>) -> Option<Node<'a>> {
+        function_node
+            .child_by_field_name("parameters")
+            .filter(|parameters| parameters.kind() == kinds::FORMAL_PARAMETERS)
+    }
+
     fn call_hierarchy_target<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
         // Not implemented for TypeScript
         None
@@ -60,3 +272,47 @@ impl std::fmt::Display for TypeScriptLang {
         write!(f, "{}", self.display_name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(TypeScriptLang.is_generated_file(Path::new("src/api.generated.ts"), ""));
+        assert!(TypeScriptLang.is_generated_file(
+            Path::new("src/index.ts"),
+            "/* eslint-disable */\n// This file is auto-generated\n"
+        ));
+        assert!(!TypeScriptLang.is_generated_file(Path::new("src/index.ts"), "// Hand written\n"));
+    }
+
+    #[test]
+    fn test_to_lsp_language_id() {
+        assert_eq!(TypeScriptLang.to_lsp_language_id(None), "typescript");
+        assert_eq!(
+            TypeScriptLang.to_lsp_language_id(Some(Path::new("src/index.ts"))),
+            "typescript"
+        );
+        assert_eq!(
+            TypeScriptLang.to_lsp_language_id(Some(Path::new("src/App.tsx"))),
+            "typescriptreact"
+        );
+        assert_eq!(crate::GoLang.to_lsp_language_id(None), "go");
+    }
+
+    #[test]
+    fn test_return_type_node() -> anyhow::Result<()> {
+        let source = "function area(): number { return 0; }\nfunction main() {}\n";
+        let tree = crate::parser::parse_file_content(source, TypeScriptLang)?;
+        let function = tree.root_node().named_child(0).unwrap();
+        let return_type = TypeScriptLang.return_type_node(function).unwrap();
+        assert_eq!(return_type.utf8_text(source.as_bytes())?, ": number");
+
+        let function = tree.root_node().named_child(1).unwrap();
+        assert!(TypeScriptLang.return_type_node(function).is_none());
+        assert!(TypeScriptLang.return_type_node(tree.root_node()).is_none());
+
+        Ok(())
+    }
+}
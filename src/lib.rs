@@ -1,4 +1,5 @@
 // Module declarations
+pub mod call_graph;
 pub mod call_node;
 pub mod call_with_target;
 pub mod cli;
@@ -9,11 +10,22 @@ pub mod languages;
 pub mod location;
 pub mod lsp;
 pub mod parser;
+pub mod project_config;
+pub mod protocol_log;
+pub mod report;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+pub mod text_edit;
+pub mod timing;
+pub mod uri;
 
 // Re-export main types
-pub use cli::Args;
+pub use cli::{Args, OutputFormat, Reporter, init_tracing};
 pub use file_search::FileSearchConfig;
-pub use integration::{find_all_call_targets, goto_definition_for_node};
-pub use language::Language;
-pub use languages::{GoLang, PythonLang, RustLang, SwiftLang, TypeScriptLang};
-pub use lsp::{LspServer, LspServerConfig};
+pub use integration::{
+    ErrorPolicy, code_actions_for_call, find_all_call_targets, find_all_callers,
+    find_callers_in_file, goto_declaration_for_node, goto_definition_for_node, stream_call_targets,
+};
+pub use language::{ErrorRecoveryStrategy, Language, LanguageCounter};
+pub use languages::{GoLang, PhpLang, PythonLang, RustLang, SwiftLang, TypeScriptLang};
+pub use lsp::{ClientCapabilitiesBuilder, LspServer, LspServerConfig, RetryPolicy};
@@ -1,28 +1,365 @@
-use lsp_types::Range;
-
-pub fn print_highlighted_range(file_lines: &[&str], range: Range) {
-    let start_line = range.start.line as usize;
-    let start_character = range.start.character as usize;
-    let end_line = range.end.line as usize;
-    let end_character = range.end.character as usize;
-
-    if start_line < file_lines.len() {
-        let line = file_lines[start_line];
-        let line_len = line.len();
-        println!("    {}", line.trim());
-
-        let leading_spaces = line.chars().take_while(|c| c.is_whitespace()).count();
-        let underline_width = if end_line == start_line {
-            (end_character - start_character).max(1)
+//! Helpers for converting and displaying source locations.
+//!
+//! Conventions used throughout the crate:
+//! - Tree-sitter points are 0-based rows and 0-based byte columns.
+//! - LSP positions are 0-based lines and 0-based characters counted in the
+//!   negotiated position encoding (UTF-16 unless the server says otherwise).
+//! - Locations shown to users are 1-based lines and 1-based columns.
+//!
+//! Convert between them with the helpers in this module rather than with ad-hoc
+//! `+ 1`s.
+
+use lsp_types::{Location, Position, PositionEncodingKind, Range, Uri};
+use std::collections::HashMap;
+use std::fmt::Display;
+use tree_sitter::Point;
+
+use crate::uri::from_file_uri;
+
+/// A 1-based line and column, as shown to users
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DisplayPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for DisplayPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Converts a tree-sitter point to an LSP position in the given encoding
+///
+/// Tree-sitter columns are byte offsets, so they can be used as is for UTF-8.
+/// For other encodings the column is recounted from the source line.
+pub fn ts_point_to_lsp_position(
+    point: Point,
+    source: &str,
+    encoding: &PositionEncodingKind,
+) -> Position {
+    let character = if *encoding == PositionEncodingKind::UTF8 {
+        point.column
+    } else {
+        let line = source.lines().nth(point.row).unwrap_or("");
+        let prefix = line.get(..point.column).unwrap_or(line);
+        if *encoding == PositionEncodingKind::UTF32 {
+            prefix.chars().count()
         } else {
-            line_len - start_character
-        };
-        let mut call_underline = String::new();
-        call_underline.push_str(
-            " ".repeat(start_character.saturating_sub(leading_spaces))
-                .as_str(),
+            prefix.encode_utf16().count()
+        }
+    };
+    Position {
+        line: point.row as u32,
+        character: character as u32,
+    }
+}
+
+/// Converts an LSP position in the given encoding to a tree-sitter point
+///
+/// This is the inverse of `ts_point_to_lsp_position`. Characters past the end
+/// of the line are clamped to the line length.
+pub fn lsp_position_to_ts_point(
+    position: Position,
+    source: &str,
+    encoding: &PositionEncodingKind,
+) -> Point {
+    let row = position.line as usize;
+    let line = source.lines().nth(row).unwrap_or("");
+    let character = position.character as usize;
+    let column = if *encoding == PositionEncodingKind::UTF8 {
+        character.min(line.len())
+    } else {
+        let mut units = 0;
+        line.char_indices()
+            .find(|(_, c)| {
+                if units >= character {
+                    return true;
+                }
+                units += if *encoding == PositionEncodingKind::UTF32 {
+                    1
+                } else {
+                    c.len_utf16()
+                };
+                false
+            })
+            .map_or(line.len(), |(index, _)| index)
+    };
+    Point::new(row, column)
+}
+
+/// Returns the byte offset of a tree-sitter point in `source`
+///
+/// Offsets past the end of the source are clamped to its length.
+pub fn ts_point_to_byte_offset(source: &str, point: Point) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(point.row)
+        .map(str::len)
+        .sum();
+    (line_start + point.column).min(source.len())
+}
+
+/// Converts a tree-sitter range to an LSP range in the given encoding
+pub fn byte_range_to_lsp_range(
+    source: &str,
+    range: tree_sitter::Range,
+    encoding: &PositionEncodingKind,
+) -> Range {
+    Range {
+        start: ts_point_to_lsp_position(range.start_point, source, encoding),
+        end: ts_point_to_lsp_position(range.end_point, source, encoding),
+    }
+}
+
+/// Converts an LSP position in the given encoding to a byte offset into `source`
+///
+/// This is the inverse of `byte_range_to_lsp_range` for a single position and
+/// is what is needed to apply a `TextEdit` to a string.
+pub fn lsp_range_to_byte_offset(
+    source: &str,
+    position: Position,
+    encoding: &PositionEncodingKind,
+) -> usize {
+    ts_point_to_byte_offset(source, lsp_position_to_ts_point(position, source, encoding))
+}
+
+/// Converts a 0-based tree-sitter point to a 1-based display position
+pub fn ts_point_to_display(point: Point) -> DisplayPosition {
+    DisplayPosition {
+        line: point.row + 1,
+        column: point.column + 1,
+    }
+}
+
+/// Converts a 0-based LSP position to a 1-based display position
+pub fn lsp_to_display(position: Position) -> DisplayPosition {
+    DisplayPosition {
+        line: position.line as usize + 1,
+        column: position.character as usize + 1,
+    }
+}
+
+pub fn print_highlighted_range(
+    file_lines: &[impl AsRef<str>],
+    range: Range,
+    encoding: &PositionEncodingKind,
+) {
+    if let Some([line, underline]) = highlight_range(file_lines, range, encoding) {
+        println!("    {}", line);
+        print!("    {}", underline);
+    }
+}
+
+/// Returns the trimmed source line where `range` starts and a `^` underline of the range
+///
+/// The characters of `range` are counted in `encoding` and the underline is
+/// aligned by Unicode scalar values. A range spanning several lines is
+/// underlined to the end of its first line. Returns None if the range starts
+/// past the end of `file_lines`.
+pub fn highlight_range(
+    file_lines: &[impl AsRef<str>],
+    range: Range,
+    encoding: &PositionEncodingKind,
+) -> Option<[String; 2]> {
+    let line = file_lines.get(range.start.line as usize)?.as_ref();
+    // The column of a position on this line, in chars
+    let column = |character: u32| {
+        let point = lsp_position_to_ts_point(Position::new(0, character), line, encoding);
+        line[..point.column].chars().count()
+    };
+    let start_character = column(range.start.character);
+    let leading_spaces = line.chars().take_while(|c| c.is_whitespace()).count();
+    let underline_width = if range.end.line == range.start.line {
+        column(range.end.character)
+            .saturating_sub(start_character)
+            .max(1)
+    } else {
+        line.chars().count().saturating_sub(start_character)
+    };
+    let underline = format!(
+        "{}{}",
+        " ".repeat(start_character.saturating_sub(leading_spaces)),
+        "^".repeat(underline_width)
+    );
+    Some([line.trim().to_string(), underline])
+}
+
+/// The lines of files read on demand, kept per URI
+///
+/// Used to show the source lines of many locations, like the references to a
+/// symbol, while reading and splitting each file once.
+#[derive(Debug, Default)]
+pub struct SourceCache {
+    /// None for files that could not be read
+    files: HashMap<Uri, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    /// Returns the lines of the file at `uri`, or None if it can't be read
+    pub fn lines(&mut self, uri: &Uri) -> Option<&[String]> {
+        self.files
+            .entry(uri.clone())
+            .or_insert_with(|| {
+                let path = from_file_uri(uri).ok()?;
+                let content = std::fs::read_to_string(path).ok()?;
+                Some(content.lines().map(str::to_string).collect())
+            })
+            .as_deref()
+    }
+
+    /// Returns the highlighted source line of `location`, see `highlight_range`
+    pub fn context(
+        &mut self,
+        location: &Location,
+        encoding: &PositionEncodingKind,
+    ) -> Option<[String; 2]> {
+        highlight_range(self.lines(&location.uri)?, location.range, encoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ts_point_to_lsp_position_utf8_is_noop() {
+        let source = "let s = \"ÿ\"; foo();";
+        let point = Point::new(0, 16);
+
+        let position = ts_point_to_lsp_position(point, source, &PositionEncodingKind::UTF8);
+
+        assert_eq!(position.line, 0);
+        assert_eq!(position.character, 16);
+    }
+
+    #[test]
+    fn test_ts_point_to_lsp_position_utf16() {
+        // "ÿ" is two bytes in UTF-8 but a single UTF-16 code unit
+        let source = "fn main() {}\nlet s = \"ÿ\"; foo();";
+        let point = Point::new(1, 16);
+
+        let position = ts_point_to_lsp_position(point, source, &PositionEncodingKind::UTF16);
+
+        assert_eq!(position.line, 1);
+        assert_eq!(position.character, 15);
+    }
+
+    #[test]
+    fn test_lsp_position_to_ts_point_round_trip() {
+        let source = "fn main() {}\nlet s = \"ÿ\"; foo();";
+        for encoding in [
+            PositionEncodingKind::UTF8,
+            PositionEncodingKind::UTF16,
+            PositionEncodingKind::UTF32,
+        ] {
+            let point = Point::new(1, 16);
+            let position = ts_point_to_lsp_position(point, source, &encoding);
+            assert_eq!(lsp_position_to_ts_point(position, source, &encoding), point);
+        }
+    }
+
+    #[test]
+    fn test_byte_range_to_lsp_range_round_trip() -> anyhow::Result<()> {
+        let source = "fn main() {\n    let s = \"ÿé\"; foo(s,\n        1);\n}\n";
+        let tree = crate::parser::parse_file_content(source, crate::RustLang)?;
+        let call = crate::parser::get_calls(&tree, crate::RustLang)
+            .next()
+            .unwrap();
+        let range = call.call_node.range();
+
+        let lsp_range = byte_range_to_lsp_range(source, range, &PositionEncodingKind::UTF16);
+        assert_eq!(lsp_range.start, Position::new(1, 18));
+        assert_eq!(lsp_range.end, Position::new(2, 10));
+
+        for encoding in [
+            PositionEncodingKind::UTF8,
+            PositionEncodingKind::UTF16,
+            PositionEncodingKind::UTF32,
+        ] {
+            let lsp_range = byte_range_to_lsp_range(source, range, &encoding);
+            assert_eq!(
+                lsp_range_to_byte_offset(source, lsp_range.start, &encoding),
+                range.start_byte
+            );
+            assert_eq!(
+                lsp_range_to_byte_offset(source, lsp_range.end, &encoding),
+                range.end_byte
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_positions_are_one_based() {
+        let display = ts_point_to_display(Point::new(0, 0));
+        assert_eq!(display, DisplayPosition { line: 1, column: 1 });
+        assert_eq!(display.to_string(), "1:1");
+
+        let display = lsp_to_display(Position::new(9, 4));
+        assert_eq!(
+            display,
+            DisplayPosition {
+                line: 10,
+                column: 5
+            }
         );
-        call_underline.push_str("^".repeat(underline_width).as_str());
-        print!("    {}", call_underline);
+        assert_eq!(display.to_string(), "10:5");
+    }
+
+    #[test]
+    fn test_highlight_range_counts_characters_in_encoding() {
+        let lines = ["    let s = \"ÿ😀\"; foo(s);", "}"];
+        let expected = [
+            "let s = \"ÿ😀\"; foo(s);".to_string(),
+            format!("{}^^^^^^", " ".repeat(14)),
+        ];
+        // The emoji is two UTF-16 code units and "ÿ😀" six bytes
+        let utf16 = Range::new(Position::new(0, 19), Position::new(0, 25));
+        assert_eq!(
+            highlight_range(&lines, utf16, &PositionEncodingKind::UTF16),
+            Some(expected.clone())
+        );
+        let utf8 = Range::new(Position::new(0, 22), Position::new(0, 28));
+        assert_eq!(
+            highlight_range(&lines, utf8, &PositionEncodingKind::UTF8),
+            Some(expected)
+        );
+
+        // A range spanning lines is underlined to the end of the first line
+        let multiline = Range::new(Position::new(0, 19), Position::new(1, 1));
+        let [_, underline] =
+            highlight_range(&lines, multiline, &PositionEncodingKind::UTF16).unwrap();
+        assert_eq!(underline, format!("{}^^^^^^^", " ".repeat(14)));
+    }
+
+    #[test]
+    fn test_source_cache_context() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let file_path = temp_dir.path().join("main.rs");
+        std::fs::write(&file_path, "fn main() {\n    helper(1);\n}\n")?;
+        let location = Location {
+            uri: crate::uri::to_file_uri(&file_path)?,
+            range: Range::new(Position::new(1, 4), Position::new(1, 10)),
+        };
+
+        let utf16 = PositionEncodingKind::UTF16;
+        let mut cache = SourceCache::default();
+        let [line, underline] = cache.context(&location, &utf16).unwrap();
+        assert_eq!(line, "helper(1);");
+        assert_eq!(underline, "^^^^^^");
+
+        // The contents are cached, so a later change of the file is not seen
+        std::fs::write(&file_path, "")?;
+        assert_eq!(cache.context(&location, &utf16).unwrap()[0], "helper(1);");
+
+        let missing = Location {
+            uri: crate::uri::to_file_uri(&temp_dir.path().join("missing.rs"))?,
+            ..location
+        };
+        assert!(cache.context(&missing, &utf16).is_none());
+
+        Ok(())
     }
 }
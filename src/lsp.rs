@@ -3,44 +3,211 @@
 
 use anyhow::Result;
 use lsp_types::notification::{
-    DidCloseTextDocument, DidOpenTextDocument, Initialized, Notification,
+    DidChangeConfiguration, DidChangeTextDocument, DidChangeWorkspaceFolders, DidCloseTextDocument,
+    DidOpenTextDocument, DidSaveTextDocument, Initialized, Notification, WillSaveTextDocument,
+};
+use lsp_types::request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    CodeActionRequest, DocumentSymbolRequest, GotoDeclaration, GotoDeclarationParams, Initialize,
+    InlayHintRefreshRequest, InlayHintResolveRequest, MonikerRequest, Request,
+    TypeHierarchyPrepare, TypeHierarchySubtypes, TypeHierarchySupertypes, WillSaveWaitUntil,
 };
-use lsp_types::request::{DocumentSymbolRequest, Initialize, Request};
 use lsp_types::{
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams,
-    InitializeParams, InitializedParams, Position, TextDocumentIdentifier, TextDocumentItem,
-    TextDocumentPositionParams, Uri, WorkspaceFolder,
+    CallHierarchyClientCapabilities, CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams,
+    CallHierarchyItem, CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams,
+    CallHierarchyPrepareParams, ClientCapabilities, CodeActionClientCapabilities,
+    CodeActionContext, CodeActionKind, CodeActionKindLiteralSupport, CodeActionLiteralSupport,
+    CodeActionOrCommand, CodeActionParams, DiagnosticClientCapabilities,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWorkspaceFoldersParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentChangeOperation, DocumentChanges, DocumentSymbol, DocumentSymbolClientCapabilities,
+    DocumentSymbolParams, GeneralClientCapabilities, GotoDefinitionResponse, InitializeParams,
+    InitializedParams, InlayHint, InlayHintClientCapabilities, InlayHintResolveClientCapabilities,
+    Location, LogMessageParams, MessageType, Moniker, MonikerClientCapabilities, MonikerParams,
+    OneOf, Position, PositionEncodingKind, SemanticTokenModifier, SemanticTokenType,
+    SemanticTokensClientCapabilities, SemanticTokensClientCapabilitiesRequests,
+    SemanticTokensFullOptions, SymbolInformation, SymbolKind, TextDocumentClientCapabilities,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, TextDocumentSaveReason, TextDocumentSyncClientCapabilities,
+    TextEdit, TokenFormat, TypeHierarchyClientCapabilities, TypeHierarchyItem,
+    TypeHierarchyPrepareParams, TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, Uri,
+    VersionedTextDocumentIdentifier, WillSaveTextDocumentParams, WindowClientCapabilities,
+    WorkDoneProgress, WorkspaceClientCapabilities, WorkspaceEdit, WorkspaceFolder,
+    WorkspaceFoldersChangeEvent,
 };
-use serde_json::{from_value, to_value};
-use std::io::{BufRead, BufReader, Write};
+use serde_json::{Value, from_value, to_value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel, sync_channel};
 use std::time::Duration;
 use tracing::warn;
 
+use crate::call_graph::CallGraph;
 use crate::language::Language;
+use crate::protocol_log::{LoggingReader, LoggingWriter, ProtocolLog};
+use crate::text_edit::apply_text_edits;
+use crate::uri::{from_file_uri, to_file_uri};
 
 /// Configuration for LSP server startup
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct LspServerConfig {
     /// Additional arguments to pass to the LSP server
     pub args: Vec<String>,
     /// Environment variables to set for the LSP server
     pub env_vars: Vec<(String, String)>,
+    /// Send `textDocument/didSave` after every `update_file`
+    pub auto_save_on_update: bool,
+    /// The least severe `window/logMessage` and `window/showMessage` type that is logged
+    pub min_message_type: MessageType,
+    /// Command to run instead of the language's default LSP server
+    pub lsp_command_override: Option<String>,
+    /// Client capabilities to send instead of those from `client_capabilities`
+    pub client_capabilities: Option<ClientCapabilities>,
+    /// How often the request helpers retry transient errors, see `request_retrying`
+    pub transient_error_retries: u32,
+    /// The delay before the first retry of a transient error, doubled for each retry
+    pub transient_error_delay: Duration,
+    /// Do not start a server, analyses that support it fall back to tree-sitter only
+    pub disabled: bool,
+    /// Append every JSON-RPC message exchanged with the server to this file
+    pub protocol_log_path: Option<PathBuf>,
+    /// The capacity in bytes of the buffer used to read the server's stdout
+    ///
+    /// A larger buffer needs fewer reads for big responses, such as the
+    /// semantic tokens of a large file or long lists of references.
+    pub stdout_buffer_capacity: usize,
+}
+
+impl Default for LspServerConfig {
+    fn default() -> Self {
+        Self {
+            args: Vec::new(),
+            env_vars: Vec::new(),
+            auto_save_on_update: false,
+            min_message_type: MessageType::WARNING,
+            lsp_command_override: None,
+            client_capabilities: None,
+            transient_error_retries: 3,
+            transient_error_delay: Duration::from_millis(50),
+            disabled: false,
+            protocol_log_path: None,
+            stdout_buffer_capacity: 64 * 1024,
+        }
+    }
+}
+
+/// An error response sent by the LSP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl LspResponseError {
+    /// The server does not implement the method
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// The document changed while the server was processing the request
+    pub const CONTENT_MODIFIED: i64 = -32801;
+    /// The client cancelled the request, or the server cancelled it to serve a newer one
+    pub const REQUEST_CANCELLED: i64 = -32800;
+    /// The server cancelled the request, for instance because it is not ready yet
+    pub const SERVER_CANCELLED: i64 = -32802;
+
+    /// Returns true if the request may succeed when retried
+    pub fn is_transient(&self) -> bool {
+        matches!(self.code, Self::CONTENT_MODIFIED | Self::SERVER_CANCELLED)
+    }
+}
+
+impl std::fmt::Display for LspResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LSP error (code {}): {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for LspResponseError {}
+
+/// The LSP server command of a language could not be run, e.g. because it is
+/// not installed or because LSP features are disabled in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspServerUnavailable {
+    pub command: String,
+    pub language: String,
+    pub disabled: bool,
+}
+
+impl std::fmt::Display for LspServerUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.disabled {
+            write!(
+                f,
+                "LSP features are disabled, not starting the {} LSP server `{}`.",
+                self.language, self.command
+            )
+        } else {
+            write!(
+                f,
+                "LSP server for {} is not available. Please make sure that `{}` is installed.",
+                self.language, self.command
+            )
+        }
+    }
+}
+
+impl std::error::Error for LspServerUnavailable {}
+
+/// How often a request that returns no result is retried
+///
+/// Servers that are still loading the workspace often answer with an empty
+/// result rather than an error. Retry `n` waits `n * delay` before it is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Send the request once without retrying
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        delay: Duration::ZERO,
+    };
+
+    pub fn new(max_retries: u32, delay: Duration) -> RetryPolicy {
+        RetryPolicy { max_retries, delay }
+    }
 }
 
 /// A running LSP server process
 pub struct LspServer<L: Language> {
-    pub process: Child,
+    /// The server process, `None` for servers connected through `from_streams`
+    pub process: Option<Child>,
     pub language: L,
     pub working_dir: PathBuf,
-    pub stdin: ChildStdin,
+    pub stdin: Box<dyn Write + Send>,
+    /// The position encoding negotiated with the server during initialization
+    pub position_encoding: PositionEncodingKind,
+    /// The configuration the server was started with
+    pub config: LspServerConfig,
+    /// The settings set by `update_configuration`, the client side of `workspace/configuration`
+    pub config_values: Value,
+    /// URIs of the documents that are currently open in the server and their versions
+    open_documents: HashMap<Uri, i32>,
+    /// Set by `stop` so that stopping again, e.g. on drop, does nothing
+    stopped: bool,
     response_rx: Receiver<Result<serde_json::Value>>,
+    /// Messages injected by `inject_notification_for_test`, read before those from the server
+    #[cfg(any(test, feature = "test-util"))]
+    injected_messages: std::collections::VecDeque<serde_json::Value>,
     next_id: u64,
 }
 
-fn request_string<T: serde::Serialize>(request: &T) -> Result<String> {
+pub(crate) fn request_string<T: serde::Serialize>(request: &T) -> Result<String> {
     let request_str = serde_json::to_string(&request)?;
     Ok(format!(
         "Content-Length: {}\r\n\r\n{}",
@@ -49,6 +216,228 @@ fn request_string<T: serde::Serialize>(request: &T) -> Result<String> {
     ))
 }
 
+/// Returns the JSON-RPC message of a notification
+fn notification_message<N: Notification>(params: N::Params) -> Result<Value> {
+    Ok(serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": N::METHOD,
+        "params": to_value(&params)?
+    }))
+}
+
+/// Reads a single `Content-Length` framed message body
+///
+/// Returns `None` when the stream has reached EOF.
+pub(crate) fn read_message(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    // Read headers
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            // EOF reached
+            Ok(0) => return Ok(None),
+            Ok(_) => {
+                if header == "\r\n" {
+                    break;
+                }
+                if let Some(length_str) = header.strip_prefix("Content-Length: ") {
+                    content_length = length_str.trim().parse().unwrap_or(0);
+                }
+            }
+            Err(e) => anyhow::bail!("Failed to read header: {}", e),
+        }
+    }
+
+    // Read content
+    let mut buffer = vec![0; content_length];
+    reader
+        .read_exact(&mut buffer)
+        .map_err(|e| anyhow::anyhow!("Failed to read content: {}", e))?;
+    Ok(Some(buffer))
+}
+
+/// Returns the `result` of a JSON-RPC response, or its `error` as an `LspResponseError`
+fn response_result(response: Value) -> Result<Value> {
+    if let Some(error) = response.get("error") {
+        let error_message = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("Unknown error");
+        let error_code = error.get("code").and_then(|c| c.as_i64()).unwrap_or(-1);
+        return Err(LspResponseError {
+            code: error_code,
+            message: error_message.to_string(),
+        }
+        .into());
+    }
+
+    // Extract the result field from the JSON-RPC response
+    let result = response
+        .get("result")
+        .ok_or_else(|| anyhow::anyhow!("Missing result field in response"))?;
+    Ok(result.clone())
+}
+
+/// Returns the severity of a message type, lower is more severe
+fn message_severity(message_type: MessageType) -> u8 {
+    match message_type {
+        MessageType::ERROR => 1,
+        MessageType::WARNING => 2,
+        MessageType::INFO => 3,
+        _ => 4,
+    }
+}
+
+/// Returns true if a message of `message_type` passes the `min_message_type` filter
+pub fn is_message_type_shown(message_type: MessageType, min_message_type: MessageType) -> bool {
+    message_severity(message_type) <= message_severity(min_message_type)
+}
+
+/// Logs `window/logMessage` and `window/showMessage` notifications that pass the filter
+///
+/// Returns false if the message is not one of those notifications.
+fn log_window_message(message: &serde_json::Value, min_message_type: MessageType) -> bool {
+    let method = message.get("method").and_then(|m| m.as_str());
+    if !matches!(method, Some("window/logMessage" | "window/showMessage")) {
+        return false;
+    }
+    let Some(params) = message
+        .get("params")
+        .and_then(|params| from_value::<LogMessageParams>(params.clone()).ok())
+    else {
+        return false;
+    };
+    if !is_message_type_shown(params.typ, min_message_type) {
+        return true;
+    }
+    match params.typ {
+        MessageType::ERROR => tracing::error!("[server] {}", params.message),
+        MessageType::WARNING => tracing::warn!("[server] {}", params.message),
+        MessageType::INFO => tracing::info!("[server] {}", params.message),
+        _ => tracing::debug!("[server] {}", params.message),
+    }
+    true
+}
+
+/// Spawns a thread that reads messages from the server and forwards them to a channel
+fn spawn_reader(
+    stdout: impl Read + Send + 'static,
+    min_message_type: MessageType,
+    buffer_capacity: usize,
+) -> Receiver<Result<serde_json::Value>> {
+    let (response_tx, response_rx) = channel();
+
+    std::thread::spawn(move || {
+        let mut stdout = BufReader::with_capacity(buffer_capacity, stdout);
+        loop {
+            let buffer = match read_message(&mut stdout) {
+                Ok(Some(buffer)) => buffer,
+                Ok(None) => return,
+                Err(e) => {
+                    let _ = response_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let response_str = match String::from_utf8(buffer) {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ =
+                        response_tx.send(Err(anyhow::anyhow!("Invalid UTF-8 in response: {}", e)));
+                    continue;
+                }
+            };
+
+            match serde_json::from_str(&response_str) {
+                Ok(response) => {
+                    // A JSON-RPC batch is passed on as its individual messages
+                    let messages = match response {
+                        Value::Array(messages) => messages,
+                        message => vec![message],
+                    };
+                    for message in messages {
+                        if !log_window_message(&message, min_message_type) {
+                            tracing::debug!("Received message: {}", message);
+                        }
+                        if response_tx.send(Ok(message)).is_err() {
+                            // Receiver has been dropped
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = response_tx.send(Err(anyhow::anyhow!("Failed to parse JSON: {}", e)));
+                }
+            }
+        }
+    });
+
+    response_rx
+}
+
+/// Expands `$VAR` and `${VAR}` using the environment of the current process
+///
+/// Unset variables expand to the empty string and `$$` is a literal `$`.
+pub fn expand_env_vars(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        let (name, remainder) = if let Some(braced) = rest.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+        {
+            (&braced[..end], &braced[end + 1..])
+        } else if let Some(remainder) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = remainder;
+            continue;
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+
+        if name.is_empty() {
+            // Not a variable reference, keep the dollar sign as is
+            expanded.push('$');
+        } else {
+            expanded.push_str(&std::env::var(name).unwrap_or_default());
+            rest = remainder;
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Returns the program to run for an LSP server command
+///
+/// On Windows, servers installed with npm are `.cmd` scripts that are only
+/// found on the PATH with their extension, so it is looked up explicitly.
+fn resolve_server_command(command: &str) -> String {
+    #[cfg(windows)]
+    if Path::new(command).extension().is_none()
+        && let Some(path_var) = std::env::var_os("PATH")
+        && let Some(program) = find_in_path(command, &path_var, &["exe", "cmd", "bat"])
+    {
+        return program.display().to_string();
+    }
+    command.to_string()
+}
+
+/// Returns the first file named `command` with one of `extensions` in the directories of `path_var`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn find_in_path(command: &str, path_var: &std::ffi::OsStr, extensions: &[&str]) -> Option<PathBuf> {
+    std::env::split_paths(path_var).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|extension| dir.join(command).with_extension(extension))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
 /// Checks if the required LSP server is available for the given language
 fn is_server_command_available(command: &str) -> bool {
     // Try to execute the command with --version or --help to check availability
@@ -56,16 +445,124 @@ fn is_server_command_available(command: &str) -> bool {
         || Command::new(command).arg("--help").output().is_ok()
 }
 
-pub fn uri_from_path(path: &std::path::Path) -> Result<Uri> {
-    Ok(format!("file://{}", path.display()).parse()?)
-}
-
 pub fn text_document_identifier_from_path(file_path: &Path) -> Result<TextDocumentIdentifier> {
     Ok(TextDocumentIdentifier {
-        uri: uri_from_path(file_path)?,
+        uri: to_file_uri(file_path)?,
     })
 }
 
+/// Flattens the variants of a goto definition/declaration response into locations
+///
+/// For `LocationLink`s the target selection range (typically the identifier) is used.
+pub fn response_locations(response: GotoDefinitionResponse) -> Vec<Location> {
+    match response {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    }
+}
+
+/// The symbol kinds that `callable_symbols` collects by default
+pub const DEFAULT_CALLABLE_KINDS: &[SymbolKind] = &[
+    SymbolKind::FUNCTION,
+    SymbolKind::METHOD,
+    SymbolKind::CONSTRUCTOR,
+];
+
+/// Which document symbols `callable_symbols` collects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallableSymbolFilter {
+    /// The kinds of symbols to collect, add kinds such as `PROPERTY` to also
+    /// analyze property accessors
+    pub kinds: Vec<SymbolKind>,
+    /// Do not look for callables nested in interfaces
+    ///
+    /// Interfaces mostly declare methods without bodies, but default methods,
+    /// like those of Java, Kotlin or PHP traits, do contain calls.
+    pub skip_interfaces: bool,
+}
+
+impl Default for CallableSymbolFilter {
+    fn default() -> Self {
+        Self {
+            kinds: DEFAULT_CALLABLE_KINDS.to_vec(),
+            skip_interfaces: false,
+        }
+    }
+}
+
+/// Returns the symbols matching `filter` among `symbols` and all their nested
+/// symbols as a flat list
+///
+/// The returned symbols have their children removed, callables nested in them
+/// follow them in the list.
+pub fn callable_symbols(
+    symbols: Vec<DocumentSymbol>,
+    filter: &CallableSymbolFilter,
+) -> Vec<DocumentSymbol> {
+    fn collect(
+        symbols: Vec<DocumentSymbol>,
+        filter: &CallableSymbolFilter,
+        result: &mut Vec<DocumentSymbol>,
+    ) {
+        for mut symbol in symbols {
+            let children = symbol.children.take();
+            let skip_children = filter.skip_interfaces && symbol.kind == SymbolKind::INTERFACE;
+            if filter.kinds.contains(&symbol.kind) {
+                result.push(symbol);
+            }
+            if let Some(children) = children
+                && !skip_children
+            {
+                collect(children, filter, result);
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    collect(symbols, filter, &mut result);
+    result
+}
+
+/// Converts a flat `SymbolInformation` into a `DocumentSymbol` without children
+///
+/// `SymbolInformation` only carries the full range of the symbol, so the
+/// selection range (the identifier) is estimated as the symbol name placed at
+/// the start of that range, clamped so it never extends past the full range.
+pub fn document_symbol_from_information(symbol: SymbolInformation) -> DocumentSymbol {
+    let range = symbol.location.range;
+    let name_end = Position {
+        line: range.start.line,
+        character: range.start.character + symbol.name.len() as u32,
+    };
+    let selection_end = if range.start.line == range.end.line {
+        name_end.min(range.end)
+    } else {
+        name_end
+    };
+
+    DocumentSymbol {
+        name: symbol.name,
+        detail: None,
+        kind: symbol.kind,
+        tags: symbol.tags,
+        #[allow(deprecated)]
+        deprecated: symbol.deprecated,
+        range,
+        selection_range: lsp_types::Range {
+            start: range.start,
+            end: selection_end,
+        },
+        children: None,
+    }
+}
+
 pub fn text_document_position_params(
     path: &Path,
     position: Position,
@@ -76,10 +573,209 @@ pub fn text_document_position_params(
     })
 }
 
+/// Returns the client capabilities sent in the `initialize` request
+///
+/// Servers only enable some features (e.g. hierarchical document symbols or
+/// inlay hints) when the client advertises support for them, so this declares
+/// every feature the crate makes use of.
+pub fn client_capabilities() -> ClientCapabilities {
+    ClientCapabilities {
+        text_document: Some(TextDocumentClientCapabilities {
+            call_hierarchy: Some(CallHierarchyClientCapabilities {
+                dynamic_registration: Some(false),
+            }),
+            document_symbol: Some(DocumentSymbolClientCapabilities {
+                dynamic_registration: Some(false),
+                hierarchical_document_symbol_support: Some(true),
+                ..Default::default()
+            }),
+            inlay_hint: Some(InlayHintClientCapabilities {
+                dynamic_registration: Some(false),
+                resolve_support: Some(InlayHintResolveClientCapabilities {
+                    properties: vec!["tooltip".to_string(), "textEdits".to_string()],
+                }),
+            }),
+            type_hierarchy: Some(TypeHierarchyClientCapabilities {
+                dynamic_registration: Some(false),
+            }),
+            moniker: Some(MonikerClientCapabilities {
+                dynamic_registration: Some(false),
+            }),
+            // Without literal support servers only reply with bare commands
+            code_action: Some(CodeActionClientCapabilities {
+                dynamic_registration: Some(false),
+                code_action_literal_support: Some(CodeActionLiteralSupport {
+                    code_action_kind: CodeActionKindLiteralSupport {
+                        value_set: [
+                            CodeActionKind::EMPTY,
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_INLINE,
+                            CodeActionKind::REFACTOR_REWRITE,
+                            CodeActionKind::SOURCE,
+                        ]
+                        .iter()
+                        .map(|kind| kind.as_str().to_string())
+                        .collect(),
+                    },
+                }),
+                ..Default::default()
+            }),
+            synchronization: Some(TextDocumentSyncClientCapabilities {
+                dynamic_registration: Some(false),
+                will_save: Some(true),
+                will_save_wait_until: Some(true),
+                did_save: Some(true),
+            }),
+            ..Default::default()
+        }),
+        workspace: Some(WorkspaceClientCapabilities {
+            workspace_folders: Some(true),
+            // Answered from `LspServer::config_values`
+            configuration: Some(true),
+            ..Default::default()
+        }),
+        window: Some(WindowClientCapabilities {
+            // Lets `wait_until_ready` know when the server is done indexing
+            work_done_progress: Some(true),
+            ..Default::default()
+        }),
+        general: Some(GeneralClientCapabilities {
+            // Tree-sitter columns are byte offsets, so UTF-8 avoids any conversion
+            position_encodings: Some(vec![
+                PositionEncodingKind::UTF8,
+                PositionEncodingKind::UTF16,
+            ]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Builds client capabilities for servers that only enable features on request
+///
+/// Set the result as `LspServerConfig::client_capabilities` to send it in the
+/// `initialize` request.
+#[derive(Debug, Clone)]
+pub struct ClientCapabilitiesBuilder {
+    capabilities: ClientCapabilities,
+}
+
+impl Default for ClientCapabilitiesBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientCapabilitiesBuilder {
+    /// Starts from the capabilities of the features the crate uses, see `client_capabilities`
+    pub fn new() -> Self {
+        ClientCapabilitiesBuilder {
+            capabilities: client_capabilities(),
+        }
+    }
+
+    /// Starts from no capabilities at all
+    pub fn empty() -> Self {
+        ClientCapabilitiesBuilder {
+            capabilities: ClientCapabilities::default(),
+        }
+    }
+
+    fn text_document(&mut self) -> &mut TextDocumentClientCapabilities {
+        self.capabilities
+            .text_document
+            .get_or_insert_with(Default::default)
+    }
+
+    /// Advertises `textDocument/semanticTokens` for whole documents and ranges
+    /// with all the standard token types and modifiers
+    pub fn with_semantic_tokens(mut self) -> Self {
+        self.text_document().semantic_tokens = Some(SemanticTokensClientCapabilities {
+            dynamic_registration: Some(false),
+            requests: SemanticTokensClientCapabilitiesRequests {
+                range: Some(true),
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+            },
+            token_types: vec![
+                SemanticTokenType::NAMESPACE,
+                SemanticTokenType::TYPE,
+                SemanticTokenType::CLASS,
+                SemanticTokenType::ENUM,
+                SemanticTokenType::INTERFACE,
+                SemanticTokenType::STRUCT,
+                SemanticTokenType::TYPE_PARAMETER,
+                SemanticTokenType::PARAMETER,
+                SemanticTokenType::VARIABLE,
+                SemanticTokenType::PROPERTY,
+                SemanticTokenType::ENUM_MEMBER,
+                SemanticTokenType::EVENT,
+                SemanticTokenType::FUNCTION,
+                SemanticTokenType::METHOD,
+                SemanticTokenType::MACRO,
+                SemanticTokenType::KEYWORD,
+                SemanticTokenType::MODIFIER,
+                SemanticTokenType::COMMENT,
+                SemanticTokenType::STRING,
+                SemanticTokenType::NUMBER,
+                SemanticTokenType::REGEXP,
+                SemanticTokenType::OPERATOR,
+                SemanticTokenType::DECORATOR,
+            ],
+            token_modifiers: vec![
+                SemanticTokenModifier::DECLARATION,
+                SemanticTokenModifier::DEFINITION,
+                SemanticTokenModifier::READONLY,
+                SemanticTokenModifier::STATIC,
+                SemanticTokenModifier::DEPRECATED,
+                SemanticTokenModifier::ABSTRACT,
+                SemanticTokenModifier::ASYNC,
+                SemanticTokenModifier::MODIFICATION,
+                SemanticTokenModifier::DOCUMENTATION,
+                SemanticTokenModifier::DEFAULT_LIBRARY,
+            ],
+            formats: vec![TokenFormat::RELATIVE],
+            overlapping_token_support: Some(false),
+            multiline_token_support: Some(false),
+            server_cancel_support: Some(false),
+            augments_syntax_tokens: Some(true),
+        });
+        self
+    }
+
+    /// Advertises `textDocument/diagnostic`, diagnostics requested by the client
+    pub fn with_pull_diagnostics(mut self) -> Self {
+        self.text_document().diagnostic = Some(DiagnosticClientCapabilities {
+            dynamic_registration: Some(false),
+            related_document_support: Some(true),
+        });
+        self
+    }
+
+    /// Advertises `textDocument/inlayHint` including resolving tooltips and edits
+    pub fn with_inlay_hints(mut self) -> Self {
+        self.text_document().inlay_hint = Some(InlayHintClientCapabilities {
+            dynamic_registration: Some(false),
+            resolve_support: Some(InlayHintResolveClientCapabilities {
+                properties: vec!["tooltip".to_string(), "textEdits".to_string()],
+            }),
+        });
+        self
+    }
+
+    pub fn build(self) -> ClientCapabilities {
+        self.capabilities
+    }
+}
+
 impl<L: Language> LspServer<L> {
     /// Checks if the LSP server process is still running
     pub fn is_alive(&mut self) -> bool {
-        match self.process.try_wait() {
+        let Some(process) = &mut self.process else {
+            return true;
+        };
+        match process.try_wait() {
             Ok(None) => true, // Still running
             Ok(Some(status)) => {
                 tracing::warn!("LSP server has exited with status: {}", status);
@@ -102,6 +798,10 @@ impl<L: Language> LspServer<L> {
 
     /// Sends a request to the LSP server with a specific ID
     pub fn send_request_with_id<R: Request>(&mut self, id: u64, params: R::Params) -> Result<()> {
+        self.send_raw_request_with_id(id, R::METHOD, to_value(&params)?)
+    }
+
+    fn send_raw_request_with_id(&mut self, id: u64, method: &str, params: Value) -> Result<()> {
         // Check if the server is still alive before sending
         if !self.is_alive() {
             return Err(anyhow::anyhow!("LSP server process has terminated"));
@@ -110,8 +810,8 @@ impl<L: Language> LspServer<L> {
         let request = serde_json::json!({
             "jsonrpc": "2.0",
             "id": id,
-            "method": R::METHOD,
-            "params": to_value(&params)?
+            "method": method,
+            "params": params
         });
 
         let message = request_string(&request)?;
@@ -139,11 +839,7 @@ impl<L: Language> LspServer<L> {
 
     /// Sends a notification to the LSP server
     pub fn send_notification<N: Notification>(&mut self, params: N::Params) -> Result<()> {
-        let notification = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": N::METHOD,
-            "params": to_value(&params)?
-        });
+        let notification = notification_message::<N>(params)?;
 
         let message = request_string(&notification)?;
         let notification_str = serde_json::to_string(&notification)?;
@@ -158,12 +854,18 @@ impl<L: Language> LspServer<L> {
     /// Opens a file in the LSP server
     ///
     /// This sends a `textDocument/didOpen` notification to inform the LSP server
-    /// that a file is now open for editing.
+    /// that a file is now open for editing. Files that are already open are skipped.
     pub fn open_file(&mut self, path: &std::path::Path, file_content: &str) -> Result<()> {
+        let uri = to_file_uri(path)?;
+        if self.open_documents.contains_key(&uri) {
+            tracing::debug!("Document {} is already open", path.display());
+            return Ok(());
+        }
+
         self.send_notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
             text_document: TextDocumentItem {
-                uri: uri_from_path(path)?,
-                language_id: self.language.to_string().to_lowercase(),
+                uri: uri.clone(),
+                language_id: self.language.to_lsp_language_id(Some(path)).to_string(),
                 version: 1,
                 text: file_content.to_string(),
             },
@@ -171,117 +873,759 @@ impl<L: Language> LspServer<L> {
         .map_err(|err| {
             tracing::warn!("Failed to open document {}: {}", path.display(), err);
             err
-        })
+        })?;
+        self.open_documents.insert(uri, 1);
+        Ok(())
     }
 
-    /// Closes a file in the LSP server
+    /// Opens several files in the LSP server
     ///
-    /// This sends a `textDocument/didClose` notification to inform the LSP server
-    /// that a file is no longer open.
-    pub fn close_file(&mut self, file_path: &std::path::Path) -> Result<()> {
-        self.send_notification::<DidCloseTextDocument>(DidCloseTextDocumentParams {
-            text_document: TextDocumentIdentifier {
-                uri: uri_from_path(file_path)?,
-            },
-        })
-        .map_err(|err| {
-            tracing::warn!("Failed to close document {}: {}", file_path.display(), err);
-            err
-        })
+    /// Some servers resolve cross-file references better when more files are
+    /// open at the same time.
+    pub fn open_files(&mut self, files: &[(PathBuf, String)]) -> Result<()> {
+        for (path, file_content) in files {
+            self.open_file(path, file_content)?;
+        }
+        Ok(())
     }
 
-    /// Reads a response from the LSP server
-    pub fn read_response(&mut self) -> Result<serde_json::Value> {
-        // Check if server is still alive first
-        if !self.is_alive() {
-            return Err(anyhow::anyhow!("LSP server process has terminated"));
+    /// Opens several files in the LSP server with a single flush
+    ///
+    /// Unlike `open_files`, which flushes after every `textDocument/didOpen`,
+    /// all notifications are built first and then written at once. This saves
+    /// system calls when opening the files of a large project, and nothing is
+    /// sent if a path is invalid. Files that are already open are skipped.
+    pub fn bulk_open_files(&mut self, files: &[(PathBuf, String)]) -> Result<()> {
+        let mut opened = Vec::new();
+        let mut seen = HashSet::new();
+        let mut messages = Vec::new();
+        for (path, file_content) in files {
+            let uri = to_file_uri(path)?;
+            if self.open_documents.contains_key(&uri) || !seen.insert(path) {
+                tracing::debug!("Document {} is already open", path.display());
+                continue;
+            }
+            let notification =
+                notification_message::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: self.language.to_lsp_language_id(Some(path)).to_string(),
+                        version: 1,
+                        text: file_content.clone(),
+                    },
+                })?;
+            messages.extend_from_slice(request_string(&notification)?.as_bytes());
+            opened.push(uri);
         }
 
-        // Wait for response with a timeout
-        match self.response_rx.recv_timeout(Duration::from_secs(30)) {
-            Ok(result) => result,
-            Err(RecvTimeoutError::Timeout) => Err(anyhow::anyhow!(
-                "Timeout waiting for LSP response after 30 seconds"
-            )),
-            Err(RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!(
-                "LSP server response channel disconnected - server likely crashed"
-            )),
+        if let Err(err) = self
+            .stdin
+            .write_all(&messages)
+            .and_then(|()| self.stdin.flush())
+        {
+            tracing::warn!("Failed to open {} documents: {}", opened.len(), err);
+            return Err(err.into());
         }
+        tracing::debug!("Opened {} documents", opened.len());
+        for uri in opened {
+            self.open_documents.insert(uri, 1);
+        }
+        Ok(())
     }
 
-    /// Reads responses until finding one with the expected ID
-    pub fn read_response_with_id(&mut self, expected_id: u64) -> Result<serde_json::Value> {
-        // Keep reading messages until we find the response with the matching ID
-        let start = std::time::Instant::now();
-        let overall_timeout = Duration::from_secs(30);
-
-        loop {
-            // Check overall timeout
-            if start.elapsed() > overall_timeout {
-                return Err(anyhow::anyhow!(
-                    "Overall timeout waiting for response with ID {}",
-                    expected_id
-                ));
+    /// Opens several files in the LSP server, serializing the notifications on `parallelism` threads
+    ///
+    /// Escaping large files as JSON takes most of the time of `bulk_open_files`.
+    /// Here a pool of worker threads builds the `textDocument/didOpen` messages
+    /// while the calling thread writes the finished ones to the server, so the
+    /// notifications may arrive in a different order than `files`. Files that
+    /// are already open are skipped.
+    pub fn open_files_parallel(
+        &mut self,
+        files: Vec<(PathBuf, String)>,
+        parallelism: usize,
+    ) -> Result<()> {
+        // Every path is validated before anything is sent or marked as open
+        let mut documents = Vec::new();
+        let mut seen = HashSet::new();
+        for (path, file_content) in files {
+            let uri = to_file_uri(&path)?;
+            if self.open_documents.contains_key(&uri) || !seen.insert(path.clone()) {
+                tracing::debug!("Document {} is already open", path.display());
+                continue;
             }
+            documents.push(TextDocumentItem {
+                uri,
+                language_id: self.language.to_lsp_language_id(Some(&path)).to_string(),
+                version: 1,
+                text: file_content,
+            });
+        }
+        let opened: Vec<Uri> = documents
+            .iter()
+            .map(|document| document.uri.clone())
+            .collect();
 
-            let message = self.read_response()?;
+        let queue = Mutex::new(documents.into_iter());
+        let stdin = &mut self.stdin;
+        let result = std::thread::scope(|scope| -> Result<()> {
+            let (message_tx, message_rx) = sync_channel(parallelism);
+            for _ in 0..parallelism.max(1) {
+                let message_tx = message_tx.clone();
+                let queue = &queue;
+                scope.spawn(move || {
+                    loop {
+                        let Some(text_document) = queue.lock().unwrap().next() else {
+                            break;
+                        };
+                        let message = notification_message::<DidOpenTextDocument>(
+                            DidOpenTextDocumentParams { text_document },
+                        )
+                        .and_then(|notification| request_string(&notification));
+                        // The receiver is gone once writing has failed
+                        if message_tx.send(message).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(message_tx);
 
-            // Check if this is a notification (no id field) or response
-            if let Some(id) = message.get("id") {
-                if id.as_u64() == Some(expected_id) {
-                    return Ok(message);
-                } else {
-                    tracing::debug!("Received response with different ID: {:?}", id);
-                }
-                // This is a notification or other message without an ID
-            } else if let Some(method) = message.get("method") {
-                tracing::debug!("Received notification: {}", method);
+            let mut writer = BufWriter::new(stdin);
+            for message in message_rx {
+                writer.write_all(message?.as_bytes())?;
             }
+            writer.flush()?;
+            Ok(())
+        });
+        if let Err(err) = result {
+            tracing::warn!("Failed to open {} documents: {}", opened.len(), err);
+            return Err(err);
+        }
+        tracing::debug!("Opened {} documents", opened.len());
+        for uri in opened {
+            self.open_documents.insert(uri, 1);
         }
+        Ok(())
     }
 
-    /// Sends a request and waits for the response
-    pub fn request<R: Request>(&mut self, params: R::Params) -> Result<R::Result> {
-        let id = self.send_request::<R>(params)?;
-        let response = self.read_response_with_id(id)?;
+    /// Returns true if the file is currently open in the LSP server
+    pub fn is_open(&self, path: &Path) -> bool {
+        to_file_uri(path).is_ok_and(|uri| self.open_documents.contains_key(&uri))
+    }
 
-        // Check if the response contains an error
-        if let Some(error) = response.get("error") {
-            let error_message = error
-                .get("message")
-                .and_then(|m| m.as_str())
-                .unwrap_or("Unknown error");
-            let error_code = error.get("code").and_then(|c| c.as_i64()).unwrap_or(-1);
-            return Err(anyhow::anyhow!(
-                "LSP error (code {}): {}",
-                error_code,
-                error_message
-            ));
-        }
-
-        // Extract the result field from the JSON-RPC response
-        let result = response
-            .get("result")
-            .ok_or_else(|| anyhow::anyhow!("Missing result field in response"))?;
+    /// Replaces the content of a file in the LSP server
+    ///
+    /// This sends a `textDocument/didChange` notification with the full new
+    /// content. Files that are not open yet are opened instead. If
+    /// `auto_save_on_update` is configured a `textDocument/didSave` follows.
+    pub fn update_file(&mut self, file_path: &Path, file_content: &str) -> Result<()> {
+        let uri = to_file_uri(file_path)?;
+        let Some(version) = self.open_documents.get_mut(&uri) else {
+            return self.open_file(file_path, file_content);
+        };
+        *version += 1;
+        let version = *version;
 
-        // Deserialize into the request's result type
-        let typed_result = from_value::<R::Result>(result.clone())?;
-        Ok(typed_result)
+        self.send_notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier { uri, version },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: file_content.to_string(),
+            }],
+        })
+        .map_err(|err| {
+            tracing::warn!("Failed to update document {}: {}", file_path.display(), err);
+            err
+        })?;
+
+        if self.config.auto_save_on_update {
+            self.save_file(file_path, false)?;
+        }
+        Ok(())
     }
 
-    /// Stops the LSP server process
-    pub fn stop(&mut self) -> Result<()> {
-        tracing::info!(
-            "Stopping LSP server for {} (PID: {:?})",
-            self.language,
-            self.process.id()
-        );
+    /// Notifies the LSP server that a file has been saved to disk
+    ///
+    /// This sends a `textDocument/didSave` notification which makes some servers
+    /// re-index the file. If `include_text` is true the current content of the
+    /// file on disk is included.
+    pub fn save_file(&mut self, file_path: &Path, include_text: bool) -> Result<()> {
+        let text = if include_text {
+            Some(std::fs::read_to_string(file_path).map_err(|e| {
+                anyhow::anyhow!("Failed to read file {}: {}", file_path.display(), e)
+            })?)
+        } else {
+            None
+        };
 
-        match self.process.kill() {
-            Ok(_) => {
-                if let Ok(exit_status) = self.process.wait() {
-                    tracing::info!("LSP server terminated with status: {}", exit_status);
+        self.send_notification::<DidSaveTextDocument>(DidSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier {
+                uri: to_file_uri(file_path)?,
+            },
+            text,
+        })
+    }
+
+    /// Notifies the LSP server that a file is about to be saved
+    ///
+    /// This sends a `textDocument/willSave` notification.
+    pub fn will_save_file(
+        &mut self,
+        file_path: &Path,
+        reason: TextDocumentSaveReason,
+    ) -> Result<()> {
+        self.send_notification::<WillSaveTextDocument>(WillSaveTextDocumentParams {
+            text_document: text_document_identifier_from_path(file_path)?,
+            reason,
+        })
+    }
+
+    /// Asks the LSP server for edits to apply to a file before it is saved
+    ///
+    /// This sends a `textDocument/willSaveWaitUntil` request, servers use it
+    /// for instance to format the file on save. A `None` result is returned as
+    /// an empty list.
+    pub fn will_save_wait_until_file(
+        &mut self,
+        file_path: &Path,
+        reason: TextDocumentSaveReason,
+    ) -> Result<Vec<TextEdit>> {
+        let edits = self.request_retrying::<WillSaveWaitUntil>(WillSaveTextDocumentParams {
+            text_document: text_document_identifier_from_path(file_path)?,
+            reason,
+        })?;
+        Ok(edits.unwrap_or_default())
+    }
+
+    /// Applies a workspace edit, e.g. from a rename, to the files on disk
+    ///
+    /// As the client advertises support for `documentChanges`, `changes` is
+    /// only used when the edit has no `documentChanges`. Resource operations
+    /// such as creating or renaming files are not supported. When a document
+    /// change names a version, it must match the version of the document open
+    /// in the server. All edits of a file refer to its original content, even
+    /// when they are spread over several document changes.
+    ///
+    /// The documents that are open in the server are updated with
+    /// `textDocument/didChange` and `textDocument/didSave` after the files are
+    /// written. With `dry_run` nothing is written or sent. In either case the
+    /// new content of every edited file is returned.
+    pub fn apply_workspace_edit(
+        &mut self,
+        edit: &WorkspaceEdit,
+        dry_run: bool,
+    ) -> Result<BTreeMap<PathBuf, String>> {
+        let mut file_edits: BTreeMap<PathBuf, Vec<TextEdit>> = BTreeMap::new();
+        let document_edits = match &edit.document_changes {
+            None => {
+                for (uri, edits) in edit.changes.iter().flatten() {
+                    file_edits
+                        .entry(from_file_uri(uri)?)
+                        .or_default()
+                        .extend(edits.iter().cloned());
+                }
+                vec![]
+            }
+            Some(DocumentChanges::Edits(edits)) => edits.iter().collect(),
+            Some(DocumentChanges::Operations(operations)) => operations
+                .iter()
+                .map(|operation| match operation {
+                    DocumentChangeOperation::Edit(edit) => Ok(edit),
+                    DocumentChangeOperation::Op(op) => {
+                        Err(anyhow::anyhow!("Unsupported resource operation: {:?}", op))
+                    }
+                })
+                .collect::<Result<_>>()?,
+        };
+        for document_edit in document_edits {
+            let document = &document_edit.text_document;
+            if let Some(version) = document.version
+                && let Some(open_version) = self.open_documents.get(&document.uri)
+                && *open_version != version
+            {
+                anyhow::bail!(
+                    "Edit for {} is for version {} but version {} is open",
+                    document.uri.as_str(),
+                    version,
+                    open_version
+                );
+            }
+            let edits = document_edit.edits.iter().map(|edit| match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(annotated) => annotated.text_edit.clone(),
+            });
+            file_edits
+                .entry(from_file_uri(&document.uri)?)
+                .or_default()
+                .extend(edits);
+        }
+
+        let mut new_contents = BTreeMap::new();
+        for (path, edits) in file_edits {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", path.display(), e))?;
+            let content = apply_text_edits(&content, &edits, &self.position_encoding)
+                .map_err(|e| anyhow::anyhow!("Failed to edit file {}: {}", path.display(), e))?;
+            new_contents.insert(path, content);
+        }
+
+        if !dry_run {
+            for (path, content) in &new_contents {
+                std::fs::write(path, content).map_err(|e| {
+                    anyhow::anyhow!("Failed to write file {}: {}", path.display(), e)
+                })?;
+            }
+            for (path, content) in &new_contents {
+                if self.is_open(path) {
+                    self.update_file(path, content)?;
+                    if !self.config.auto_save_on_update {
+                        self.save_file(path, false)?;
+                    }
+                }
+            }
+        }
+        Ok(new_contents)
+    }
+
+    /// Pushes new settings to the LSP server
+    ///
+    /// This sends a `workspace/didChangeConfiguration` notification. Servers
+    /// such as gopls and pylsp read settings like enabled analyses or plugins
+    /// from it after initialization.
+    pub fn change_configuration(&mut self, settings: Value) -> Result<()> {
+        self.send_notification::<DidChangeConfiguration>(DidChangeConfigurationParams { settings })
+    }
+
+    /// Replaces the client configuration and pushes it to the server
+    ///
+    /// The settings are stored in `config_values` and sent with
+    /// `change_configuration`. Servers that pull their settings instead read
+    /// them with `workspace/configuration` requests, which are answered from
+    /// the stored settings.
+    pub fn update_configuration(&mut self, settings: Value) -> Result<()> {
+        self.config_values = settings.clone();
+        self.change_configuration(settings)
+    }
+
+    /// Returns a section of the client configuration, as sent to the server
+    ///
+    /// Sections are dotted paths into `config_values`, like
+    /// `rust-analyzer.cargo`, and an empty section is the whole configuration.
+    /// Returns null for sections that are not set.
+    pub fn get_workspace_configuration(&self, section: &str) -> Value {
+        section
+            .split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(&self.config_values, |value, key| value.get(key))
+            .cloned()
+            .unwrap_or(Value::Null)
+    }
+
+    /// Adds and removes workspace folders after initialization
+    ///
+    /// This sends a `workspace/didChangeWorkspaceFolders` notification, which
+    /// lets multi-root tools add project directories incrementally.
+    pub fn change_workspace_folders(
+        &mut self,
+        added: Vec<WorkspaceFolder>,
+        removed: Vec<WorkspaceFolder>,
+    ) -> Result<()> {
+        self.send_notification::<DidChangeWorkspaceFolders>(DidChangeWorkspaceFoldersParams {
+            event: WorkspaceFoldersChangeEvent { added, removed },
+        })
+    }
+
+    /// Closes a file in the LSP server
+    ///
+    /// This sends a `textDocument/didClose` notification to inform the LSP server
+    /// that a file is no longer open. Files that are not open are skipped.
+    pub fn close_file(&mut self, file_path: &std::path::Path) -> Result<()> {
+        let uri = to_file_uri(file_path)?;
+        if !self.open_documents.contains_key(&uri) {
+            tracing::debug!("Document {} is not open", file_path.display());
+            return Ok(());
+        }
+
+        self.close_uri(uri).map_err(|err| {
+            tracing::warn!("Failed to close document {}: {}", file_path.display(), err);
+            err
+        })
+    }
+
+    /// Closes all files that are currently open in the LSP server
+    pub fn close_all(&mut self) -> Result<()> {
+        let uris: Vec<Uri> = self.open_documents.keys().cloned().collect();
+        for uri in uris {
+            self.close_uri(uri)?;
+        }
+        Ok(())
+    }
+
+    fn close_uri(&mut self, uri: Uri) -> Result<()> {
+        self.send_notification::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+        })?;
+        self.open_documents.remove(&uri);
+        Ok(())
+    }
+
+    /// Returns the next message from the server, waiting at most `timeout`
+    fn receive_message(
+        &mut self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<serde_json::Value>, RecvTimeoutError> {
+        #[cfg(any(test, feature = "test-util"))]
+        if let Some(message) = self.injected_messages.pop_front() {
+            return Ok(Ok(message));
+        }
+        self.response_rx.recv_timeout(timeout)
+    }
+
+    /// Makes the client read a notification as if the server had sent it
+    ///
+    /// The notification is read before any message from the server that has
+    /// not been read yet. This is meant for testing how notifications are
+    /// handled without a server that sends them.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn inject_notification_for_test(&mut self, method: &str, params: Value) {
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        });
+        // Like the messages from the server, window messages are logged when received
+        log_window_message(&notification, self.config.min_message_type);
+        self.injected_messages.push_back(notification);
+    }
+
+    /// Reads a response from the LSP server
+    pub fn read_response(&mut self) -> Result<serde_json::Value> {
+        // Check if server is still alive first
+        if !self.is_alive() {
+            return Err(anyhow::anyhow!("LSP server process has terminated"));
+        }
+
+        // Wait for response with a timeout
+        match self.receive_message(Duration::from_secs(30)) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(anyhow::anyhow!(
+                "Timeout waiting for LSP response after 30 seconds"
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!(
+                "LSP server response channel disconnected - server likely crashed"
+            )),
+        }
+    }
+
+    /// Reads responses until finding one with the expected ID
+    pub fn read_response_with_id(&mut self, expected_id: u64) -> Result<serde_json::Value> {
+        self.read_response_observing(expected_id, |_| {})
+    }
+
+    /// Like `read_response_with_id`, but passes the notifications read on the way to `on_notification`
+    fn read_response_observing(
+        &mut self,
+        expected_id: u64,
+        mut on_notification: impl FnMut(&Value),
+    ) -> Result<serde_json::Value> {
+        // Keep reading messages until we find the response with the matching ID
+        let start = std::time::Instant::now();
+        let overall_timeout = Duration::from_secs(30);
+
+        loop {
+            // Check overall timeout
+            if start.elapsed() > overall_timeout {
+                return Err(anyhow::anyhow!(
+                    "Overall timeout waiting for response with ID {}",
+                    expected_id
+                ));
+            }
+
+            let message = self.read_response()?;
+
+            // Check if this is a request from the server, a notification (no id field) or response
+            if let (Some(id), Some(method)) = (message.get("id"), message["method"].as_str()) {
+                // The server numbers its requests independently of ours
+                self.reply_to_server_request(id, method, &message["params"])?;
+            } else if let Some(id) = message.get("id") {
+                if id.as_u64() == Some(expected_id) {
+                    return Ok(message);
+                } else {
+                    tracing::debug!("Received response with different ID: {:?}", id);
+                }
+                // This is a notification or other message without an ID
+            } else if let Some(method) = message.get("method") {
+                tracing::debug!("Received notification: {}", method);
+                on_notification(&message);
+            }
+        }
+    }
+
+    /// Waits until the server reports that it is done with its initial work
+    ///
+    /// Servers report work such as indexing with `$/progress` notifications.
+    /// The server is considered ready once all reported work has ended and no
+    /// new work starts within a short moment, since servers like rust-analyzer
+    /// report several phases one after another. Servers that never report
+    /// progress are waited on for the full `max_wait`.
+    ///
+    /// Returns true if the server reported that it is ready, false if `max_wait`
+    /// ran out first.
+    pub fn wait_until_ready(&mut self, max_wait: Duration) -> Result<bool> {
+        let settle_time = Duration::from_millis(300);
+        let deadline = std::time::Instant::now() + max_wait;
+        let mut active_tokens = HashSet::new();
+        let mut any_ended = false;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let settling = any_ended && active_tokens.is_empty();
+            let timeout = if settling {
+                remaining.min(settle_time)
+            } else {
+                remaining
+            };
+            let message = match self.receive_message(timeout) {
+                Ok(message) => message?,
+                Err(RecvTimeoutError::Timeout) => return Ok(settling),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow::anyhow!(
+                        "LSP server response channel disconnected - server likely crashed"
+                    ));
+                }
+            };
+
+            match message["method"].as_str() {
+                Some(method) if message.get("id").is_some() => {
+                    self.reply_to_server_request(&message["id"], method, &message["params"])?;
+                }
+                Some("$/progress") => {
+                    let token = message["params"]["token"].to_string();
+                    match message["params"]["value"]["kind"].as_str() {
+                        Some("begin") => {
+                            tracing::debug!(
+                                "Server started: {}",
+                                message["params"]["value"]["title"]
+                            );
+                            active_tokens.insert(token);
+                        }
+                        Some("end") => {
+                            active_tokens.remove(&token);
+                            any_ended = true;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits for the server to index the project, at most for the language's
+    /// `indexing_warmup`
+    pub fn wait_for_indexing(&mut self) -> Result<bool> {
+        tracing::info!("Waiting for the LSP server to index the project...");
+        let ready = self.wait_until_ready(self.language.indexing_warmup())?;
+        if !ready {
+            tracing::debug!("The LSP server did not report being ready in time");
+        }
+        Ok(ready)
+    }
+
+    /// Answers a request sent by the server to the client
+    ///
+    /// Progress tokens from `window/workDoneProgress/create` are accepted and
+    /// `workspace/configuration` is answered with `get_workspace_configuration`.
+    /// Other requests are answered with a `MethodNotFound` error, so that the
+    /// server does not wait for them.
+    fn reply_to_server_request(&mut self, id: &Value, method: &str, params: &Value) -> Result<()> {
+        let result = match method {
+            // The server waits for the client to accept the progress token
+            "window/workDoneProgress/create" => Value::Null,
+            "workspace/configuration" => params["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|item| {
+                    self.get_workspace_configuration(item["section"].as_str().unwrap_or_default())
+                })
+                .collect(),
+            _ => {
+                tracing::debug!("Declining request from the server: {}", method);
+                let response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": LspResponseError::METHOD_NOT_FOUND,
+                        "message": format!("Method not found: {}", method)
+                    }
+                });
+                return self.send_response(&response);
+            }
+        };
+        self.send_response(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }))
+    }
+
+    /// Writes a response to a request of the server
+    fn send_response(&mut self, response: &Value) -> Result<()> {
+        self.stdin.write_all(request_string(response)?.as_bytes())?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Sends a request and waits for the response
+    pub fn request<R: Request>(&mut self, params: R::Params) -> Result<R::Result> {
+        let result = self.raw_request(R::METHOD, to_value(&params)?)?;
+
+        // Deserialize into the request's result type
+        let typed_result = from_value::<R::Result>(result)?;
+        Ok(typed_result)
+    }
+
+    /// Low-level: sends a request with any method and JSON params and returns the raw result
+    ///
+    /// This bypasses the typed `Request` trait, for experimenting with server
+    /// specific extensions like `rust-analyzer/analyzerStatus`. Nothing about
+    /// the method or params is checked. Prefer `request` for standard methods.
+    pub fn raw_request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_raw_request_with_id(id, method, params)?;
+        let response = self.read_response_with_id(id)?;
+        response_result(response)
+    }
+
+    /// Sends a request with a work-done progress token and reports the progress to `progress_cb`
+    ///
+    /// The token is set as the `workDoneToken` of the params, and every
+    /// `$/progress` notification for it that arrives before the response is
+    /// passed to `progress_cb`. Servers only report progress for requests whose
+    /// params include `WorkDoneProgressParams`, such as `workspace/symbol`, and
+    /// may not report any at all.
+    pub fn request_with_progress<R: Request>(
+        &mut self,
+        params: R::Params,
+        progress_token: &str,
+        mut progress_cb: impl FnMut(WorkDoneProgress),
+    ) -> Result<R::Result> {
+        let mut params = to_value(&params)?;
+        let Some(fields) = params.as_object_mut() else {
+            anyhow::bail!("The params of {} cannot carry a progress token", R::METHOD);
+        };
+        fields.insert("workDoneToken".to_string(), Value::from(progress_token));
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_raw_request_with_id(id, R::METHOD, params)?;
+        let response = self.read_response_observing(id, |message| {
+            let progress = &message["params"];
+            if message["method"] != "$/progress" || progress["token"] != progress_token {
+                return;
+            }
+            match from_value::<WorkDoneProgress>(progress["value"].clone()) {
+                Ok(value) => progress_cb(value),
+                Err(e) => tracing::debug!("Ignoring invalid progress for {}: {}", R::METHOD, e),
+            }
+        })?;
+        Ok(from_value(response_result(response)?)?)
+    }
+
+    /// Sends a request for each of `requests` and pairs the results with their tags
+    ///
+    /// The requests are sent one after another and a failing request does not
+    /// stop the rest. The tag `T` is any value the caller uses to identify a
+    /// request, e.g. the file it is about.
+    pub fn request_all<R: Request, T>(
+        &mut self,
+        requests: Vec<(R::Params, T)>,
+    ) -> Vec<(T, Result<R::Result>)> {
+        requests
+            .into_iter()
+            .map(|(params, tag)| (tag, self.request::<R>(params)))
+            .collect()
+    }
+
+    /// Sends a request and retries it when the server reports a transient error
+    ///
+    /// Requests that fail with "content modified" (-32801) or "server cancelled"
+    /// (-32802) are retried up to `max_retries` times. The delay before each
+    /// retry starts at `retry_delay` and doubles every time.
+    pub fn request_with_retry<R: Request>(
+        &mut self,
+        params: R::Params,
+        max_retries: u32,
+        retry_delay: Duration,
+    ) -> Result<R::Result>
+    where
+        R::Params: Clone,
+    {
+        let mut delay = retry_delay;
+        for attempt in 0..max_retries {
+            match self.request::<R>(params.clone()) {
+                Err(err)
+                    if err
+                        .downcast_ref::<LspResponseError>()
+                        .is_some_and(LspResponseError::is_transient) =>
+                {
+                    tracing::debug!(
+                        "{} failed on attempt {}: {}, retrying in {:.2?}",
+                        R::METHOD,
+                        attempt + 1,
+                        err,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+        self.request::<R>(params)
+    }
+
+    /// Sends a request and retries transient errors as configured in `LspServerConfig`
+    ///
+    /// All the request helpers of `LspServer` use this, so that "content
+    /// modified" and "server cancelled" errors are handled the same everywhere.
+    pub fn request_retrying<R: Request>(&mut self, params: R::Params) -> Result<R::Result>
+    where
+        R::Params: Clone,
+    {
+        let retries = self.config.transient_error_retries;
+        let delay = self.config.transient_error_delay;
+        self.request_with_retry::<R>(params, retries, delay)
+    }
+
+    /// Stops the LSP server process
+    ///
+    /// Stopping an already stopped server does nothing.
+    pub fn stop(&mut self) -> Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+        let Some(process) = &mut self.process else {
+            self.stopped = true;
+            return Ok(());
+        };
+
+        tracing::info!(
+            "Stopping LSP server for {} (PID: {:?})",
+            self.language,
+            process.id()
+        );
+
+        match process.kill() {
+            Ok(_) => {
+                if let Ok(exit_status) = process.wait() {
+                    tracing::info!("LSP server terminated with status: {}", exit_status);
                 }
+                self.stopped = true;
                 Ok(())
             }
             Err(e) => {
@@ -298,14 +1642,34 @@ impl<L: Language> LspServer<L> {
         config: LspServerConfig,
     ) -> Result<LspServer<L>> {
         // Check if the LSP server is available
-        let (command, args) = language.lsp_server_command();
-        if !is_server_command_available(command) {
-            return Err(anyhow::anyhow!(
-                "LSP server for {} is not available. Please make sure the it is installed.",
-                language,
-            ));
+        let (command, args) = match &config.lsp_command_override {
+            Some(command) => (command.clone(), vec![]),
+            None => {
+                let (command, args) = language.lsp_server_command();
+                (command.to_string(), args)
+            }
+        };
+        let command = resolve_server_command(&command);
+        if config.disabled || !is_server_command_available(&command) {
+            return Err(LspServerUnavailable {
+                command,
+                language: language.to_string(),
+                disabled: config.disabled,
+            }
+            .into());
         }
 
+        Self::spawn(language, working_dir, &command, &args, config)
+    }
+
+    /// Spawns `command` as the LSP server process and connects to its stdio
+    fn spawn(
+        language: L,
+        working_dir: PathBuf,
+        command: &str,
+        args: &[String],
+        config: LspServerConfig,
+    ) -> Result<LspServer<L>> {
         tracing::info!(
             "Starting LSP server for {language} in {}",
             working_dir.display()
@@ -316,12 +1680,12 @@ impl<L: Language> LspServer<L> {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .args(&args)
-            .args(&config.args);
+            .args(args)
+            .args(config.args.iter().map(|arg| expand_env_vars(arg)));
 
         // Set environment variables
         for (key, value) in &config.env_vars {
-            cmd.env(key, value);
+            cmd.env(key, expand_env_vars(value));
         }
 
         tracing::debug!("Executing command: {} {}", command, args.join(" "));
@@ -344,70 +1708,25 @@ impl<L: Language> LspServer<L> {
             .take()
             .ok_or_else(|| anyhow::anyhow!("Failed to get stdout"))?;
 
-        // Create a channel for receiving responses
-        let (response_tx, response_rx) = channel();
-
-        // Spawn a thread to read responses from stdout
-        std::thread::spawn(move || {
-            let mut stdout = BufReader::new(stdout);
-            loop {
-                // Read headers
-                let mut content_length = 0;
-                loop {
-                    let mut header = String::new();
-                    match stdout.read_line(&mut header) {
-                        Ok(0) => {
-                            // EOF reached
-                            return;
-                        }
-                        Ok(_) => {
-                            if header == "\r\n" {
-                                break;
-                            }
-                            if let Some(length_str) = header.strip_prefix("Content-Length: ") {
-                                content_length = length_str.trim().parse().unwrap_or(0);
-                            }
-                        }
-                        Err(e) => {
-                            let _ = response_tx
-                                .send(Err(anyhow::anyhow!("Failed to read header: {}", e)));
-                            return;
-                        }
-                    }
-                }
-
-                // Read content
-                let mut buffer = vec![0; content_length];
-                if let Err(e) = std::io::Read::read_exact(&mut stdout, &mut buffer) {
-                    let _ = response_tx.send(Err(anyhow::anyhow!("Failed to read content: {}", e)));
-                    return;
+        // Only wrap the pipes when logging, so there is no overhead otherwise
+        let (stdin, stdout): (Box<dyn Write + Send>, Box<dyn Read + Send>) =
+            match &config.protocol_log_path {
+                Some(path) => {
+                    let log = ProtocolLog::open(path)?;
+                    (
+                        Box::new(LoggingWriter::new(stdin, log.clone())),
+                        Box::new(LoggingReader::new(stdout, log)),
+                    )
                 }
+                None => (Box::new(stdin), Box::new(stdout)),
+            };
 
-                let response_str = match String::from_utf8(buffer) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        let _ = response_tx
-                            .send(Err(anyhow::anyhow!("Invalid UTF-8 in response: {}", e)));
-                        continue;
-                    }
-                };
-
-                tracing::debug!("Received message: {}", response_str);
-
-                match serde_json::from_str(&response_str) {
-                    Ok(response) => {
-                        if response_tx.send(Ok(response)).is_err() {
-                            // Receiver has been dropped
-                            return;
-                        }
-                    }
-                    Err(e) => {
-                        let _ =
-                            response_tx.send(Err(anyhow::anyhow!("Failed to parse JSON: {}", e)));
-                    }
-                }
-            }
-        });
+        // Spawn a thread to read responses from stdout
+        let response_rx = spawn_reader(
+            stdout,
+            config.min_message_type,
+            config.stdout_buffer_capacity,
+        );
 
         // Spawn a thread to consume stderr to prevent the LSP server from blocking
         // when the stderr pipe fills up
@@ -423,15 +1742,56 @@ impl<L: Language> LspServer<L> {
         }
 
         Ok(LspServer {
-            process,
+            process: Some(process),
             language,
             working_dir,
             stdin,
+            // UTF-16 is the default until the server says otherwise
+            position_encoding: PositionEncodingKind::UTF16,
+            config,
+            config_values: Value::Null,
+            open_documents: HashMap::new(),
+            stopped: false,
             response_rx,
+            #[cfg(any(test, feature = "test-util"))]
+            injected_messages: Default::default(),
             next_id: 1,
         })
     }
 
+    /// Connects to an LSP server that communicates through the given streams
+    ///
+    /// This is for servers that do not run as a child process, such as
+    /// in-process servers used in tests. `reader` yields the messages sent by
+    /// the server and `writer` receives the messages sent by the client.
+    pub fn from_streams(
+        language: L,
+        working_dir: PathBuf,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> LspServer<L> {
+        let config = LspServerConfig::default();
+        LspServer {
+            process: None,
+            language,
+            working_dir,
+            stdin: Box::new(writer),
+            position_encoding: PositionEncodingKind::UTF16,
+            response_rx: spawn_reader(
+                reader,
+                config.min_message_type,
+                config.stdout_buffer_capacity,
+            ),
+            config,
+            config_values: Value::Null,
+            open_documents: HashMap::new(),
+            stopped: false,
+            #[cfg(any(test, feature = "test-util"))]
+            injected_messages: Default::default(),
+            next_id: 1,
+        }
+    }
+
     /// Starts and initializes an LSP server for the specified language in the given directory
     ///
     /// This is a convenience method that combines `start()` with the initialization sequence
@@ -441,67 +1801,301 @@ impl<L: Language> LspServer<L> {
         working_dir: PathBuf,
         config: LspServerConfig,
     ) -> Result<LspServer<L>> {
-        let mut server = Self::start(language, working_dir.clone(), config)?;
+        let mut server = Self::start(language, working_dir, config)?;
+        server.initialize()?;
+        Ok(server)
+    }
 
-        // Initialize the LSP server
-        tracing::info!("Initializing LSP server...");
-        let workspace_uri = uri_from_path(&working_dir)?;
-
-        // Set up client capabilities to enable all features we want to use
-        use lsp_types::{
-            CallHierarchyClientCapabilities, ClientCapabilities, TextDocumentClientCapabilities,
-            WorkspaceClientCapabilities,
+    /// Starts and initializes an LSP server with several workspace folders
+    ///
+    /// All `folders` are sent as workspace roots and the first one is used as
+    /// the working directory of the server. This lets servers resolve symbols
+    /// across, for instance, the crates of a Cargo workspace.
+    pub fn start_and_init_with_folders(language: L, folders: Vec<PathBuf>) -> Result<LspServer<L>> {
+        let Some(working_dir) = folders.first() else {
+            anyhow::bail!("At least one workspace folder is required");
         };
+        let mut server = Self::start(language, working_dir.clone(), Default::default())?;
+        server.initialize_with_folders(&folders)?;
+        Ok(server)
+    }
 
-        let capabilities = ClientCapabilities {
-            text_document: Some(TextDocumentClientCapabilities {
-                call_hierarchy: Some(CallHierarchyClientCapabilities {
-                    dynamic_registration: Some(false),
-                }),
-                document_symbol: Some(lsp_types::DocumentSymbolClientCapabilities {
-                    dynamic_registration: Some(false),
-                    hierarchical_document_symbol_support: Some(true),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            }),
-            workspace: Some(WorkspaceClientCapabilities {
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
+    /// Performs the LSP initialization handshake
+    ///
+    /// This sends the Initialize request with the working directory as the
+    /// workspace folder, followed by the Initialized notification.
+    pub fn initialize(&mut self) -> Result<()> {
+        let working_dir = self.working_dir.clone();
+        self.initialize_with_folders(&[working_dir])
+    }
+
+    /// Performs the LSP initialization handshake with the given workspace folders
+    pub fn initialize_with_folders(&mut self, folders: &[PathBuf]) -> Result<()> {
+        tracing::info!("Initializing LSP server...");
+        let workspace_folders = folders
+            .iter()
+            .map(|folder| {
+                Ok(WorkspaceFolder {
+                    uri: to_file_uri(folder)?,
+                    name: folder
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("workspace")
+                        .to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         let initialize_params = InitializeParams {
             process_id: Some(std::process::id()),
-            workspace_folders: Some(vec![WorkspaceFolder {
-                uri: workspace_uri,
-                name: working_dir
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("workspace")
-                    .to_string(),
-            }]),
-            capabilities,
+            workspace_folders: Some(workspace_folders),
+            capabilities: self
+                .config
+                .client_capabilities
+                .clone()
+                .unwrap_or_else(client_capabilities),
             ..Default::default()
         };
 
-        server.request::<Initialize>(initialize_params)?;
-        server.send_notification::<Initialized>(InitializedParams {})?;
+        let initialize_result = self.request::<Initialize>(initialize_params)?;
+        if let Some(encoding) = initialize_result.capabilities.position_encoding {
+            self.position_encoding = encoding;
+        }
+        tracing::info!(
+            "Using {} position encoding",
+            self.position_encoding.as_str()
+        );
+        self.send_notification::<Initialized>(InitializedParams {})?;
         tracing::info!("LSP server initialized");
 
-        Ok(server)
+        Ok(())
     }
 
     pub fn start_and_init(language: L, working_dir: PathBuf) -> Result<LspServer<L>> {
         Self::start_and_init_with_config(language, working_dir, Default::default())
     }
 
+    /// Requests the declaration of the symbol at `position` in `file_path`
+    ///
+    /// This sends `textDocument/declaration` and flattens the response into locations.
+    pub fn goto_declaration(
+        &mut self,
+        file_path: &Path,
+        position: Position,
+    ) -> Result<Vec<Location>> {
+        let response = self.request_retrying::<GotoDeclaration>(GotoDeclarationParams {
+            text_document_position_params: text_document_position_params(file_path, position)?,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        Ok(response.map(response_locations).unwrap_or_default())
+    }
+
+    /// Requests the code actions, like quick-fixes and refactorings, for `range` in `file_path`
+    ///
+    /// This sends `textDocument/codeAction`. The response mixes code actions
+    /// and bare commands, so both are returned as `CodeActionOrCommand`. A null
+    /// response is returned as no actions.
+    pub fn code_actions(
+        &mut self,
+        file_path: &Path,
+        range: lsp_types::Range,
+        context: CodeActionContext,
+    ) -> Result<Vec<CodeActionOrCommand>> {
+        let actions = self.request_retrying::<CodeActionRequest>(CodeActionParams {
+            text_document: text_document_identifier_from_path(file_path)?,
+            range,
+            context,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        Ok(actions.unwrap_or_default())
+    }
+
+    /// Requests the monikers of the symbol at `position` in `file_path`
+    ///
+    /// This sends `textDocument/moniker`. Monikers identify a symbol by a
+    /// scheme and an identifier that stay the same across projects, which lets
+    /// indexes of different repositories be linked. A null response is returned
+    /// as no monikers.
+    pub fn moniker(&mut self, file_path: &Path, position: Position) -> Result<Vec<Moniker>> {
+        let monikers = self.request_retrying::<MonikerRequest>(MonikerParams {
+            text_document_position_params: text_document_position_params(file_path, position)?,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        Ok(monikers.unwrap_or_default())
+    }
+
+    /// Resolves the lazily computed properties of an inlay hint
+    ///
+    /// This sends `inlayHint/resolve`. Servers may leave out properties such as
+    /// `tooltip` and `textEdits` until a hint is resolved.
+    pub fn resolve_inlay_hint(&mut self, hint: InlayHint) -> Result<InlayHint> {
+        self.request_retrying::<InlayHintResolveRequest>(hint)
+    }
+
+    /// Asks the server to refresh the inlay hints of all open documents
+    ///
+    /// This sends `workspace/inlayHint/refresh`. The spec defines it as a
+    /// server to client request, but some servers accept it from the client
+    /// and recompute their hints. Servers that do not know the method are
+    /// not treated as an error.
+    pub fn request_inlay_hints_refresh(&mut self) -> Result<()> {
+        match self.request::<InlayHintRefreshRequest>(()) {
+            Err(e)
+                if e.downcast_ref::<LspResponseError>()
+                    .is_some_and(|e| e.code == LspResponseError::METHOD_NOT_FOUND) =>
+            {
+                tracing::debug!("The LSP server does not support refreshing inlay hints");
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Requests the call hierarchy item for the symbol at `position` in `file_path`
+    ///
+    /// This sends `textDocument/prepareCallHierarchy` and returns the first
+    /// item. Empty results are retried according to `retry`, errors are not.
+    pub fn prepare_call_hierarchy(
+        &mut self,
+        file_path: &Path,
+        position: Position,
+        retry: RetryPolicy,
+    ) -> Result<Option<CallHierarchyItem>> {
+        for attempt in 0..=retry.max_retries {
+            if attempt > 0 {
+                std::thread::sleep(retry.delay * attempt);
+            }
+            let items =
+                self.request_retrying::<CallHierarchyPrepare>(CallHierarchyPrepareParams {
+                    text_document_position_params: text_document_position_params(
+                        file_path, position,
+                    )?,
+                    work_done_progress_params: Default::default(),
+                })?;
+            if let Some(item) = items.and_then(|items| items.into_iter().next()) {
+                return Ok(Some(item));
+            }
+            tracing::debug!(
+                "No call hierarchy item for {}:{:?} on attempt {}",
+                file_path.display(),
+                position,
+                attempt + 1
+            );
+        }
+        Ok(None)
+    }
+
+    /// Requests the calls made from within a call hierarchy item
+    ///
+    /// This sends `callHierarchy/outgoingCalls`, a `None` result is returned as
+    /// an empty list.
+    pub fn outgoing_calls(
+        &mut self,
+        item: CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyOutgoingCall>> {
+        let calls = self.request_retrying::<CallHierarchyOutgoingCalls>(
+            CallHierarchyOutgoingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        )?;
+        Ok(calls.unwrap_or_default())
+    }
+
+    /// Builds the call graph reachable from `root_item` by following outgoing calls
+    ///
+    /// See `call_graph::build_call_graph`.
+    pub fn build_call_graph(
+        &mut self,
+        root_item: CallHierarchyItem,
+        max_depth: usize,
+    ) -> Result<CallGraph> {
+        crate::call_graph::build_call_graph(self, root_item, max_depth)
+    }
+
+    /// Requests the calls made to a call hierarchy item
+    ///
+    /// This sends `callHierarchy/incomingCalls`, a `None` result is returned as
+    /// an empty list.
+    pub fn incoming_calls(
+        &mut self,
+        item: CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyIncomingCall>> {
+        let calls = self.request_retrying::<CallHierarchyIncomingCalls>(
+            CallHierarchyIncomingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        )?;
+        Ok(calls.unwrap_or_default())
+    }
+
+    /// Requests the type hierarchy items for the type at `position` in `file_path`
+    ///
+    /// This sends `textDocument/prepareTypeHierarchy`, the returned items are
+    /// used to request their supertypes and subtypes. A null response is
+    /// returned as no items.
+    pub fn prepare_type_hierarchy(
+        &mut self,
+        file_path: &Path,
+        position: Position,
+    ) -> Result<Vec<TypeHierarchyItem>> {
+        let items = self.request_retrying::<TypeHierarchyPrepare>(TypeHierarchyPrepareParams {
+            text_document_position_params: text_document_position_params(file_path, position)?,
+            work_done_progress_params: Default::default(),
+        })?;
+        Ok(items.unwrap_or_default())
+    }
+
+    /// Requests the direct supertypes of a type hierarchy item, none for a null response
+    pub fn type_hierarchy_supertypes(
+        &mut self,
+        item: TypeHierarchyItem,
+    ) -> Result<Vec<TypeHierarchyItem>> {
+        let items =
+            self.request_retrying::<TypeHierarchySupertypes>(TypeHierarchySupertypesParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })?;
+        Ok(items.unwrap_or_default())
+    }
+
+    /// Requests the direct subtypes of a type hierarchy item, none for a null response
+    pub fn type_hierarchy_subtypes(
+        &mut self,
+        item: TypeHierarchyItem,
+    ) -> Result<Vec<TypeHierarchyItem>> {
+        let items =
+            self.request_retrying::<TypeHierarchySubtypes>(TypeHierarchySubtypesParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })?;
+        Ok(items.unwrap_or_default())
+    }
+
+    /// Requests the document symbols of `file_path` and returns the callable ones, see `callable_symbols`
+    pub fn get_callable_symbols(
+        &mut self,
+        file_path: &Path,
+        filter: &CallableSymbolFilter,
+    ) -> Result<Vec<DocumentSymbol>> {
+        let (symbols, _) = self.get_document_symbols(file_path)?;
+        Ok(callable_symbols(symbols, filter))
+    }
+
     pub fn get_document_symbols(
         &mut self,
         file_path: &Path,
     ) -> Result<(Vec<DocumentSymbol>, bool)> {
-        let file_uri = uri_from_path(file_path)?;
-        let response = self.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+        let file_uri = to_file_uri(file_path)?;
+        let response = self.request_retrying::<DocumentSymbolRequest>(DocumentSymbolParams {
             text_document: TextDocumentIdentifier { uri: file_uri },
             work_done_progress_params: Default::default(),
             partial_result_params: Default::default(),
@@ -510,36 +2104,10 @@ impl<L: Language> LspServer<L> {
             Ok(Some(lsp_types::DocumentSymbolResponse::Nested(symbols))) => Ok((symbols, false)),
             Ok(Some(lsp_types::DocumentSymbolResponse::Flat(symbols))) => {
                 warn!("LSP server returned flat document symbols, performing sketchy conversion");
-                // Convert flat symbols to nested format
-                // For flat symbols, we need to estimate the selection_range (identifier location)
-                // since SymbolInformation doesn't provide it separately
                 Ok((
                     symbols
                         .into_iter()
-                        .map(|sym| {
-                            let range = sym.location.range;
-                            // Make a rough guess of the selection range
-                            let start = range.start;
-                            let selection_range = lsp_types::Range {
-                                start,
-                                end: lsp_types::Position {
-                                    line: start.line,
-                                    character: start.character + sym.name.len() as u32,
-                                },
-                            };
-
-                            DocumentSymbol {
-                                name: sym.name,
-                                detail: None,
-                                kind: sym.kind,
-                                tags: sym.tags,
-                                #[allow(deprecated)]
-                                deprecated: None,
-                                range,
-                                selection_range,
-                                children: None,
-                            }
-                        })
+                        .map(document_symbol_from_information)
                         .collect(),
                     true,
                 ))
@@ -558,8 +2126,1495 @@ impl<L: Language> LspServer<L> {
 
 impl<L: Language> Drop for LspServer<L> {
     fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        if let Err(e) = self.close_all() {
+            tracing::warn!("Error closing documents in drop: {}", e);
+        }
         if let Err(e) = self.stop() {
             tracing::error!("Error stopping LSP server in drop: {}", e);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_client_capabilities_include_hierarchical_symbols() {
+        let capabilities = to_value(client_capabilities()).unwrap();
+
+        assert_eq!(
+            capabilities["textDocument"]["documentSymbol"]["hierarchicalDocumentSymbolSupport"],
+            serde_json::json!(true)
+        );
+        assert_eq!(
+            capabilities["textDocument"]["callHierarchy"]["dynamicRegistration"],
+            serde_json::json!(false)
+        );
+        assert_eq!(
+            capabilities["textDocument"]["typeHierarchy"]["dynamicRegistration"],
+            serde_json::json!(false)
+        );
+        assert_eq!(
+            capabilities["textDocument"]["synchronization"]["willSaveWaitUntil"],
+            serde_json::json!(true)
+        );
+        assert_eq!(
+            capabilities["textDocument"]["inlayHint"]["resolveSupport"]["properties"],
+            serde_json::json!(["tooltip", "textEdits"])
+        );
+        assert_eq!(
+            capabilities["general"]["positionEncodings"],
+            serde_json::json!(["utf-8", "utf-16"])
+        );
+    }
+
+    #[test]
+    fn test_find_in_path() -> Result<()> {
+        let first = tempfile::TempDir::new()?;
+        let second = tempfile::TempDir::new()?;
+        std::fs::write(second.path().join("intelephense.cmd"), "")?;
+        std::fs::write(first.path().join("intelephense"), "")?;
+        let path_var = std::env::join_paths([first.path(), second.path()])?;
+
+        assert_eq!(
+            find_in_path("intelephense", &path_var, &["exe", "cmd"]),
+            Some(second.path().join("intelephense.cmd"))
+        );
+        assert_eq!(find_in_path("gopls", &path_var, &["exe", "cmd"]), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_locations_normalizes_links() {
+        let uri: Uri = "file:///project/header.h".parse().unwrap();
+        let range = |line| lsp_types::Range {
+            start: Position { line, character: 4 },
+            end: Position { line, character: 7 },
+        };
+        let location = Location {
+            uri: uri.clone(),
+            range: range(2),
+        };
+
+        let scalar = response_locations(GotoDefinitionResponse::Scalar(location.clone()));
+        assert_eq!(scalar, vec![location.clone()]);
+
+        let links = response_locations(GotoDefinitionResponse::Link(vec![
+            lsp_types::LocationLink {
+                origin_selection_range: None,
+                target_uri: uri.clone(),
+                target_range: range(1),
+                target_selection_range: range(2),
+            },
+        ]));
+        assert_eq!(links, vec![location]);
+    }
+
+    #[test]
+    fn test_document_symbol_from_information() {
+        let range = lsp_types::Range {
+            start: Position::new(4, 4),
+            end: Position::new(8, 5),
+        };
+        #[allow(deprecated)]
+        let information = SymbolInformation {
+            name: "calculate".to_string(),
+            kind: lsp_types::SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: "file:///project/main.py".parse().unwrap(),
+                range,
+            },
+            container_name: None,
+        };
+
+        let symbol = document_symbol_from_information(information);
+
+        assert_eq!(symbol.name, "calculate");
+        assert_eq!(symbol.kind, lsp_types::SymbolKind::FUNCTION);
+        assert_eq!(symbol.range, range);
+        assert_eq!(symbol.selection_range.start, Position::new(4, 4));
+        assert_eq!(symbol.selection_range.end, Position::new(4, 13));
+        assert!(symbol.children.is_none());
+    }
+
+    #[test]
+    fn test_get_callable_symbols() -> Result<()> {
+        let symbol = |name: &str, kind: u32, children: Value| {
+            let range = serde_json::json!({
+                "start": {"line": 0, "character": 0},
+                "end": {"line": 0, "character": 1}
+            });
+            serde_json::json!({
+                "name": name,
+                "kind": kind,
+                "range": range,
+                "selectionRange": range,
+                "children": children
+            })
+        };
+        // Kinds: 2 module, 5 class, 6 method, 7 property, 8 field, 9 constructor,
+        // 12 function, 13 variable
+        let symbols = serde_json::json!([
+            symbol(
+                "main",
+                12,
+                serde_json::json!([symbol("helper", 12, Value::Null)])
+            ),
+            symbol("COUNT", 13, Value::Null),
+            symbol(
+                "shapes",
+                2,
+                serde_json::json!([symbol(
+                    "Circle",
+                    5,
+                    serde_json::json!([
+                        symbol("radius", 8, Value::Null),
+                        symbol("diameter", 7, Value::Null),
+                        symbol("new", 9, Value::Null),
+                        symbol("area", 6, Value::Null),
+                    ])
+                )])
+            ),
+        ]);
+        let stub =
+            crate::testing::StubServer::new().on_request("textDocument/documentSymbol", symbols);
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let path = Path::new("/project/src/main.rs");
+        let callables = server.get_callable_symbols(path, &CallableSymbolFilter::default())?;
+
+        let names: Vec<_> = callables
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("main", lsp_types::SymbolKind::FUNCTION),
+                ("helper", lsp_types::SymbolKind::FUNCTION),
+                ("new", lsp_types::SymbolKind::CONSTRUCTOR),
+                ("area", lsp_types::SymbolKind::METHOD),
+            ]
+        );
+        assert!(callables.iter().all(|symbol| symbol.children.is_none()));
+
+        let with_properties = CallableSymbolFilter {
+            kinds: [DEFAULT_CALLABLE_KINDS, &[SymbolKind::PROPERTY]].concat(),
+            ..Default::default()
+        };
+        let callables = server.get_callable_symbols(path, &with_properties)?;
+        let names: Vec<_> = callables
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, ["main", "helper", "diameter", "new", "area"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callable_symbols_in_interfaces() -> Result<()> {
+        let symbol = |name: &str, line: u32, kind: SymbolKind, children| {
+            let range = lsp_types::Range::new(Position::new(line, 0), Position::new(line, 4));
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: name.to_string(),
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children,
+            }
+        };
+        // A default method, like `default void log() { print(name()); }` in Java
+        let symbols = vec![symbol(
+            "Named",
+            0,
+            SymbolKind::INTERFACE,
+            Some(vec![
+                symbol("name", 1, SymbolKind::METHOD, None),
+                symbol("log", 2, SymbolKind::METHOD, None),
+            ]),
+        )];
+        let names = |filter: &CallableSymbolFilter| -> Vec<String> {
+            callable_symbols(symbols.clone(), filter)
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect()
+        };
+
+        assert_eq!(names(&CallableSymbolFilter::default()), ["name", "log"]);
+        let skip_interfaces = CallableSymbolFilter {
+            skip_interfaces: true,
+            ..Default::default()
+        };
+        assert!(names(&skip_interfaces).is_empty());
+
+        // The calls inside the default method are found through its call hierarchy
+        let from_range = serde_json::json!([{"start": {"line": 2, "character": 30}, "end": {"line": 2, "character": 36}}]);
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "textDocument/prepareCallHierarchy",
+                serde_json::json!([call_hierarchy_item("name", 1)]),
+            )
+            .on_request(
+                "textDocument/prepareCallHierarchy",
+                serde_json::json!([call_hierarchy_item("log", 2)]),
+            )
+            .on_request("callHierarchy/outgoingCalls", serde_json::json!([]))
+            .on_request(
+                "callHierarchy/outgoingCalls",
+                serde_json::json!([{"to": call_hierarchy_item("name", 1), "fromRanges": from_range}]),
+            );
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let path = Path::new("/project/main.rs");
+        let mut calls = Vec::new();
+        for symbol in callable_symbols(symbols.clone(), &CallableSymbolFilter::default()) {
+            let item = server
+                .prepare_call_hierarchy(path, symbol.selection_range.start, RetryPolicy::NONE)?
+                .unwrap();
+            for call in server.outgoing_calls(item)? {
+                calls.push((symbol.name.clone(), call.to.name));
+            }
+        }
+        assert_eq!(calls, [("log".to_string(), "name".to_string())]);
+        let prepares = stub.received_with_method("textDocument/prepareCallHierarchy");
+        assert_eq!(prepares[1]["params"]["position"]["line"], 2);
+        let outgoing = stub.received_with_method("callHierarchy/outgoingCalls");
+        assert_eq!(outgoing[1]["params"]["item"]["name"], "log");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_files_tracks_open_documents() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files: Vec<(PathBuf, String)> = ["a.rs", "b.rs", "c.rs"]
+            .iter()
+            .map(|name| (temp_dir.path().join(name), "fn main() {}".to_string()))
+            .collect();
+
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, temp_dir.path().to_path_buf());
+
+        server.open_files(&files)?;
+        for (path, _) in &files {
+            assert!(server.is_open(path));
+        }
+
+        server.close_file(&files[0].0)?;
+        assert!(!server.is_open(&files[0].0));
+        assert!(server.is_open(&files[1].0));
+
+        server.close_all()?;
+        for (path, _) in &files {
+            assert!(!server.is_open(path));
+        }
+
+        // A round trip guarantees the stub has seen all earlier notifications
+        server.request::<lsp_types::request::Shutdown>(())?;
+        assert_eq!(stub.received_with_method("textDocument/didOpen").len(), 3);
+        assert_eq!(stub.received_with_method("textDocument/didClose").len(), 3);
+
+        Ok(())
+    }
+
+    /// Counts the writes and flushes to the server's stdin
+    #[derive(Clone, Default)]
+    struct CountingWriter {
+        writes: Arc<AtomicUsize>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Collects the bytes written to the server's stdin and counts the flushes
+    struct SharedBuffer {
+        buffer: Arc<Mutex<Vec<u8>>>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buffer.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bulk_open_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files: Vec<(PathBuf, String)> = (0..200)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{i}.rs"));
+                (path, format!("fn function{i}() {{}}\n"))
+            })
+            .collect();
+
+        let open_with = |bulk: bool| -> Result<(usize, usize, Duration)> {
+            let writer = CountingWriter::default();
+            let mut server = LspServer::from_streams(
+                crate::RustLang,
+                temp_dir.path().to_path_buf(),
+                std::io::empty(),
+                writer.clone(),
+            );
+            let start_time = std::time::Instant::now();
+            if bulk {
+                server.bulk_open_files(&files)?;
+            } else {
+                server.open_files(&files)?;
+            }
+            let elapsed = start_time.elapsed();
+            assert!(files.iter().all(|(path, _)| server.is_open(path)));
+            Ok((
+                writer.writes.load(Ordering::SeqCst),
+                writer.flushes.load(Ordering::SeqCst),
+                elapsed,
+            ))
+        };
+
+        let (sequential_writes, sequential_flushes, sequential_time) = open_with(false)?;
+        let (bulk_writes, bulk_flushes, bulk_time) = open_with(true)?;
+        tracing::debug!("Sequential: {sequential_time:?}, bulk: {bulk_time:?}");
+        // Timings are too noisy to compare, the number of system calls is not
+        assert_eq!(sequential_flushes, 200);
+        assert_eq!(bulk_flushes, 1);
+        assert!(
+            bulk_writes * 10 < sequential_writes,
+            "{bulk_writes} writes in bulk, {sequential_writes} sequentially"
+        );
+
+        Ok(())
+    }
+
+    /// A server stdin that fails every write, like a pipe to a server that exited
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_bulk_open_files_rolls_back_on_write_error() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files = vec![
+            (
+                temp_dir.path().join("small.rs"),
+                "fn small() {}\n".to_string(),
+            ),
+            (
+                temp_dir.path().join("large.rs"),
+                "// padding\n".repeat(2000),
+            ),
+        ];
+        let mut server = LspServer::from_streams(
+            crate::RustLang,
+            temp_dir.path().to_path_buf(),
+            std::io::empty(),
+            BrokenPipe,
+        );
+
+        assert!(server.bulk_open_files(&files).is_err());
+        assert!(files.iter().all(|(path, _)| !server.is_open(path)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_open_files_sends_nothing_for_invalid_path() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files = vec![
+            (temp_dir.path().join("main.rs"), "fn main() {}".to_string()),
+            (PathBuf::from("relative.rs"), "fn relative() {}".to_string()),
+        ];
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, temp_dir.path().to_path_buf());
+
+        assert!(server.bulk_open_files(&files).is_err());
+        assert!(!server.is_open(&files[0].0));
+        // The stub handles messages in order, so the reply means it has seen
+        // everything sent before
+        server.request::<lsp_types::request::Shutdown>(())?;
+        assert!(stub.received_with_method("textDocument/didOpen").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_files_parallel() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut files: Vec<(PathBuf, String)> = (0..200)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{i}.rs"));
+                let body = format!("    println!(\"line {i}\\t\\\"quoted\\\"\");\n").repeat(500);
+                (path, format!("fn function{i}() {{\n{body}}}\n"))
+            })
+            .collect();
+        // Opened only once
+        files.push(files[0].clone());
+
+        let open_with = |parallelism: Option<usize>| -> Result<(Vec<u8>, usize, Duration)> {
+            let buffer = Arc::new(Mutex::new(Vec::new()));
+            let flushes = Arc::new(AtomicUsize::new(0));
+            let mut server = LspServer::from_streams(
+                crate::RustLang,
+                temp_dir.path().to_path_buf(),
+                std::io::empty(),
+                SharedBuffer {
+                    buffer: buffer.clone(),
+                    flushes: flushes.clone(),
+                },
+            );
+            let start_time = std::time::Instant::now();
+            match parallelism {
+                Some(parallelism) => server.open_files_parallel(files.clone(), parallelism)?,
+                None => server.open_files(&files)?,
+            }
+            let elapsed = start_time.elapsed();
+            assert!(files.iter().all(|(path, _)| server.is_open(path)));
+            let written = buffer.lock().unwrap().clone();
+            Ok((written, flushes.load(Ordering::SeqCst), elapsed))
+        };
+
+        let messages = |written: Vec<u8>| -> Result<Vec<String>> {
+            let mut reader = written.as_slice();
+            let mut messages = Vec::new();
+            while let Some(body) = read_message(&mut reader)? {
+                messages.push(String::from_utf8(body)?);
+            }
+            messages.sort();
+            Ok(messages)
+        };
+
+        let (sequential, _, sequential_time) = open_with(None)?;
+        let (parallel, parallel_flushes, parallel_time) = open_with(Some(4))?;
+        tracing::debug!("Sequential: {sequential_time:?}, parallel: {parallel_time:?}");
+        assert_eq!(parallel_flushes, 1);
+        let sequential = messages(sequential)?;
+        assert_eq!(sequential.len(), 200);
+        assert_eq!(messages(parallel)?, sequential);
+
+        // A single worker thread opens the files too
+        let (single, _, _) = open_with(Some(1))?;
+        assert_eq!(messages(single)?, sequential);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_files_parallel_opens_nothing_for_invalid_path() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let main_path = temp_dir.path().join("main.rs");
+        let files = vec![
+            (main_path.clone(), "fn main() {}".to_string()),
+            (PathBuf::from("relative.rs"), "fn relative() {}".to_string()),
+        ];
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, temp_dir.path().to_path_buf());
+
+        assert!(server.open_files_parallel(files, 2).is_err());
+        assert!(!server.is_open(&main_path));
+        server.request::<lsp_types::request::Shutdown>(())?;
+        assert!(stub.received_with_method("textDocument/didOpen").is_empty());
+
+        // The file is not skipped as already open when it is opened again
+        server.open_file(&main_path, "fn main() {}")?;
+        server.request::<lsp_types::request::Shutdown>(())?;
+        assert_eq!(stub.received_with_method("textDocument/didOpen").len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_file_sends_change_and_auto_save() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let file_path = temp_dir.path().join("main.rs");
+        std::fs::write(&file_path, "fn main() { foo(); }")?;
+
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, temp_dir.path().to_path_buf());
+        server.config.auto_save_on_update = true;
+
+        server.open_file(&file_path, "fn main() {}")?;
+        server.update_file(&file_path, "fn main() { foo(); }")?;
+        server.save_file(&file_path, true)?;
+
+        server.request::<lsp_types::request::Shutdown>(())?;
+        let changes = stub.received_with_method("textDocument/didChange");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["params"]["textDocument"]["version"], 2);
+        let saves = stub.received_with_method("textDocument/didSave");
+        assert_eq!(saves.len(), 2);
+        assert!(saves[0]["params"].get("text").is_none());
+        assert_eq!(saves[1]["params"]["text"], "fn main() { foo(); }");
+
+        Ok(())
+    }
+
+    /// Yields at most one pipe's worth of bytes per read and counts the reads
+    struct PipeLikeReader<'a> {
+        bytes: &'a [u8],
+        reads: usize,
+    }
+
+    impl Read for PipeLikeReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            let n = buf.len().min(self.bytes.len()).min(64 * 1024);
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_stdout_buffer_capacity() -> Result<()> {
+        // A burst of reference responses of a few kilobytes each
+        let locations: Vec<_> = (0..40)
+            .map(|line| {
+                serde_json::json!({
+                    "uri": "file:///project/src/lib.rs",
+                    "range": {
+                        "start": {"line": line, "character": 4},
+                        "end": {"line": line, "character": 10}
+                    }
+                })
+            })
+            .collect();
+        let stream: String = (0..500)
+            .map(|id| {
+                request_string(
+                    &serde_json::json!({"jsonrpc": "2.0", "id": id, "result": locations}),
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        let read_all = |capacity| -> Result<(usize, Duration)> {
+            let start_time = std::time::Instant::now();
+            let mut reader = BufReader::with_capacity(
+                capacity,
+                PipeLikeReader {
+                    bytes: stream.as_bytes(),
+                    reads: 0,
+                },
+            );
+            let mut messages = 0;
+            while read_message(&mut reader)?.is_some() {
+                messages += 1;
+            }
+            assert_eq!(messages, 500);
+            Ok((reader.get_ref().reads, start_time.elapsed()))
+        };
+
+        let (small_reads, small_time) = read_all(8 * 1024)?;
+        let default_capacity = LspServerConfig::default().stdout_buffer_capacity;
+        let (large_reads, large_time) = read_all(default_capacity)?;
+        tracing::debug!("8KB buffer: {small_time:?}, default buffer: {large_time:?}");
+        // Timings are too noisy to compare, the number of reads is not
+        assert!(
+            large_reads * 4 < small_reads,
+            "{large_reads} reads with the default buffer, {small_reads} with 8KB"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_type_filter() {
+        let min = LspServerConfig::default().min_message_type;
+        assert!(is_message_type_shown(MessageType::ERROR, min));
+        assert!(is_message_type_shown(MessageType::WARNING, min));
+        assert!(!is_message_type_shown(MessageType::INFO, min));
+        assert!(!is_message_type_shown(MessageType::LOG, min));
+        assert!(is_message_type_shown(MessageType::LOG, MessageType::LOG));
+    }
+
+    #[test]
+    fn test_request_with_retry_retries_transient_errors() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request_error(
+                "textDocument/references",
+                LspResponseError::CONTENT_MODIFIED,
+                "content modified",
+            )
+            .on_request_error(
+                "textDocument/references",
+                LspResponseError::SERVER_CANCELLED,
+                "server cancelled",
+            )
+            .on_request("textDocument/references", serde_json::json!([]))
+            .on_request_error("textDocument/hover", -32603, "internal error");
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let position =
+            text_document_position_params(Path::new("/project/main.rs"), Position::new(0, 0))?;
+
+        let references = server.request_with_retry::<lsp_types::request::References>(
+            lsp_types::ReferenceParams {
+                text_document_position: position.clone(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: lsp_types::ReferenceContext {
+                    include_declaration: true,
+                },
+            },
+            3,
+            Duration::from_millis(1),
+        )?;
+        assert_eq!(references, Some(vec![]));
+        assert_eq!(
+            stub.received_with_method("textDocument/references").len(),
+            3
+        );
+
+        let error = server
+            .request_with_retry::<lsp_types::request::HoverRequest>(
+                lsp_types::HoverParams {
+                    text_document_position_params: position,
+                    work_done_progress_params: Default::default(),
+                },
+                3,
+                Duration::from_millis(1),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<LspResponseError>().map(|e| e.code),
+            Some(-32603)
+        );
+        assert_eq!(stub.received_with_method("textDocument/hover").len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_helpers_retry_content_modified() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request_error(
+                "textDocument/references",
+                LspResponseError::CONTENT_MODIFIED,
+                "content modified",
+            )
+            .on_request("textDocument/references", serde_json::json!([]))
+            .on_request_error(
+                "callHierarchy/outgoingCalls",
+                LspResponseError::CONTENT_MODIFIED,
+                "content modified",
+            )
+            .on_request("callHierarchy/outgoingCalls", serde_json::json!([]));
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        server.config.transient_error_delay = Duration::from_millis(1);
+
+        let references = server.request_retrying::<lsp_types::request::References>(
+            lsp_types::ReferenceParams {
+                text_document_position: text_document_position_params(
+                    Path::new("/project/main.rs"),
+                    Position::new(0, 0),
+                )?,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: lsp_types::ReferenceContext {
+                    include_declaration: true,
+                },
+            },
+        )?;
+        assert_eq!(references, Some(vec![]));
+        assert_eq!(
+            stub.received_with_method("textDocument/references").len(),
+            2
+        );
+
+        let item = serde_json::from_value(call_hierarchy_item("main", 0))?;
+        assert!(server.outgoing_calls(item)?.is_empty());
+        assert_eq!(
+            stub.received_with_method("callHierarchy/outgoingCalls")
+                .len(),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_hierarchy_requests() -> Result<()> {
+        let item = |name: &str, line: u32| {
+            serde_json::json!({
+                "name": name,
+                "kind": 5,
+                "uri": "file:///project/main.rs",
+                "range": {"start": {"line": line, "character": 0}, "end": {"line": line, "character": 10}},
+                "selectionRange": {"start": {"line": line, "character": 7}, "end": {"line": line, "character": 10}},
+            })
+        };
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "textDocument/prepareTypeHierarchy",
+                serde_json::json!([item("Dog", 4)]),
+            )
+            .on_request(
+                "typeHierarchy/supertypes",
+                serde_json::json!([item("Animal", 0)]),
+            )
+            .on_request("typeHierarchy/subtypes", serde_json::Value::Null);
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+
+        let items =
+            server.prepare_type_hierarchy(Path::new("/project/main.rs"), Position::new(4, 8))?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "Dog");
+
+        let supertypes = server.type_hierarchy_supertypes(items[0].clone())?;
+        assert_eq!(supertypes[0].name, "Animal");
+        assert_eq!(supertypes[0].selection_range.start, Position::new(0, 7));
+        // A null response means there are no subtypes
+        assert_eq!(server.type_hierarchy_subtypes(items[0].clone())?, vec![]);
+
+        let request = &stub.received_with_method("typeHierarchy/supertypes")[0];
+        assert_eq!(request["params"]["item"]["name"], "Dog");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_capabilities_from_config() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        server.config.client_capabilities = Some(
+            ClientCapabilitiesBuilder::empty()
+                .with_semantic_tokens()
+                .with_pull_diagnostics()
+                .build(),
+        );
+        server.initialize()?;
+
+        let capabilities = &stub.received_with_method("initialize")[0]["params"]["capabilities"];
+        let text_document = &capabilities["textDocument"];
+        assert_eq!(text_document["semanticTokens"]["requests"]["full"], true);
+        assert_eq!(text_document["diagnostic"]["relatedDocumentSupport"], true);
+        assert!(text_document.get("callHierarchy").is_none());
+
+        // The default capabilities are extended rather than replaced
+        let capabilities = ClientCapabilitiesBuilder::new()
+            .with_semantic_tokens()
+            .build();
+        let text_document = capabilities.text_document.unwrap();
+        assert!(text_document.semantic_tokens.is_some());
+        assert!(text_document.call_hierarchy.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_initialize_with_multiple_workspace_folders() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project/core"));
+        server.initialize_with_folders(&[
+            PathBuf::from("/project/core"),
+            PathBuf::from("/project/cli"),
+        ])?;
+
+        let initialize = &stub.received_with_method("initialize")[0];
+        assert_eq!(
+            initialize["params"]["workspaceFolders"],
+            serde_json::json!([
+                {"uri": "file:///project/core", "name": "core"},
+                {"uri": "file:///project/cli", "name": "cli"},
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_request() -> Result<()> {
+        let status = serde_json::json!("Workspaces: 1, loaded crates: 42");
+        let stub = crate::testing::StubServer::new()
+            .on_request("rust-analyzer/analyzerStatus", status.clone())
+            .on_request_error("experimental/unknown", -32601, "Method not found");
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+        let params = serde_json::json!({"textDocument": {"uri": "file:///project/src/main.rs"}});
+
+        assert_eq!(
+            server.raw_request("rust-analyzer/analyzerStatus", params.clone())?,
+            status
+        );
+        let request = &stub.received_with_method("rust-analyzer/analyzerStatus")[0];
+        assert_eq!(request["params"], params);
+
+        let error = server
+            .raw_request("experimental/unknown", Value::Null)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<LspResponseError>().map(|e| e.code),
+            Some(-32601)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_batched_response() -> Result<()> {
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "$/progress",
+            "params": {"token": "indexing", "value": {"kind": "end"}}
+        });
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": [{"name": "main"}]});
+        let batch = serde_json::json!([notification, response]);
+        let mut server = LspServer::from_streams(
+            crate::RustLang,
+            PathBuf::from("/project"),
+            std::io::Cursor::new(request_string(&batch)?),
+            std::io::sink(),
+        );
+
+        let mut notifications = Vec::new();
+        let message =
+            server.read_response_observing(1, |message| notifications.push(message.clone()))?;
+        assert_eq!(message, response);
+        assert_eq!(notifications, [notification]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_with_progress() -> Result<()> {
+        let progress = |token: &str, value: Value| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "$/progress",
+                "params": { "token": token, "value": value }
+            })
+        };
+        let stub = crate::testing::StubServer::new()
+            .on_request("workspace/symbol", serde_json::json!([]))
+            .with_message_before_reply(
+                "workspace/symbol",
+                progress(
+                    "symbols",
+                    serde_json::json!({"kind": "begin", "title": "Searching"}),
+                ),
+            )
+            .with_message_before_reply(
+                "workspace/symbol",
+                progress(
+                    "indexing",
+                    serde_json::json!({"kind": "report", "percentage": 10}),
+                ),
+            )
+            .with_message_before_reply(
+                "workspace/symbol",
+                progress(
+                    "symbols",
+                    serde_json::json!({"kind": "report", "percentage": 50}),
+                ),
+            )
+            .with_message_before_reply(
+                "workspace/symbol",
+                progress("symbols", serde_json::json!({"kind": "end"})),
+            );
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let mut reported = Vec::new();
+        let symbols = server.request_with_progress::<lsp_types::request::WorkspaceSymbolRequest>(
+            lsp_types::WorkspaceSymbolParams {
+                query: "main".to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            "symbols",
+            |progress| reported.push(progress),
+        )?;
+
+        assert_eq!(
+            symbols,
+            Some(lsp_types::WorkspaceSymbolResponse::Flat(vec![]))
+        );
+        let request = &stub.received_with_method("workspace/symbol")[0];
+        assert_eq!(request["params"]["workDoneToken"], "symbols");
+        assert_eq!(request["params"]["query"], "main");
+        assert!(matches!(
+            reported.as_slice(),
+            [
+                WorkDoneProgress::Begin(begin),
+                WorkDoneProgress::Report(report),
+                WorkDoneProgress::End(_),
+            ] if begin.title == "Searching" && report.percentage == Some(50)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_configuration() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start_and_init(crate::GoLang, PathBuf::from("/project"))?;
+        let settings = serde_json::json!({"gopls": {"analyses": {"unusedparams": true}}});
+        server.change_configuration(settings.clone())?;
+        // A request makes sure the stub has received the notification
+        server.request::<lsp_types::request::Shutdown>(())?;
+
+        let notifications = stub.received_with_method("workspace/didChangeConfiguration");
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0]["params"]["settings"], settings);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_workspace_configuration() -> Result<()> {
+        let stub = crate::testing::StubServer::new().with_message_before_reply(
+            "shutdown",
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "workspace/configuration",
+                "params": { "items": [{ "section": "gopls.analyses" }, { "section": "python" }] }
+            }),
+        )
+        .with_message_before_reply(
+            "shutdown",
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "client/unknownRequest",
+                "params": {}
+            }),
+        );
+        let mut server = stub.start_and_init(crate::GoLang, PathBuf::from("/project"))?;
+        let settings = serde_json::json!({"gopls": {"analyses": {"unusedparams": true}}});
+        server.update_configuration(settings.clone())?;
+
+        assert_eq!(server.config_values, settings);
+        assert_eq!(
+            server.get_workspace_configuration("gopls.analyses"),
+            serde_json::json!({"unusedparams": true})
+        );
+        assert_eq!(server.get_workspace_configuration(""), settings);
+        assert!(
+            server
+                .get_workspace_configuration("gopls.missing")
+                .is_null()
+        );
+
+        // The request of the server is answered while waiting for the response
+        server.request::<lsp_types::request::Shutdown>(())?;
+        let notifications = stub.received_with_method("workspace/didChangeConfiguration");
+        assert_eq!(notifications[0]["params"]["settings"], settings);
+        // The stub replies to the shutdown before it reads the answers
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let reply_to = |id: i64| loop {
+            let reply = stub
+                .received_messages()
+                .into_iter()
+                .find(|message| message["id"] == id && message.get("method").is_none());
+            if let Some(reply) = reply {
+                break reply;
+            }
+            assert!(
+                std::time::Instant::now() < deadline,
+                "The request {id} of the server was not answered"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(
+            reply_to(1)["result"],
+            serde_json::json!([{"unusedparams": true}, null])
+        );
+        // Unknown requests are declined rather than left waiting
+        assert_eq!(
+            reply_to(2)["error"]["code"],
+            LspResponseError::METHOD_NOT_FOUND
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_workspace_folders() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project/core"))?;
+        let folder = |path: &str, name: &str| -> Result<WorkspaceFolder> {
+            Ok(WorkspaceFolder {
+                uri: path.parse()?,
+                name: name.to_string(),
+            })
+        };
+        server.change_workspace_folders(
+            vec![folder("file:///project/cli", "cli")?],
+            vec![folder("file:///project/core", "core")?],
+        )?;
+        // A request makes sure the stub has received the notification
+        server.request::<lsp_types::request::Shutdown>(())?;
+
+        let notifications = stub.received_with_method("workspace/didChangeWorkspaceFolders");
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(
+            notifications[0]["params"]["event"],
+            serde_json::json!({
+                "added": [{"uri": "file:///project/cli", "name": "cli"}],
+                "removed": [{"uri": "file:///project/core", "name": "core"}],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_moniker() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "textDocument/moniker",
+                serde_json::json!([{
+                    "scheme": "tsc",
+                    "identifier": "lib/parser:Parser.parse",
+                    "unique": "project",
+                    "kind": "export"
+                }]),
+            )
+            .on_request("textDocument/moniker", serde_json::Value::Null);
+        let mut server = stub.start(crate::TypeScriptLang, PathBuf::from("/project"));
+
+        let monikers = server.moniker(Path::new("/project/lib/parser.ts"), Position::new(3, 9))?;
+        assert_eq!(monikers.len(), 1);
+        assert_eq!(monikers[0].scheme, "tsc");
+        assert_eq!(monikers[0].identifier, "lib/parser:Parser.parse");
+        assert_eq!(monikers[0].kind, Some(lsp_types::MonikerKind::Export));
+        let request = &stub.received_with_method("textDocument/moniker")[0];
+        assert_eq!(request["params"]["position"]["line"], 3);
+
+        // Symbols without monikers
+        assert!(
+            server
+                .moniker(Path::new("/project/lib/parser.ts"), Position::new(0, 0))?
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_inlay_hint() -> Result<()> {
+        let hint = serde_json::json!({
+            "position": {"line": 2, "character": 9},
+            "label": ": i32",
+            "kind": 1,
+            "data": {"id": 7},
+        });
+        let mut resolved = hint.clone();
+        resolved["tooltip"] = serde_json::json!("The type of `x`");
+        let stub = crate::testing::StubServer::new().on_request("inlayHint/resolve", resolved);
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+
+        let hint = server.resolve_inlay_hint(from_value(hint)?)?;
+        assert!(matches!(
+            hint.tooltip,
+            Some(lsp_types::InlayHintTooltip::String(tooltip)) if tooltip == "The type of `x`"
+        ));
+        let request = &stub.received_with_method("inlayHint/resolve")[0];
+        assert_eq!(request["params"]["data"], serde_json::json!({"id": 7}));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_inlay_hints_refresh() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request("workspace/inlayHint/refresh", serde_json::Value::Null);
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        server.request_inlay_hints_refresh()?;
+        assert_eq!(
+            stub.received_with_method("workspace/inlayHint/refresh")
+                .len(),
+            1
+        );
+
+        // Servers that do not implement the method are not an error
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        server.request_inlay_hints_refresh()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepare_call_hierarchy() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request("textDocument/prepareCallHierarchy", serde_json::Value::Null)
+            .on_request("textDocument/prepareCallHierarchy", serde_json::json!([]))
+            .on_request(
+                "textDocument/prepareCallHierarchy",
+                serde_json::json!([call_hierarchy_item("main", 0)]),
+            );
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let path = Path::new("/project/main.rs");
+
+        let item = server.prepare_call_hierarchy(path, Position::new(0, 3), RetryPolicy::NONE)?;
+        assert!(item.is_none());
+
+        let item = server
+            .prepare_call_hierarchy(
+                path,
+                Position::new(0, 3),
+                RetryPolicy::new(3, Duration::from_millis(1)),
+            )?
+            .unwrap();
+        assert_eq!(item.name, "main");
+        assert_eq!(
+            stub.received_with_method("textDocument/prepareCallHierarchy")
+                .len(),
+            3
+        );
+
+        Ok(())
+    }
+
+    /// A function call hierarchy item in `/project/main.rs` at `line`
+    fn call_hierarchy_item(name: &str, line: u32) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "kind": 12,
+            "uri": "file:///project/main.rs",
+            "range": {"start": {"line": line, "character": 0}, "end": {"line": line + 2, "character": 1}},
+            "selectionRange": {"start": {"line": line, "character": 3}, "end": {"line": line, "character": 7}},
+        })
+    }
+
+    #[test]
+    fn test_outgoing_and_incoming_calls() -> Result<()> {
+        let from_range = serde_json::json!([{"start": {"line": 1, "character": 4}, "end": {"line": 1, "character": 9}}]);
+        let stub = crate::testing::StubServer::new()
+            .on_request(
+                "callHierarchy/outgoingCalls",
+                serde_json::json!([{"to": call_hierarchy_item("helper", 5), "fromRanges": from_range}]),
+            )
+            .on_request("callHierarchy/incomingCalls", serde_json::Value::Null);
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let item: CallHierarchyItem = from_value(call_hierarchy_item("main", 0))?;
+
+        let outgoing = server.outgoing_calls(item.clone())?;
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to.name, "helper");
+        assert_eq!(outgoing[0].from_ranges[0].start, Position::new(1, 4));
+
+        assert!(server.incoming_calls(item)?.is_empty());
+        let request = &stub.received_with_method("callHierarchy/incomingCalls")[0];
+        assert_eq!(request["params"]["item"]["name"], "main");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_will_save_file() -> Result<()> {
+        let stub = crate::testing::StubServer::new().on_request(
+            "textDocument/willSaveWaitUntil",
+            serde_json::json!([{
+                "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 4}},
+                "newText": "fn",
+            }]),
+        );
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let path = Path::new("/project/main.rs");
+
+        server.will_save_file(path, TextDocumentSaveReason::MANUAL)?;
+        let edits = server.will_save_wait_until_file(path, TextDocumentSaveReason::AFTER_DELAY)?;
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "fn");
+
+        let notification = &stub.received_with_method("textDocument/willSave")[0];
+        assert_eq!(
+            notification["params"]["textDocument"]["uri"],
+            "file:///project/main.rs"
+        );
+        assert_eq!(notification["params"]["reason"], 1);
+        let request = &stub.received_with_method("textDocument/willSaveWaitUntil")[0];
+        assert_eq!(request["params"]["reason"], 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_all_pairs_results_with_tags() -> Result<()> {
+        let stub = crate::testing::StubServer::new()
+            .on_request("textDocument/documentSymbol", serde_json::json!([]))
+            .on_request_error("textDocument/documentSymbol", -32603, "internal error");
+        let mut server = stub.start(crate::RustLang, PathBuf::from("/project"));
+        let requests = ["/project/a.rs", "/project/b.rs"]
+            .into_iter()
+            .map(|path| {
+                let params = DocumentSymbolParams {
+                    text_document: text_document_identifier_from_path(Path::new(path))?,
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                };
+                Ok((params, path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let results = server.request_all::<DocumentSymbolRequest, _>(requests);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "/project/a.rs");
+        assert!(results[0].1.as_ref().unwrap().is_some());
+        assert_eq!(results[1].0, "/project/b.rs");
+        assert!(results[1].1.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_workspace_edit_renames_symbol() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let lib_path = temp_dir.path().join("lib.rs");
+        let main_path = temp_dir.path().join("main.rs");
+        std::fs::write(&lib_path, "pub fn foo() {}\n")?;
+        std::fs::write(&main_path, "fn main() {\n    lib::foo();\n}\n")?;
+
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start(crate::RustLang, temp_dir.path().to_path_buf());
+        server.open_file(&main_path, "fn main() {\n    lib::foo();\n}\n")?;
+        let edit = |line, start, end, new_text| {
+            serde_json::json!({
+                "range": {
+                    "start": {"line": line, "character": start},
+                    "end": {"line": line, "character": end}
+                },
+                "newText": new_text,
+            })
+        };
+        let rename = |version| -> Result<WorkspaceEdit> {
+            Ok(from_value(serde_json::json!({
+                // Ignored since there are document changes
+                "changes": {
+                    to_file_uri(&lib_path)?.as_str(): [edit(0, 7, 10, "baz")],
+                },
+                "documentChanges": [
+                    {
+                        "textDocument": {"uri": to_file_uri(&lib_path)?.as_str(), "version": null},
+                        "edits": [edit(0, 7, 10, "bar")],
+                    },
+                    {
+                        "textDocument": {"uri": to_file_uri(&main_path)?.as_str(), "version": version},
+                        "edits": [edit(1, 9, 12, "bar")],
+                    },
+                    // Positions refer to the original content, not the one after the edit above
+                    {
+                        "textDocument": {"uri": to_file_uri(&main_path)?.as_str(), "version": version},
+                        "edits": [edit(1, 4, 7, "crate::lib")],
+                    },
+                ],
+            }))?)
+        };
+
+        let new_contents = server.apply_workspace_edit(&rename(1)?, true)?;
+        assert_eq!(new_contents[&lib_path], "pub fn bar() {}\n");
+        assert_eq!(
+            new_contents[&main_path],
+            "fn main() {\n    crate::lib::bar();\n}\n"
+        );
+        assert_eq!(std::fs::read_to_string(&lib_path)?, "pub fn foo() {}\n");
+
+        assert!(server.apply_workspace_edit(&rename(2)?, false).is_err());
+        assert_eq!(std::fs::read_to_string(&lib_path)?, "pub fn foo() {}\n");
+
+        server.apply_workspace_edit(&rename(1)?, false)?;
+        assert_eq!(std::fs::read_to_string(&lib_path)?, "pub fn bar() {}\n");
+        assert_eq!(
+            std::fs::read_to_string(&main_path)?,
+            "fn main() {\n    crate::lib::bar();\n}\n"
+        );
+
+        // Only the open document is updated in the server
+        server.request::<lsp_types::request::Shutdown>(())?;
+        let changes = stub.received_with_method("textDocument/didChange");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0]["params"]["textDocument"],
+            serde_json::json!({"uri": to_file_uri(&main_path)?.as_str(), "version": 2})
+        );
+        assert_eq!(
+            changes[0]["params"]["contentChanges"][0]["text"],
+            "fn main() {\n    crate::lib::bar();\n}\n"
+        );
+        let saves = stub.received_with_method("textDocument/didSave");
+        assert_eq!(saves.len(), 1);
+        assert_eq!(
+            saves[0]["params"]["textDocument"]["uri"],
+            to_file_uri(&main_path)?.as_str()
+        );
+
+        // Without document changes the changes are applied
+        let changes: WorkspaceEdit = from_value(serde_json::json!({
+            "changes": {
+                to_file_uri(&lib_path)?.as_str(): [edit(0, 7, 10, "baz")],
+            },
+        }))?;
+        let new_contents = server.apply_workspace_edit(&changes, true)?;
+        assert_eq!(new_contents[&lib_path], "pub fn baz() {}\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        let name = env!("CARGO_PKG_NAME");
+        assert_eq!(
+            expand_env_vars("$CARGO_PKG_NAME/log"),
+            format!("{name}/log")
+        );
+        assert_eq!(
+            expand_env_vars("${CARGO_PKG_NAME}_log"),
+            format!("{name}_log")
+        );
+        assert_eq!(expand_env_vars("cost: $$5"), "cost: $5");
+        assert_eq!(expand_env_vars("a $ b"), "a $ b");
+        assert_eq!(expand_env_vars("$TS_LSP_SURELY_UNSET_VARIABLE!"), "!");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_spawn_expands_env_vars() -> Result<()> {
+        // A fake server that replies with a single message echoing its environment
+        // and first argument
+        let script = r#"msg="{\"env\":\"$FAKE_SERVER_ENV\",\"arg\":\"$1\"}"; printf 'Content-Length: %d\r\n\r\n%s' ${#msg} "$msg""#;
+        let config = LspServerConfig {
+            args: vec!["$CARGO_PKG_NAME/log".to_string()],
+            env_vars: vec![(
+                "FAKE_SERVER_ENV".to_string(),
+                "${CARGO_PKG_NAME}-$$".to_string(),
+            )],
+            ..Default::default()
+        };
+        let args = ["-c".to_string(), script.to_string(), "sh".to_string()];
+        let server = LspServer::spawn(crate::RustLang, std::env::temp_dir(), "sh", &args, config)?;
+
+        let message = server.response_rx.recv_timeout(Duration::from_secs(5))??;
+        let name = env!("CARGO_PKG_NAME");
+        assert_eq!(message["env"], format!("{name}-$"));
+        assert_eq!(message["arg"], format!("{name}/log"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_is_idempotent() -> Result<()> {
+        let mut server = LspServer::spawn(
+            crate::RustLang,
+            std::env::temp_dir(),
+            "cat",
+            &[],
+            Default::default(),
+        )?;
+        assert!(server.is_alive());
+
+        server.stop()?;
+        assert!(server.stopped);
+        server.stop()?;
+        drop(server);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_until_ready_returns_when_progress_ends() -> Result<()> {
+        let progress = |kind: &str| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "$/progress",
+                "params": { "token": "indexing", "value": { "kind": kind, "title": "Indexing" } }
+            })
+        };
+        let stub = crate::testing::StubServer::new()
+            .with_message_before_reply(
+                "initialized",
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "create-1",
+                    "method": "window/workDoneProgress/create",
+                    "params": { "token": "indexing" }
+                }),
+            )
+            .with_message_before_reply("initialized", progress("begin"))
+            .with_message_before_reply("initialized", progress("end"));
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        let start = std::time::Instant::now();
+        assert!(server.wait_until_ready(Duration::from_secs(20))?);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // The progress token was accepted
+        server.request::<lsp_types::request::Shutdown>(())?;
+        assert!(
+            stub.received_messages()
+                .iter()
+                .any(|message| message["id"] == "create-1" && message["result"].is_null())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_until_ready_times_out_without_progress() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start_and_init(crate::PythonLang, PathBuf::from("/project"))?;
+
+        assert!(!server.wait_until_ready(Duration::from_millis(100))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_injected_notifications_are_read_first() -> Result<()> {
+        let stub = crate::testing::StubServer::new();
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        server.inject_notification_for_test(
+            "window/logMessage",
+            serde_json::json!({ "type": 3, "message": "Loading workspace" }),
+        );
+        // The request skips over the injected notification to find its response
+        server.request::<lsp_types::request::Shutdown>(())?;
+
+        for kind in ["begin", "end"] {
+            server.inject_notification_for_test(
+                "$/progress",
+                serde_json::json!({ "token": "load", "value": { "kind": kind, "title": "Load" } }),
+            );
+        }
+        assert_eq!(server.read_response()?["params"]["value"]["kind"], "begin");
+        assert!(server.wait_until_ready(Duration::from_secs(20))?);
+
+        Ok(())
+    }
+}
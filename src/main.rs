@@ -6,10 +6,13 @@ use lsp_types::{
 use std::env;
 use std::path::PathBuf;
 use tree_sitter_lsp_experiment::{
-    FileSearchConfig, GoLang, Language, LspServer, PythonLang, RustLang, SwiftLang, TypeScriptLang,
-    lsp::uri_from_path,
+    FileSearchConfig, GoLang, Language, LspServer, PhpLang, PythonLang, RustLang, SwiftLang,
+    TypeScriptLang, uri::to_file_uri,
 };
 
+/// The languages accepted by `--language`, the same as for the other binaries
+const SUPPORTED_LANGUAGES: &str = "rust, python, typescript, go, swift, php";
+
 fn start<L: Language + Copy>(language: L, project_path: PathBuf) -> Result<()> {
     tracing::info!(
         "Starting LSP experiment with project: {} (Language: {}, Extensions: {})",
@@ -44,10 +47,8 @@ fn start<L: Language + Copy>(language: L, project_path: PathBuf) -> Result<()> {
     tracing::info!("Opening document: {}", &file_path.display());
     lsp_server.open_file(&file_path, &file_content)?;
 
-    // Give rust-analyzer time to index the project
     // rust-analyzer needs to load and analyze the project which can take a moment
-    tracing::info!("Waiting for LSP server to index the project...");
-    std::thread::sleep(std::time::Duration::from_secs(3));
+    lsp_server.wait_for_indexing()?;
 
     tracing::info!(
         "Requesting definition at {}:8:18 (the 'add' function call)",
@@ -57,7 +58,7 @@ fn start<L: Language + Copy>(language: L, project_path: PathBuf) -> Result<()> {
     let definition_params = GotoDefinitionParams {
         text_document_position_params: TextDocumentPositionParams {
             text_document: TextDocumentIdentifier {
-                uri: uri_from_path(&file_path)?,
+                uri: to_file_uri(&file_path)?,
             },
             // Line 7 (0-indexed) is: "let result = add(x, y);"
             // Character 17 is on the 'a' in 'add'
@@ -73,15 +74,7 @@ fn start<L: Language + Copy>(language: L, project_path: PathBuf) -> Result<()> {
     tracing::info!("Requesting definition...");
 
     // Retry the request if rust-analyzer reports "content modified"
-    let definition_response = lsp_server.request::<GotoDefinition>(definition_params.clone())?;
-    // {
-    //     Ok(response) => response,
-    //     Err(e) if e.to_string().contains("content modified") => {
-    //         tracing::debug!("Got 'content modified' error, retrying in 500ms...");
-    //         panic!("Got 'content modified' error, retrying in 500ms...");
-    //     }
-    //     Err(e) => return Err(e),
-    // };
+    let definition_response = lsp_server.request_retrying::<GotoDefinition>(definition_params)?;
 
     if let Some(response) = definition_response {
         tracing::info!("Definition response: {:#?}", response);
@@ -107,7 +100,7 @@ fn main() -> Result<()> {
 
     if args.len() < 2 {
         eprintln!("Usage: {} <project_path> --language <language>", args[0]);
-        eprintln!("Supported languages: rust, python, typescript, go");
+        eprintln!("Supported languages: {}", SUPPORTED_LANGUAGES);
         std::process::exit(1);
     }
 
@@ -155,7 +148,7 @@ fn main() -> Result<()> {
         None => {
             eprintln!("Error: --language argument is required");
             eprintln!("Usage: {} <project_path> --language <language>", args[0]);
-            eprintln!("Supported languages: rust, python, typescript, go");
+            eprintln!("Supported languages: {}", SUPPORTED_LANGUAGES);
             std::process::exit(1);
         }
     };
@@ -171,6 +164,8 @@ fn main() -> Result<()> {
             project_path.display()
         );
     }
+    // File URIs can only be created for absolute paths
+    let project_path = project_path.canonicalize()?;
 
     match language.as_str() {
         "rust" => start(RustLang, project_path)?,
@@ -178,7 +173,12 @@ fn main() -> Result<()> {
         "typescript" => start(TypeScriptLang, project_path)?,
         "go" => start(GoLang, project_path)?,
         "swift" => start(SwiftLang, project_path)?,
-        lang => anyhow::bail!("Unsupported language: {}.", lang),
+        "php" => start(PhpLang, project_path)?,
+        lang => anyhow::bail!(
+            "Unsupported language: {}. Supported languages: {}",
+            lang,
+            SUPPORTED_LANGUAGES
+        ),
     };
 
     Ok(())
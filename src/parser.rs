@@ -6,9 +6,11 @@ use anyhow::Result;
 use std::fmt::Display;
 use std::fs;
 use std::path::Path;
-use tree_sitter::{Node, Parser, Tree, TreeCursor};
+use tree_sitter::{InputEdit, Node, Parser, Point, Tree, TreeCursor};
 
-use crate::{call_node::CallNode, language::Language};
+use crate::call_node::CallNode;
+use crate::language::{ErrorRecoveryStrategy, Language};
+use crate::location::{lsp_position_to_ts_point, ts_point_to_byte_offset, ts_point_to_display};
 
 /// Parses source code content using Tree Sitter for the specified language
 pub fn parse_file_content(source_code: &str, language: impl Language) -> Result<Tree> {
@@ -34,6 +36,77 @@ pub fn parse_file_content(source_code: &str, language: impl Language) -> Result<
     Ok(tree)
 }
 
+/// Reparses source code after `old_tree` has been edited with `Tree::edit`
+///
+/// Tree-sitter reuses the unchanged parts of the old tree, which is much faster
+/// than parsing from scratch.
+pub fn parse_file_content_incremental(
+    source_code: &str,
+    old_tree: &Tree,
+    language: impl Language,
+) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&language.tree_sitter_language())
+        .map_err(|e| anyhow::anyhow!("Failed to set language for parser: {}", e))?;
+
+    parser
+        .parse(source_code, Some(old_tree))
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse source code"))
+}
+
+/// Parses `new_content` incrementally from the tree of `original` and an LSP edit
+///
+/// `new_content` must be the result of applying `edit` to `original`. The edit
+/// range is interpreted in UTF-16, the default LSP position encoding. This
+/// round-trips `textDocument/didChange` edits to updated trees, mostly for
+/// testing purposes.
+pub fn parse_file_with_edit(
+    original: &str,
+    new_content: &str,
+    edit: lsp_types::TextEdit,
+    language: impl Language,
+) -> Result<Tree> {
+    let encoding = lsp_types::PositionEncodingKind::UTF16;
+    let start_position = lsp_position_to_ts_point(edit.range.start, original, &encoding);
+    let old_end_position = lsp_position_to_ts_point(edit.range.end, original, &encoding);
+    let start_byte = ts_point_to_byte_offset(original, start_position);
+    let old_end_byte = ts_point_to_byte_offset(original, old_end_position);
+    let new_end_byte = start_byte + edit.new_text.len();
+
+    let edited = format!(
+        "{}{}{}",
+        &original[..start_byte],
+        edit.new_text,
+        &original[old_end_byte..]
+    );
+    if edited != new_content {
+        anyhow::bail!("The new content does not match the original with the edit applied");
+    }
+
+    let new_end_position = match edit.new_text.rfind('\n') {
+        Some(last_newline) => Point::new(
+            start_position.row + edit.new_text.matches('\n').count(),
+            edit.new_text.len() - last_newline - 1,
+        ),
+        None => Point::new(
+            start_position.row,
+            start_position.column + edit.new_text.len(),
+        ),
+    };
+
+    let mut tree = parse_file_content(original, language)?;
+    tree.edit(&InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position,
+        old_end_position,
+        new_end_position,
+    });
+    parse_file_content_incremental(new_content, &tree, language)
+}
+
 /// Parses a file using Tree Sitter for the specified language
 pub fn parse_file(file_path: &Path, language: impl Language) -> Result<Tree> {
     // Read the file contents
@@ -60,9 +133,8 @@ struct DisplayNodeLocation<'a> {
 
 impl Display for DisplayNodeLocation<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let row = self.node.start_position().row + 1;
-        let column = self.node.start_position().column + 1;
-        write!(f, "{}:{}:{}", self.file_path.display(), row, column)
+        let position = ts_point_to_display(self.node.start_position());
+        write!(f, "{}:{}", self.file_path.display(), position)
     }
 }
 
@@ -91,70 +163,395 @@ pub fn display_node_location<'a>(file_path: &'a Path, node: Node<'a>) -> impl 'a
 /// }
 /// ```
 pub fn get_calls(tree: &Tree, language: impl Language) -> impl Iterator<Item = CallNode<'_>> {
+    get_calls_with_config(tree, language, &[], CallIteratorConfig::default())
+}
+
+/// Returns an iterator over the calls in the syntax tree whose node kind is in `kinds`
+///
+/// This works like `get_calls` but restricts matching to the given subset of
+/// the language's call node kinds, e.g. only `new_expression` for constructor
+/// calls in TypeScript. Kinds that the language never reports as calls are
+/// logged as a warning since they can never match.
+pub fn get_calls_with_kinds<'a>(
+    tree: &'a Tree,
+    language: impl Language,
+    kinds: &'a [&'a str],
+) -> impl Iterator<Item = CallNode<'a>> {
+    for kind in kinds {
+        if !language.call_node_kinds().contains(kind) {
+            tracing::warn!("'{}' is not a call node kind for {}", kind, language);
+        }
+    }
+
+    let config = CallIteratorConfig {
+        kinds: Some(kinds),
+        ..Default::default()
+    };
+    get_calls_with_config(tree, language, &[], config)
+}
+
+/// Options for the calls returned by `get_calls_with_config`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallIteratorConfig<'a> {
+    /// Restricts matches to these node kinds, `None` uses the language defaults
+    pub kinds: Option<&'a [&'a str]>,
+    /// Skips calls for which `Language::is_builtin_call` returns true
+    pub skip_builtins: bool,
+    /// Skips calls for which `Language::should_skip_as_operator` returns true
+    pub skip_operators: bool,
+    /// Restricts matches to awaited or not awaited calls
+    pub async_filter: AsyncFilter,
+}
+
+/// Which calls `get_calls_with_config` returns, see `CallNode::is_async_call`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AsyncFilter {
+    /// Both awaited and other calls
+    #[default]
+    All,
+    /// Only calls whose result is awaited
+    OnlyAsync,
+    /// Only calls that are not awaited
+    OnlySync,
+}
+
+impl AsyncFilter {
+    fn matches(self, call: &CallNode, language: impl Language) -> bool {
+        match self {
+            AsyncFilter::All => true,
+            AsyncFilter::OnlyAsync => call.is_async_call(language),
+            AsyncFilter::OnlySync => !call.is_async_call(language),
+        }
+    }
+}
+
+/// Returns an iterator over the calls in the syntax tree selected by `config`
+///
+/// `source` is the source the tree was parsed from. It is only read when
+/// skipping builtins or operators, which are recognized by name.
+pub fn get_calls_with_config<'a>(
+    tree: &'a Tree,
+    language: impl Language,
+    source: &'a [u8],
+    config: CallIteratorConfig<'a>,
+) -> impl Iterator<Item = CallNode<'a>> {
     CallIterator {
         cursor: tree.walk(),
         language,
-        visited_root: false,
+        source,
+        config,
+        finished: false,
+    }
+}
+
+/// Returns an iterator over the names of all class declarations in the syntax tree
+///
+/// What counts as a class declaration is decided by `Language::find_class_declaration`,
+/// e.g. struct type declarations in Go.
+pub fn get_class_definitions(
+    tree: &Tree,
+    language: impl Language,
+) -> impl Iterator<Item = Node<'_>> {
+    nodes(tree).filter_map(move |node| language.find_class_declaration(node))
+}
+
+/// Returns an iterator over all function and method declarations in the syntax tree
+///
+/// Yields the declaration node and its name node, for the declarations accepted
+/// by `Language::call_hierarchy_target`. The name nodes are the ones returned
+/// by `enclosing_function`.
+pub fn get_function_declarations(
+    tree: &Tree,
+    language: impl Language,
+) -> impl Iterator<Item = (Node<'_>, Node<'_>)> {
+    nodes(tree).filter_map(move |node| Some((node, language.call_hierarchy_target(node)?)))
+}
+
+/// Returns an iterator over all trait, interface and protocol nodes in the syntax tree
+///
+/// The matched node kinds are given by `Language::trait_interface_node_kinds`.
+pub fn get_interfaces(tree: &Tree, language: impl Language) -> impl Iterator<Item = Node<'_>> {
+    nodes(tree).filter(move |node| language.trait_interface_node_kinds().contains(&node.kind()))
+}
+
+/// Returns an iterator over all imports in the syntax tree
+///
+/// The matched node kinds are given by `Language::import_node_kinds`, the
+/// imported files can be found with `Language::resolve_import_to_path`.
+pub fn get_imports(tree: &Tree, language: impl Language) -> impl Iterator<Item = Node<'_>> {
+    nodes(tree).filter(move |node| language.import_node_kinds().contains(&node.kind()))
+}
+
+/// Returns an iterator over all variable declarations in the syntax tree
+///
+/// The matched node kinds are given by `Language::variable_declaration_node_kinds`.
+pub fn get_variable_declarations(
+    tree: &Tree,
+    language: impl Language,
+) -> impl Iterator<Item = Node<'_>> {
+    nodes(tree).filter(move |node| {
+        language
+            .variable_declaration_node_kinds()
+            .contains(&node.kind())
+    })
+}
+
+/// Returns an iterator over all explicitly written types in the syntax tree
+///
+/// The matched node kinds are given by `Language::type_annotation_node_kinds`.
+/// Nested types, such as the arguments of a generic type, are yielded as well.
+pub fn get_type_annotations(
+    tree: &Tree,
+    language: impl Language,
+) -> impl Iterator<Item = Node<'_>> {
+    nodes(tree).filter(move |node| language.type_annotation_node_kinds().contains(&node.kind()))
+}
+
+/// Returns an iterator over the name and number of parameters of all named functions
+///
+/// Functions are the nodes for which `Language::parameter_list_node` returns a
+/// node, and parameters are its children with a kind in
+/// `Language::parameter_node_kinds`. Anonymous functions are skipped.
+pub fn get_function_signatures<'a>(
+    tree: &'a Tree,
+    language: impl Language,
+    source: &'a [u8],
+) -> impl Iterator<Item = (&'a str, usize)> {
+    nodes(tree).filter_map(move |node| {
+        let parameters = language.parameter_list_node(node)?;
+        let name = node.child_by_field_name("name")?.utf8_text(source).ok()?;
+        let mut cursor = parameters.walk();
+        let count = parameters
+            .named_children(&mut cursor)
+            .filter(|child| language.parameter_node_kinds().contains(&child.kind()))
+            .count();
+        Some((name, count))
+    })
+}
+
+/// Returns the attributes or decorators of a function, in source order
+///
+/// These are the nodes with a kind in `Language::attribute_node_kinds` that
+/// directly precede the function, like Rust attributes and Python decorators,
+/// or that are children of it, like Swift attributes.
+pub fn get_function_attributes<'a>(
+    function_node: Node<'a>,
+    language: impl Language,
+) -> Vec<Node<'a>> {
+    let kinds = language.attribute_node_kinds();
+    let mut attributes = Vec::new();
+
+    // Comments may appear between the attributes
+    let mut sibling = function_node.prev_named_sibling();
+    while let Some(node) = sibling {
+        if kinds.contains(&node.kind()) {
+            attributes.push(node);
+        } else if !node.is_extra() {
+            break;
+        }
+        sibling = node.prev_named_sibling();
+    }
+    attributes.reverse();
+
+    let mut cursor = function_node.walk();
+    for child in function_node.named_children(&mut cursor) {
+        if kinds.contains(&child.kind()) {
+            attributes.push(child);
+        } else if child.kind() == crate::languages::kinds::swift::MODIFIERS {
+            // Swift groups attributes with the other modifiers of a declaration
+            let mut cursor = child.walk();
+            attributes.extend(
+                child
+                    .named_children(&mut cursor)
+                    .filter(|modifier| kinds.contains(&modifier.kind())),
+            );
+        }
+    }
+
+    attributes
+}
+
+/// Returns an iterator over all test functions in the syntax tree
+///
+/// The yielded nodes are the function declarations, as identified by
+/// `Language::is_test_node`.
+pub fn get_test_functions<'a>(
+    tree: &'a Tree,
+    language: impl Language,
+    source: &'a [u8],
+) -> impl Iterator<Item = Node<'a>> {
+    nodes(tree).filter(move |&node| language.is_test_node(node, source))
+}
+
+/// Returns the name of the function or method that contains `node`
+///
+/// The parents of `node` are searched for the nearest declaration accepted by
+/// `Language::call_hierarchy_target`. Returns None for top-level code.
+pub fn enclosing_function<'a>(node: Node<'a>, language: impl Language) -> Option<Node<'a>> {
+    let mut parent = node.parent();
+    while let Some(current) = parent {
+        if let Some(name) = language.call_hierarchy_target(current) {
+            return Some(name);
+        }
+        parent = current.parent();
+    }
+    None
+}
+
+/// Returns the documentation comment directly preceding `node`, if any
+///
+/// Consecutive documentation comments, as decided by
+/// `Language::is_documentation_comment`, are joined with newlines. The comment
+/// markers are kept and a blank line ends the documentation.
+pub fn get_doc_comment_for_node<'a>(
+    node: Node<'a>,
+    language: impl Language,
+    source: &[u8],
+) -> Option<String> {
+    let mut comments = Vec::new();
+    let mut next_row = node.start_position().row;
+    let mut sibling = node.prev_sibling();
+    while let Some(comment) = sibling.filter(|s| language.is_documentation_comment(*s, source)) {
+        // Line comments may include the trailing newline and end on the next row
+        let end = comment.end_position();
+        let end_row = if end.column == 0 && end.row > 0 {
+            end.row - 1
+        } else {
+            end.row
+        };
+        if end_row + 1 < next_row {
+            break;
+        }
+        comments.push(comment.utf8_text(source).ok()?.trim_end());
+        next_row = comment.start_position().row;
+        sibling = comment.prev_sibling();
+    }
+
+    if comments.is_empty() {
+        return None;
+    }
+    comments.reverse();
+    Some(comments.join("\n"))
+}
+
+/// Returns an iterator over all nodes in the syntax tree in depth-first order
+fn nodes(tree: &Tree) -> impl Iterator<Item = Node<'_>> {
+    NodeIterator {
+        cursor: tree.walk(),
+        done: false,
+    }
+}
+
+/// Iterator that traverses a Tree-sitter tree and yields every node
+struct NodeIterator<'a> {
+    cursor: TreeCursor<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for NodeIterator<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let node = self.cursor.node();
+
+        // Advance the cursor depth-first for the next iteration
+        if !self.cursor.goto_first_child() {
+            while !self.cursor.goto_next_sibling() {
+                if !self.cursor.goto_parent() {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+
+        Some(node)
     }
 }
 
 /// Iterator that traverses a Tree-sitter tree and yields call nodes
+///
+/// Every node is visited exactly once in pre-order, so an outer call is yielded
+/// before the calls nested in its arguments.
 struct CallIterator<'a, L: Language> {
     cursor: TreeCursor<'a>,
     language: L,
-    visited_root: bool,
+    source: &'a [u8],
+    config: CallIteratorConfig<'a>,
+    /// Set once the traversal has moved past the last node of the tree
+    finished: bool,
+}
+
+impl<'a, L: Language> CallIterator<'a, L> {
+    /// Moves the cursor to the next node in depth-first order
+    fn advance(&mut self) {
+        if !self.cursor.goto_first_child() {
+            self.skip_subtree();
+        }
+    }
+
+    /// Moves the cursor to the next node that is not a descendant of the current one
+    fn skip_subtree(&mut self) {
+        while !self.cursor.goto_next_sibling() {
+            if !self.cursor.goto_parent() {
+                self.finished = true;
+                return;
+            }
+        }
+    }
 }
 
 impl<'a, L: Language> Iterator for CallIterator<'a, L> {
     type Item = CallNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
+        let error_strategy = self.language.error_node_strategy();
+        while !self.finished {
             let node = self.cursor.node();
 
-            // Check if current node is a call using the language-specific method
-            if self.visited_root
-                && let Some(goto_definition_node) = self.language.find_call(node)
-            {
-                let call_node = CallNode {
-                    call_node: node,
-                    goto_definition_node,
-                };
-
-                // Move to next node for the next iteration
-                if !self.cursor.goto_first_child() {
-                    while !self.cursor.goto_next_sibling() {
-                        if !self.cursor.goto_parent() {
-                            return Some(call_node);
-                        }
-                    }
-                }
-
-                return Some(call_node);
+            if node.is_error() && error_strategy != ErrorRecoveryStrategy::Include {
+                self.skip_subtree();
+                continue;
             }
 
-            self.visited_root = true;
+            // Check if current node is a call using the language-specific method
+            let call_node = if !(node.has_error() && error_strategy == ErrorRecoveryStrategy::Skip)
+                && self
+                    .config
+                    .kinds
+                    .is_none_or(|kinds| kinds.contains(&node.kind()))
+                && !(self.config.skip_builtins && self.language.is_builtin_call(node, self.source))
+                && !(self.config.skip_operators
+                    && self.language.should_skip_as_operator(node, self.source))
+            {
+                self.language
+                    .find_call(node)
+                    .map(|goto_definition_node| CallNode {
+                        call_node: node,
+                        goto_definition_node,
+                    })
+                    .filter(|call| self.config.async_filter.matches(call, self.language))
+            } else {
+                None
+            };
 
             // Traverse the tree depth-first
-            if self.cursor.goto_first_child() {
-                continue;
-            }
+            self.advance();
 
-            loop {
-                if self.cursor.goto_next_sibling() {
-                    break;
-                }
-                if !self.cursor.goto_parent() {
-                    return None; // Reached end of tree
-                }
+            if call_node.is_some() {
+                return call_node;
             }
         }
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::languages::kinds;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -170,7 +567,7 @@ mod tests {
 
         // Check that we got a valid tree
         assert!(root.child_count() > 0);
-        assert_eq!(root.kind(), "source_file");
+        assert_eq!(root.kind(), kinds::rust::SOURCE_FILE);
 
         Ok(())
     }
@@ -186,7 +583,7 @@ mod tests {
 
         // Check that we got a valid tree
         assert!(root.child_count() > 0);
-        assert_eq!(root.kind(), "module");
+        assert_eq!(root.kind(), kinds::python::MODULE);
 
         Ok(())
     }
@@ -203,7 +600,7 @@ mod tests {
 
         // Check that we got a valid tree
         assert!(root.child_count() > 0);
-        assert_eq!(root.kind(), "program");
+        assert_eq!(root.kind(), kinds::typescript::PROGRAM);
 
         Ok(())
     }
@@ -222,7 +619,7 @@ mod tests {
 
         // Check that we got a valid tree
         assert!(root.child_count() > 0);
-        assert_eq!(root.kind(), "source_file");
+        assert_eq!(root.kind(), kinds::go::SOURCE_FILE);
 
         Ok(())
     }
@@ -239,7 +636,7 @@ mod tests {
 
         // Check that we got a valid tree
         assert!(root.child_count() > 0);
-        assert_eq!(root.kind(), "source_file");
+        assert_eq!(root.kind(), kinds::swift::SOURCE_FILE);
 
         Ok(())
     }
@@ -286,18 +683,199 @@ mod tests {
         assert_eq!(calls.len(), 3);
 
         // Verify order and content
-        assert_eq!(calls[0].call_node.kind(), "macro_invocation");
+        assert_eq!(calls[0].call_node.kind(), kinds::rust::MACRO_INVOCATION);
         assert!(calls[0].call_node.utf8_text(&source)?.contains("println!"));
 
-        assert_eq!(calls[1].call_node.kind(), "call_expression");
+        assert_eq!(calls[1].call_node.kind(), kinds::rust::CALL_EXPRESSION);
         assert!(calls[1].call_node.utf8_text(&source)?.contains("calculate"));
 
-        assert_eq!(calls[2].call_node.kind(), "call_expression");
+        assert_eq!(calls[2].call_node.kind(), kinds::rust::CALL_EXPRESSION);
         assert!(calls[2].call_node.utf8_text(&source)?.contains("foo"));
 
         Ok(())
     }
 
+    #[test]
+    fn test_call_is_macro_rust() -> Result<()> {
+        let tree = parse_file_content("fn main() { println!(\"Hi\"); foo(); }", crate::RustLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::RustLang).collect();
+
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].is_macro(crate::RustLang));
+        assert!(!calls[1].is_macro(crate::RustLang));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_method_receiver_text() -> Result<()> {
+        let source = "calc.add(2, 3)\nfoo()\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::PythonLang).collect();
+        let receivers: Vec<_> = calls
+            .iter()
+            .map(|call| crate::PythonLang.method_receiver_text(call.call_node, source.as_bytes()))
+            .collect();
+        assert_eq!(receivers, vec![Some("calc"), None]);
+
+        let source = "calc.add(2, 3);\nfoo();\n";
+        let tree = parse_file_content(source, crate::TypeScriptLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::TypeScriptLang).collect();
+        let receivers: Vec<_> = calls
+            .iter()
+            .map(|call| {
+                crate::TypeScriptLang.method_receiver_text(call.call_node, source.as_bytes())
+            })
+            .collect();
+        assert_eq!(receivers, vec![Some("calc"), None]);
+
+        let source = "calc.add(2, 3)\nfoo()\n";
+        let tree = parse_file_content(source, crate::SwiftLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::SwiftLang).collect();
+        let receivers: Vec<_> = calls
+            .iter()
+            .map(|call| crate::SwiftLang.method_receiver_text(call.call_node, source.as_bytes()))
+            .collect();
+        assert_eq!(receivers, vec![Some("calc"), None]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_closure_argument_positions() -> Result<()> {
+        fn positions(source: &str, language: impl Language) -> Result<Vec<Vec<usize>>> {
+            let tree = parse_file_content(source, language)?;
+            Ok(get_calls(&tree, language)
+                .map(|call| language.closure_argument_positions(call.call_node))
+                .collect())
+        }
+
+        let source = "fn f() { v.map(|x| x, /* c */ move || 1); g(1); }";
+        assert_eq!(positions(source, crate::RustLang)?, [vec![0, 1], vec![]]);
+
+        let source = "sorted(xs, key=lambda x: -x)\n";
+        assert_eq!(positions(source, crate::PythonLang)?, [vec![1]]);
+
+        let source = "xs.reduce((a, x) => a + x, 0, function (y) { return y; });";
+        assert_eq!(positions(source, crate::TypeScriptLang)?, [vec![0, 2]]);
+
+        let source =
+            "package main\n\nfunc f() { sort.Slice(xs, func(i, j int) bool { return true }) }";
+        assert_eq!(positions(source, crate::GoLang)?, [vec![1]]);
+
+        let source = "xs.map({ $0 * 2 })\nxs.reduce(0) { $0 + $1 }\n";
+        assert_eq!(positions(source, crate::SwiftLang)?, [vec![0], vec![1]]);
+
+        let source = "<?php array_map(fn($x) => $x, $xs);";
+        assert_eq!(positions(source, crate::PhpLang)?, [vec![0]]);
+
+        Ok(())
+    }
+
+    fn node_texts<'a>(nodes: impl Iterator<Item = Node<'a>>, source: &'a str) -> Vec<&'a str> {
+        nodes
+            .map(|node| node.utf8_text(source.as_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_class_definitions() -> Result<()> {
+        let source = "class Foo {}\nfunction bar() {}\nclass Baz {}\n";
+        let tree = parse_file_content(source, crate::TypeScriptLang)?;
+        let names = node_texts(get_class_definitions(&tree, crate::TypeScriptLang), source);
+        assert_eq!(names, vec!["Foo", "Baz"]);
+
+        let source = "class Foo:\n    pass\n\ndef bar():\n    pass\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let names = node_texts(get_class_definitions(&tree, crate::PythonLang), source);
+        assert_eq!(names, vec!["Foo"]);
+
+        let source = "class Foo {}\nfunc bar() {}\n";
+        let tree = parse_file_content(source, crate::SwiftLang)?;
+        let names = node_texts(get_class_definitions(&tree, crate::SwiftLang), source);
+        assert_eq!(names, vec!["Foo"]);
+
+        let source = "package main\ntype Foo struct {}\ntype Id int\ntype Bar struct { x int }\n";
+        let tree = parse_file_content(source, crate::GoLang)?;
+        let names = node_texts(get_class_definitions(&tree, crate::GoLang), source);
+        assert_eq!(names, vec!["Foo", "Bar"]);
+
+        // Every struct of a grouped declaration is found
+        let source = "package main\ntype (\n\tA struct{}\n\tId int\n\tB struct{ x int }\n)\n";
+        let tree = parse_file_content(source, crate::GoLang)?;
+        let names = node_texts(get_class_definitions(&tree, crate::GoLang), source);
+        assert_eq!(names, vec!["A", "B"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_interfaces() -> Result<()> {
+        let tree = parse_file_content(
+            "trait Shape { fn area(&self) -> f64; }\nstruct Square;\ntrait Named {}\n",
+            crate::RustLang,
+        )?;
+        let interface_kinds: Vec<_> = get_interfaces(&tree, crate::RustLang)
+            .map(|node| node.kind())
+            .collect();
+        assert_eq!(
+            interface_kinds,
+            vec![kinds::rust::TRAIT_ITEM, kinds::rust::TRAIT_ITEM]
+        );
+
+        let tree = parse_file_content(
+            "package main\ntype Shape interface { Area() float64 }\ntype Square struct {}\n",
+            crate::GoLang,
+        )?;
+        assert_eq!(get_interfaces(&tree, crate::GoLang).count(), 1);
+
+        let tree = parse_file_content(
+            "interface Shape { area(): number; }\nclass Square {}\n",
+            crate::TypeScriptLang,
+        )?;
+        assert_eq!(get_interfaces(&tree, crate::TypeScriptLang).count(), 1);
+
+        let tree = parse_file_content(
+            "protocol Shape { func area() -> Double }\n",
+            crate::SwiftLang,
+        )?;
+        assert_eq!(get_interfaces(&tree, crate::SwiftLang).count(), 1);
+
+        let tree = parse_file_content("class Shape:\n    pass\n", crate::PythonLang)?;
+        assert_eq!(get_interfaces(&tree, crate::PythonLang).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_variable_declarations() -> Result<()> {
+        let source = "fn main() {\n    let x = foo();\n    let y = 2;\n}\n";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let declarations = get_variable_declarations(&tree, crate::RustLang);
+        assert_eq!(
+            node_texts(declarations, source),
+            vec!["let x = foo();", "let y = 2;"]
+        );
+
+        let source = "x = foo()\nfoo(y)\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let declarations = get_variable_declarations(&tree, crate::PythonLang);
+        assert_eq!(node_texts(declarations, source), vec!["x = foo()"]);
+
+        let source = "package main\n\nvar a = 1\n\nfunc main() {\n\tb := foo()\n}\n";
+        let tree = parse_file_content(source, crate::GoLang)?;
+        assert_eq!(get_variable_declarations(&tree, crate::GoLang).count(), 2);
+
+        let source = "const a = foo();\nvar b = 1;\nlet c;\n";
+        let tree = parse_file_content(source, crate::TypeScriptLang)?;
+        assert_eq!(
+            get_variable_declarations(&tree, crate::TypeScriptLang).count(),
+            3
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_calls_python() -> Result<()> {
         let mut temp_file = NamedTempFile::new()?;
@@ -313,13 +891,13 @@ mod tests {
         // Should find: print, calculate, foo in that order
         assert_eq!(calls.len(), 3);
 
-        assert_eq!(calls[0].call_node.kind(), "call");
+        assert_eq!(calls[0].call_node.kind(), kinds::python::CALL);
         assert!(calls[0].call_node.utf8_text(&source)?.contains("print"));
 
-        assert_eq!(calls[1].call_node.kind(), "call");
+        assert_eq!(calls[1].call_node.kind(), kinds::python::CALL);
         assert!(calls[1].call_node.utf8_text(&source)?.contains("calculate"));
 
-        assert_eq!(calls[2].call_node.kind(), "call");
+        assert_eq!(calls[2].call_node.kind(), kinds::python::CALL);
         assert!(calls[2].call_node.utf8_text(&source)?.contains("foo"));
 
         Ok(())
@@ -341,7 +919,10 @@ mod tests {
         // Should find: console.log, calculate, new MyClass in that order
         assert_eq!(calls.len(), 3);
 
-        assert_eq!(calls[0].call_node.kind(), "call_expression");
+        assert_eq!(
+            calls[0].call_node.kind(),
+            kinds::typescript::CALL_EXPRESSION
+        );
         assert!(
             calls[0]
                 .call_node
@@ -349,15 +930,39 @@ mod tests {
                 .contains("console.log")
         );
 
-        assert_eq!(calls[1].call_node.kind(), "call_expression");
+        assert_eq!(
+            calls[1].call_node.kind(),
+            kinds::typescript::CALL_EXPRESSION
+        );
         assert!(calls[1].call_node.utf8_text(&source)?.contains("calculate"));
 
-        assert_eq!(calls[2].call_node.kind(), "new_expression");
+        assert_eq!(calls[2].call_node.kind(), kinds::typescript::NEW_EXPRESSION);
         assert!(calls[2].call_node.utf8_text(&source)?.contains("MyClass"));
 
         Ok(())
     }
 
+    #[test]
+    fn test_get_calls_with_kinds_typescript() -> Result<()> {
+        let source = "const x = calculate(5, 10);\nconst obj = new MyClass(make());\n";
+        let tree = parse_file_content(source, crate::TypeScriptLang)?;
+        let calls: Vec<_> = get_calls_with_kinds(
+            &tree,
+            crate::TypeScriptLang,
+            &[kinds::typescript::NEW_EXPRESSION],
+        )
+        .collect();
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].call_node.kind(), kinds::typescript::NEW_EXPRESSION);
+        assert_eq!(
+            calls[0].call_node.utf8_text(source.as_bytes())?,
+            "new MyClass(make())"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_calls_go() -> Result<()> {
         let mut temp_file = NamedTempFile::new()?;
@@ -375,10 +980,10 @@ mod tests {
         // Should find: println, calculate in that order
         assert_eq!(calls.len(), 2);
 
-        assert_eq!(calls[0].call_node.kind(), "call_expression");
+        assert_eq!(calls[0].call_node.kind(), kinds::go::CALL_EXPRESSION);
         assert!(calls[0].call_node.utf8_text(&source)?.contains("println"));
 
-        assert_eq!(calls[1].call_node.kind(), "call_expression");
+        assert_eq!(calls[1].call_node.kind(), kinds::go::CALL_EXPRESSION);
         assert!(calls[1].call_node.utf8_text(&source)?.contains("calculate"));
 
         Ok(())
@@ -399,15 +1004,21 @@ mod tests {
         // Should find: print, calculate in that order
         assert_eq!(calls.len(), 2);
 
-        assert_eq!(calls[0].call_node.kind(), "call_expression");
+        assert_eq!(calls[0].call_node.kind(), kinds::swift::CALL_EXPRESSION);
         assert!(calls[0].call_node.utf8_text(&source)?.contains("print"));
-        assert_eq!(calls[0].goto_definition_node.kind(), "simple_identifier");
+        assert_eq!(
+            calls[0].goto_definition_node.kind(),
+            kinds::swift::SIMPLE_IDENTIFIER
+        );
         let def_text = calls[0].goto_definition_node.utf8_text(&source)?;
         assert_eq!(def_text, "print");
 
-        assert_eq!(calls[1].call_node.kind(), "call_expression");
+        assert_eq!(calls[1].call_node.kind(), kinds::swift::CALL_EXPRESSION);
         assert_eq!(calls[1].call_node.utf8_text(&source)?, "calculate(5, 10)");
-        assert_eq!(calls[1].goto_definition_node.kind(), "simple_identifier");
+        assert_eq!(
+            calls[1].goto_definition_node.kind(),
+            kinds::swift::SIMPLE_IDENTIFIER
+        );
         let def_text = calls[1].goto_definition_node.utf8_text(&source)?;
         assert_eq!(def_text, "calculate");
 
@@ -437,15 +1048,18 @@ mod tests {
 
         // Find the method call 'calc.add(2, 3)'
         let method_call = calls.get(1).expect("Method call not found");
-        assert_eq!(method_call.call_node.kind(), "call_expression");
+        assert_eq!(method_call.call_node.kind(), kinds::swift::CALL_EXPRESSION);
         // The goto_definition_node should point to just the method name "add"
-        assert_eq!(method_call.goto_definition_node.kind(), "simple_identifier");
+        assert_eq!(
+            method_call.goto_definition_node.kind(),
+            kinds::swift::SIMPLE_IDENTIFIER
+        );
         let def_text = method_call.goto_definition_node.utf8_text(&source)?;
         assert_eq!(def_text, "add");
 
         // Find the method call `Foo<UInt8>.allocate(capacity: length)`
         let method_call = calls.get(2).expect("Method call not found");
-        assert_eq!(method_call.call_node.kind(), "call_expression");
+        assert_eq!(method_call.call_node.kind(), kinds::swift::CALL_EXPRESSION);
         // The goto_definition_node should point to just the method name
         // "allocate", but the Swift tree-sitter grammar doesn't parse the call
         // correctly due to the generics. We might want to work around this in
@@ -455,4 +1069,468 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_call_iterator_yields_root() -> Result<()> {
+        let language = crate::testing::MockLang::new(kinds::rust::SOURCE_FILE);
+        let tree = parse_file_content("fn main() {}", language)?;
+        let calls: Vec<_> = get_calls(&tree, language).collect();
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].call_node, tree.root_node());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_iterator_tree_without_children() -> Result<()> {
+        let language = crate::testing::MockLang::new(kinds::rust::SOURCE_FILE);
+        let tree = parse_file_content("", language)?;
+        assert_eq!(tree.root_node().child_count(), 0);
+
+        let calls: Vec<_> = get_calls(&tree, language).collect();
+        assert_eq!(calls.len(), 1);
+
+        let language = crate::testing::MockLang::new(kinds::rust::IDENTIFIER);
+        assert_eq!(get_calls(&tree, language).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_iterator_error_node_strategy() -> Result<()> {
+        // The `2` is wrapped in an ERROR node inside the arguments of `foo`
+        let source = "fn main() {\n    foo(1 2);\n    bar(baz());\n}\n";
+        let calls = |call_kind, error_node_strategy| -> Result<Vec<String>> {
+            let language = crate::testing::MockLang {
+                call_kind,
+                error_node_strategy,
+            };
+            let tree = parse_file_content(source, language)?;
+            get_calls(&tree, language)
+                .map(|call| Ok(call.call_node.utf8_text(source.as_bytes())?.to_string()))
+                .collect()
+        };
+
+        use ErrorRecoveryStrategy::{Include, IncludeParent, Skip};
+        assert_eq!(
+            calls(kinds::rust::CALL_EXPRESSION, Skip)?,
+            ["bar(baz())", "baz()"]
+        );
+        assert_eq!(calls(kinds::rust::INTEGER_LITERAL, Skip)?, ["1"]);
+        assert_eq!(
+            calls(kinds::rust::CALL_EXPRESSION, IncludeParent)?,
+            ["foo(1 2)", "bar(baz())", "baz()"]
+        );
+        assert_eq!(calls(kinds::rust::INTEGER_LITERAL, IncludeParent)?, ["1"]);
+        assert_eq!(
+            calls(kinds::rust::CALL_EXPRESSION, Include)?,
+            ["foo(1 2)", "bar(baz())", "baz()"]
+        );
+        assert_eq!(calls(kinds::rust::INTEGER_LITERAL, Include)?, ["1", "2"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_calls_skips_broken_source() -> Result<()> {
+        // Recovered as a call spanning all three lines inside an ERROR node
+        let source = "def f(x:\n    foo(bar())\n    return\nbaz()\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        assert!(tree.root_node().has_error());
+        assert_eq!(get_calls(&tree, crate::PythonLang).count(), 0);
+
+        let source = "def f(x):\n    foo(bar(1 2))\n    return baz()\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::PythonLang)
+            .map(|call| call.call_node.utf8_text(source.as_bytes()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(calls, ["baz()"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_iterator_deeply_nested() -> Result<()> {
+        let language = crate::testing::MockLang::new(kinds::rust::BLOCK);
+        let source = "fn main() { { { { } } } }";
+        let tree = parse_file_content(source, language)?;
+        let depths: Vec<_> = get_calls(&tree, language)
+            .map(|call| call.call_node.start_position().column)
+            .collect();
+
+        assert_eq!(depths, vec![10, 12, 14, 16]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_calls_nested_in_arguments() -> Result<()> {
+        let source = "fn main() { outer(inner(x)); }";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::RustLang)
+            .map(|call| call.call_node.utf8_text(source.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(calls, vec!["outer(inner(x))", "inner(x)"]);
+
+        let source = "outer(inner(x), other(y))";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let calls: Vec<_> = get_calls(&tree, crate::PythonLang)
+            .map(|call| call.call_node.utf8_text(source.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(
+            calls,
+            vec!["outer(inner(x), other(y))", "inner(x)", "other(y)"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_with_edit() -> Result<()> {
+        let original = "fn main() {\n    foo();\n}\n";
+        let new_content = "fn main() {\n    bar(1, 2);\n    baz();\n}\n";
+        let edit = lsp_types::TextEdit {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(1, 4),
+                lsp_types::Position::new(1, 10),
+            ),
+            new_text: "bar(1, 2);\n    baz();".to_string(),
+        };
+
+        let tree = parse_file_with_edit(original, new_content, edit.clone(), crate::RustLang)?;
+        let fresh = parse_file_content(new_content, crate::RustLang)?;
+        assert_eq!(tree.root_node().to_sexp(), fresh.root_node().to_sexp());
+        let calls: Vec<_> = get_calls(&tree, crate::RustLang)
+            .map(|call| call.call_node.utf8_text(new_content.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(calls, vec!["bar(1, 2)", "baz()"]);
+
+        assert!(parse_file_with_edit(original, original, edit, crate::RustLang).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_test_functions() -> Result<()> {
+        fn name(node: Node) -> Node {
+            node.child_by_field_name("name").unwrap()
+        }
+
+        let source = "#[test]\nfn it_works() {}\n\n#[cfg(test)]\nfn helper() {}\n\n#[tokio::test]\n#[ignore]\nasync fn it_runs() {}\n\nfn main() {}\n";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let tests = get_test_functions(&tree, crate::RustLang, source.as_bytes()).map(name);
+        assert_eq!(node_texts(tests, source), vec!["it_works", "it_runs"]);
+
+        let source = "def test_add():\n    pass\n\ndef add():\n    pass\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let tests = get_test_functions(&tree, crate::PythonLang, source.as_bytes()).map(name);
+        assert_eq!(node_texts(tests, source), vec!["test_add"]);
+
+        let source = "package main\n\nfunc TestAdd(t *testing.T) {}\n\nfunc add() {}\n";
+        let tree = parse_file_content(source, crate::GoLang)?;
+        let tests = get_test_functions(&tree, crate::GoLang, source.as_bytes()).map(name);
+        assert_eq!(node_texts(tests, source), vec!["TestAdd"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_type_annotations() -> Result<()> {
+        let source = "fn f(a: Vec<Item>) -> u32 { 0 }\n";
+        let tree = parse_file_content(source, crate::RustLang)?;
+        let types = get_type_annotations(&tree, crate::RustLang);
+        assert_eq!(node_texts(types, source), vec!["Vec<Item>", "Vec", "Item"]);
+
+        let source = "let x: number = 1;\nfunction f(s: string) {}\n";
+        let tree = parse_file_content(source, crate::TypeScriptLang)?;
+        let types = get_type_annotations(&tree, crate::TypeScriptLang);
+        assert_eq!(node_texts(types, source), vec![": number", ": string"]);
+
+        let source = "count: int = 0\nname = 'x'\n";
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        let types = get_type_annotations(&tree, crate::PythonLang);
+        assert_eq!(node_texts(types, source), vec!["int"]);
+
+        let source = "package main\n\nvar s Server\n";
+        let tree = parse_file_content(source, crate::GoLang)?;
+        let types = get_type_annotations(&tree, crate::GoLang);
+        assert_eq!(node_texts(types, source), vec!["Server"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_calls_skipping_builtins() -> Result<()> {
+        fn calls(source: &str, language: impl Language) -> Result<Vec<String>> {
+            let tree = parse_file_content(source, language)?;
+            let config = CallIteratorConfig {
+                skip_builtins: true,
+                ..Default::default()
+            };
+            Ok(
+                get_calls_with_config(&tree, language, source.as_bytes(), config)
+                    .map(|call| {
+                        call.call_node
+                            .utf8_text(source.as_bytes())
+                            .unwrap()
+                            .to_string()
+                    })
+                    .collect(),
+            )
+        }
+
+        let source = "for i in range(len(xs)):\n    process(xs[i])\n    print(i)\n";
+        assert_eq!(calls(source, crate::PythonLang)?, vec!["process(xs[i])"]);
+        let tree = parse_file_content(source, crate::PythonLang)?;
+        assert_eq!(get_calls(&tree, crate::PythonLang).count(), 4);
+
+        let source = "package main\n\nfunc main() {\n\ts := make([]int, 0)\n\tfmt.Println(len(s))\n\trun(s)\n}\n";
+        assert_eq!(calls(source, crate::GoLang)?, vec!["run(s)"]);
+
+        let source = "fn main() { let v = vec![1]; println!(\"{:?}\", v); drop(Some(run(v))); }";
+        assert_eq!(calls(source, crate::RustLang)?, vec!["run(v)"]);
+
+        let source = "console.log(parseInt(x));\nconst m = new Map();\nnew Parser(m).run();\n";
+        assert_eq!(
+            calls(source, crate::TypeScriptLang)?,
+            vec!["new Parser(m).run()", "new Parser(m)"]
+        );
+
+        let source = "print(greet(name))\n";
+        assert_eq!(calls(source, crate::SwiftLang)?, vec!["greet(name)"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_calls_with_async_filter() -> Result<()> {
+        fn calls(
+            source: &str,
+            language: impl Language,
+            filter: AsyncFilter,
+        ) -> Result<Vec<String>> {
+            let tree = parse_file_content(source, language)?;
+            let config = CallIteratorConfig {
+                async_filter: filter,
+                ..Default::default()
+            };
+            get_calls_with_config(&tree, language, &[], config)
+                .map(|call| {
+                    Ok(call
+                        .goto_definition_node
+                        .utf8_text(source.as_bytes())?
+                        .to_string())
+                })
+                .collect()
+        }
+
+        let source = "async fn f() { fetch().await.text(); (load()).await; log(); }";
+        assert_eq!(
+            calls(source, crate::RustLang, AsyncFilter::OnlyAsync)?,
+            vec!["fetch()", "load()"]
+        );
+        assert_eq!(
+            calls(source, crate::RustLang, AsyncFilter::OnlySync)?,
+            vec!["fetch().await.text()", "log()"]
+        );
+        assert_eq!(calls(source, crate::RustLang, AsyncFilter::All)?.len(), 4);
+
+        let source = "async function f() { await fetch(url); await (a.load()); log(); }";
+        assert_eq!(
+            calls(source, crate::TypeScriptLang, AsyncFilter::OnlyAsync)?,
+            vec!["fetch(url)", "a.load()"]
+        );
+
+        let source =
+            "async def f():\n    x = await fetch(url)\n    y = await (load())\n    log(x)\n";
+        assert_eq!(
+            calls(source, crate::PythonLang, AsyncFilter::OnlyAsync)?,
+            vec!["fetch(url)", "load()"]
+        );
+
+        let source =
+            "func f() async { let x = try await fetch(url); let y = await (load()); log(x) }";
+        assert_eq!(
+            calls(source, crate::SwiftLang, AsyncFilter::OnlyAsync)?,
+            vec!["fetch", "load"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_function_signatures() -> Result<()> {
+        fn signatures(source: &str, language: impl Language) -> Result<Vec<(String, usize)>> {
+            let tree = parse_file_content(source, language)?;
+            Ok(get_function_signatures(&tree, language, source.as_bytes())
+                .map(|(name, count)| (name.to_string(), count))
+                .collect())
+        }
+        let expected = |pairs: &[(&str, usize)]| {
+            pairs
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect::<Vec<_>>()
+        };
+
+        let source = "fn main() { let f = |x| x; }
+impl A { fn add(&self, a: u32, b: u32) {} }
+";
+        assert_eq!(
+            signatures(source, crate::RustLang)?,
+            expected(&[("main", 0), ("add", 3)])
+        );
+
+        let source = "def f(a, b: int, *args, c=1, **kwargs):
+    g = lambda x: x
+";
+        assert_eq!(
+            signatures(source, crate::PythonLang)?,
+            expected(&[("f", 5)])
+        );
+
+        let source = "package main
+
+func (s *S) Run(a int, rest ...string) {}
+";
+        assert_eq!(signatures(source, crate::GoLang)?, expected(&[("Run", 2)]));
+
+        let source = "function f(a: number, b?: string, ...rest: any[]) {}
+const g = (x) => x;
+";
+        assert_eq!(
+            signatures(source, crate::TypeScriptLang)?,
+            expected(&[("f", 3)])
+        );
+
+        let source = "func greet(name: String, times: Int) {}
+";
+        assert_eq!(
+            signatures(source, crate::SwiftLang)?,
+            expected(&[("greet", 2)])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enclosing_function() -> Result<()> {
+        fn caller(source: &str, language: impl Language) -> Result<Option<String>> {
+            let tree = parse_file_content(source, language)?;
+            let call = get_calls(&tree, language).next().unwrap();
+            Ok(enclosing_function(call.call_node, language)
+                .map(|name| name.utf8_text(source.as_bytes()).unwrap().to_string()))
+        }
+
+        let source = "fn outer() {\n    if true {\n        helper();\n    }\n}\n";
+        assert_eq!(caller(source, crate::RustLang)?.as_deref(), Some("outer"));
+
+        let source = "class Calculator {\n    func run() {\n        add(1, 2)\n    }\n}\n";
+        assert_eq!(caller(source, crate::SwiftLang)?.as_deref(), Some("run"));
+
+        let source = "main()\n";
+        assert_eq!(caller(source, crate::PythonLang)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_doc_comment_for_node() -> Result<()> {
+        fn doc_comment(
+            source: &str,
+            language: impl Language,
+            index: usize,
+        ) -> Result<Option<String>> {
+            let tree = parse_file_content(source, language)?;
+            let node = tree.root_node().named_child(index).unwrap();
+            Ok(get_doc_comment_for_node(node, language, source.as_bytes()))
+        }
+
+        let source =
+            "/// Adds numbers\n/// together\nfn add() {}\n\n// Not documentation\nfn sub() {}\n";
+        assert_eq!(
+            doc_comment(source, crate::RustLang, 2)?.as_deref(),
+            Some("/// Adds numbers\n/// together")
+        );
+        assert_eq!(doc_comment(source, crate::RustLang, 4)?, None);
+
+        let source = "/// Detached\n\nfn add() {}\n";
+        assert_eq!(doc_comment(source, crate::RustLang, 1)?, None);
+
+        let source = "package main\n\n// Add adds numbers\nfunc Add() {}\n";
+        assert_eq!(
+            doc_comment(source, crate::GoLang, 2)?.as_deref(),
+            Some("// Add adds numbers")
+        );
+
+        let source =
+            "/** Adds numbers */\nfunction add() {}\n// Not documentation\nfunction sub() {}\n";
+        assert_eq!(
+            doc_comment(source, crate::TypeScriptLang, 1)?.as_deref(),
+            Some("/** Adds numbers */")
+        );
+        assert_eq!(doc_comment(source, crate::TypeScriptLang, 3)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_function_attributes() -> Result<()> {
+        fn attributes(source: &str, language: impl Language, kind: &str) -> Result<Vec<String>> {
+            let tree = parse_file_content(source, language)?;
+            let function = nodes(&tree)
+                .find(|node| node.kind() == kind)
+                .ok_or_else(|| anyhow::anyhow!("No {} in source", kind))?;
+            Ok(get_function_attributes(function, language)
+                .iter()
+                .map(|node| node.utf8_text(source.as_bytes()).unwrap().to_string())
+                .collect())
+        }
+
+        let source = "#[test]\n/// Docs\n#[ignore]\nfn f() {}\n";
+        assert_eq!(
+            attributes(source, crate::RustLang, kinds::rust::FUNCTION_ITEM)?,
+            ["#[test]", "#[ignore]"]
+        );
+        assert!(
+            attributes(
+                "#[test]\nstruct A;\nfn f() {}",
+                crate::RustLang,
+                kinds::rust::FUNCTION_ITEM
+            )?
+            .is_empty()
+        );
+
+        let source = "class A:\n    @staticmethod\n    @cache(1)\n    def f(): pass\n";
+        assert_eq!(
+            attributes(
+                source,
+                crate::PythonLang,
+                kinds::python::FUNCTION_DEFINITION
+            )?,
+            ["@staticmethod", "@cache(1)"]
+        );
+
+        let source = "class A { @Input() f() {} }";
+        assert_eq!(
+            attributes(
+                source,
+                crate::TypeScriptLang,
+                kinds::typescript::METHOD_DEFINITION
+            )?,
+            ["@Input()"]
+        );
+
+        let source = "@objc @MainActor func f() {}";
+        assert_eq!(
+            attributes(source, crate::SwiftLang, kinds::swift::FUNCTION_DECLARATION)?,
+            ["@objc", "@MainActor"]
+        );
+
+        let source = "package main\n\nfunc f() {}\n";
+        assert!(attributes(source, crate::GoLang, kinds::go::FUNCTION_DECLARATION)?.is_empty());
+
+        Ok(())
+    }
 }
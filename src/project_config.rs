@@ -0,0 +1,164 @@
+//! Project configuration files with file search and LSP server settings.
+//!
+//! A `.ts-lsp.toml` in the project root (or a file passed with `--config`)
+//! saves re-typing the same command-line options:
+//!
+//! ```toml
+//! skip_dirs = ["target", "fixtures"]
+//! include = "**/src/**"
+//! exclude = ["**/*_test.rs"]
+//! max_depth = 4
+//!
+//! [server]
+//! command = "/opt/rust-analyzer/bin/rust-analyzer"
+//! args = ["--log-file", "/tmp/ra.log"]
+//! env_vars = { RA_LOG = "info" }
+//! ```
+//!
+//! Values from the file override the defaults, and command-line flags override
+//! values from the file.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{FileSearchConfig, LspServerConfig};
+
+/// The name of the configuration file looked up in the project root
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".ts-lsp.toml";
+
+/// Settings read from a project configuration file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Directories to skip, replaces the default list
+    pub skip_dirs: Option<Vec<String>>,
+    /// Glob pattern to include only matching files
+    pub include: Option<String>,
+    /// Glob patterns to exclude matching files
+    pub exclude: Vec<String>,
+    /// Maximum directory depth to search
+    pub max_depth: Option<usize>,
+    /// LSP server settings
+    pub server: ServerConfig,
+}
+
+/// The `[server]` section of a project configuration file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Command to run instead of the language's default LSP server
+    pub command: Option<String>,
+    /// Additional arguments to pass to the LSP server
+    pub args: Vec<String>,
+    /// Environment variables to set for the LSP server
+    pub env_vars: BTreeMap<String, String>,
+}
+
+impl ProjectConfig {
+    /// Reads a configuration file
+    pub fn load(path: &Path) -> Result<ProjectConfig> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Returns the path of the configuration file in `project_root`, if there is one
+    pub fn find(project_root: &Path) -> Option<PathBuf> {
+        let path = project_root.join(PROJECT_CONFIG_FILE_NAME);
+        path.is_file().then_some(path)
+    }
+
+    /// Returns a `FileSearchConfig` with the defaults overridden by this configuration
+    pub fn file_search_config(&self) -> Result<FileSearchConfig> {
+        let mut config = FileSearchConfig::default();
+        if let Some(skip_dirs) = &self.skip_dirs {
+            config.skip_dirs = skip_dirs.clone();
+        }
+        if let Some(pattern) = &self.include {
+            config.include_glob = Some(glob::Pattern::new(pattern).map_err(|e| {
+                anyhow::anyhow!("Invalid include glob pattern '{}': {}", pattern, e)
+            })?);
+        }
+        for pattern in &self.exclude {
+            config
+                .exclude_globs
+                .push(glob::Pattern::new(pattern).map_err(|e| {
+                    anyhow::anyhow!("Invalid exclude glob pattern '{}': {}", pattern, e)
+                })?);
+        }
+        if self.max_depth.is_some() {
+            config.max_depth = self.max_depth;
+        }
+        Ok(config)
+    }
+
+    /// Returns an `LspServerConfig` with the defaults overridden by this configuration
+    pub fn lsp_server_config(&self) -> LspServerConfig {
+        LspServerConfig {
+            args: self.server.args.clone(),
+            env_vars: self.server.env_vars.clone().into_iter().collect(),
+            lsp_command_override: self.server.command.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_project_config() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert_eq!(ProjectConfig::find(temp_dir.path()), None);
+
+        fs::write(
+            temp_dir.path().join(PROJECT_CONFIG_FILE_NAME),
+            r#"
+skip_dirs = ["target", "fixtures"]
+include = "**/src/**"
+exclude = ["**/*_test.rs"]
+max_depth = 4
+
+[server]
+command = "my-analyzer"
+args = ["--verbose"]
+env_vars = { RA_LOG = "info" }
+"#,
+        )?;
+        let path = ProjectConfig::find(temp_dir.path()).unwrap();
+        let project_config = ProjectConfig::load(&path)?;
+
+        let file_search = project_config.file_search_config()?;
+        assert_eq!(file_search.skip_dirs, vec!["target", "fixtures"]);
+        assert_eq!(file_search.include_glob.unwrap().as_str(), "**/src/**");
+        assert_eq!(file_search.exclude_globs.len(), 1);
+        assert_eq!(file_search.max_depth, Some(4));
+
+        let server = project_config.lsp_server_config();
+        assert_eq!(server.lsp_command_override.as_deref(), Some("my-analyzer"));
+        assert_eq!(server.args, vec!["--verbose"]);
+        assert_eq!(
+            server.env_vars,
+            vec![("RA_LOG".to_string(), "info".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_project_config_rejects_unknown_keys() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join(PROJECT_CONFIG_FILE_NAME);
+        fs::write(&path, "max_dept = 4\n")?;
+
+        assert!(ProjectConfig::load(&path).is_err());
+
+        Ok(())
+    }
+}
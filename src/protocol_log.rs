@@ -0,0 +1,190 @@
+//! Dumps of the JSON-RPC messages exchanged with an LSP server.
+//!
+//! The wrappers observe the bytes passing through the server's stdin and
+//! stdout and append every complete message to a log file, one line per
+//! message prefixed with a timestamp and `-->` for messages sent to the server
+//! or `<--` for messages received from it.
+
+use anyhow::Result;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A log file shared by the wrappers of both directions
+#[derive(Debug, Clone)]
+pub struct ProtocolLog {
+    file: Arc<Mutex<File>>,
+}
+
+impl ProtocolLog {
+    /// Opens the log at `path` for appending, creating it if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                anyhow::anyhow!("Failed to open protocol log {}: {}", path.display(), e)
+            })?;
+        Ok(ProtocolLog {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    fn record(&self, direction: &str, message: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut line = format!("[{:.3}] {} ", timestamp, direction).into_bytes();
+        line.extend_from_slice(message);
+        line.push(b'\n');
+        // A failing log must not break the connection to the server
+        if let Err(e) = self.file.lock().unwrap().write_all(&line) {
+            tracing::warn!("Failed to write the protocol log: {}", e);
+        }
+    }
+}
+
+/// Collects the bytes of one direction and logs each message once it is complete
+#[derive(Debug)]
+struct MessageTap {
+    log: ProtocolLog,
+    direction: &'static str,
+    pending: Vec<u8>,
+}
+
+impl MessageTap {
+    fn observe(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        while let Some((body_start, body_end)) = next_message(&self.pending) {
+            self.log
+                .record(self.direction, &self.pending[body_start..body_end]);
+            self.pending.drain(..body_end);
+        }
+    }
+}
+
+/// Returns the byte range of the body of the first complete `Content-Length` framed message
+fn next_message(bytes: &[u8]) -> Option<(usize, usize)> {
+    let header_end = bytes.windows(4).position(|window| window == b"\r\n\r\n")?;
+    let headers = std::str::from_utf8(&bytes[..header_end]).ok()?;
+    let content_length: usize = headers.lines().find_map(|header| {
+        header
+            .strip_prefix("Content-Length:")
+            .and_then(|length| length.trim().parse().ok())
+    })?;
+    let body_start = header_end + 4;
+    let body_end = body_start + content_length;
+    (bytes.len() >= body_end).then_some((body_start, body_end))
+}
+
+/// Logs the messages written to the server
+#[derive(Debug)]
+pub struct LoggingWriter<W> {
+    inner: W,
+    tap: MessageTap,
+}
+
+impl<W: Write> LoggingWriter<W> {
+    pub fn new(inner: W, log: ProtocolLog) -> Self {
+        LoggingWriter {
+            inner,
+            tap: MessageTap {
+                log,
+                direction: "-->",
+                pending: Vec::new(),
+            },
+        }
+    }
+}
+
+impl<W: Write> Write for LoggingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.tap.observe(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Logs the messages read from the server
+#[derive(Debug)]
+pub struct LoggingReader<R> {
+    inner: R,
+    tap: MessageTap,
+}
+
+impl<R: Read> LoggingReader<R> {
+    pub fn new(inner: R, log: ProtocolLog) -> Self {
+        LoggingReader {
+            inner,
+            tap: MessageTap {
+                log,
+                direction: "<--",
+                pending: Vec::new(),
+            },
+        }
+    }
+}
+
+impl<R: Read> Read for LoggingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.tap.observe(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::request_string;
+
+    #[test]
+    fn test_logs_complete_messages_in_both_directions() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("protocol.log");
+        let log = ProtocolLog::open(&path)?;
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "shutdown"});
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": null});
+        let notification = serde_json::json!({"jsonrpc": "2.0", "method": "exit"});
+
+        // Written in pieces that split the header and the body
+        let mut writer = LoggingWriter::new(Vec::new(), log.clone());
+        let sent = [request_string(&request)?, request_string(&notification)?].concat();
+        for chunk in sent.as_bytes().chunks(7) {
+            writer.write_all(chunk)?;
+        }
+        assert_eq!(writer.inner, sent.as_bytes());
+
+        let received = request_string(&response)?;
+        let mut reader = LoggingReader::new(received.as_bytes(), log);
+        let mut buffer = String::new();
+        reader.read_to_string(&mut buffer)?;
+        assert_eq!(buffer, received);
+
+        let contents = std::fs::read_to_string(&path)?;
+        let lines: Vec<_> = contents
+            .lines()
+            .map(|line| line.split_once("] ").unwrap().1)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                format!("--> {}", request),
+                format!("--> {}", notification),
+                format!("<-- {}", response),
+            ]
+        );
+        assert!(contents.starts_with('['));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,554 @@
+//! Flat reference records for exporting reports as CSV or JSON, and the
+//! `Render` trait for writing reports in every `OutputFormat`.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::call_node::OwnedCallNode;
+use crate::call_with_target::{CallWithTarget, SerializableCallDefinition};
+use crate::cli::OutputFormat;
+use crate::integration::{CallAnalysisResults, DeclarationCallers};
+use crate::location::{lsp_to_display, ts_point_to_display};
+use crate::lsp::response_locations;
+use crate::uri::display_uri;
+
+/// The CSV header, matching the fields of `ReferenceRecord`
+pub const CSV_HEADER: &str = "symbol,kind,ref_file,ref_line,ref_col,def_file,def_line,def_col";
+
+/// A reference to a symbol and the definition it refers to
+///
+/// Lines and columns are 1-based. An unknown definition has an empty
+/// `def_file` and a `def_line` and `def_col` of 0, and likewise for an
+/// unknown reference.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferenceRecord {
+    /// The name of the symbol, or the source text of a call
+    pub symbol: String,
+    /// The kind of symbol, or `call` for calls
+    pub kind: String,
+    pub ref_file: String,
+    pub ref_line: usize,
+    pub ref_col: usize,
+    pub def_file: String,
+    pub def_line: usize,
+    pub def_col: usize,
+}
+
+impl ReferenceRecord {
+    /// Returns a record for each location the call resolved to
+    pub fn from_call(call: &CallWithTarget) -> Vec<ReferenceRecord> {
+        let call_position = ts_point_to_display(call.call_node.start_position);
+        response_locations(call.definition.clone())
+            .into_iter()
+            .map(|location| {
+                let target = lsp_to_display(location.range.start);
+                ReferenceRecord {
+                    symbol: call.call_node.text.clone(),
+                    kind: "call".to_string(),
+                    ref_file: call.file_path.display().to_string(),
+                    ref_line: call_position.line,
+                    ref_col: call_position.column,
+                    def_file: display_uri(&location.uri),
+                    def_line: target.line,
+                    def_col: target.column,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a record with an unknown definition for a call found without an LSP server
+    pub fn from_call_without_lsp(file_path: &Path, call_node: &OwnedCallNode) -> ReferenceRecord {
+        let call_position = ts_point_to_display(call_node.start_position);
+        ReferenceRecord {
+            symbol: call_node.text.clone(),
+            kind: "call".to_string(),
+            ref_file: file_path.display().to_string(),
+            ref_line: call_position.line,
+            ref_col: call_position.column,
+            def_file: String::new(),
+            def_line: 0,
+            def_col: 0,
+        }
+    }
+
+    /// Returns a record for each call of the declaration, or a single record
+    /// with an unknown reference if it has no callers
+    pub fn from_callers(declaration: &DeclarationCallers) -> Vec<ReferenceRecord> {
+        let position = ts_point_to_display(declaration.position);
+        let record = |ref_file: String, ref_line: usize, ref_col: usize| ReferenceRecord {
+            symbol: declaration.name.clone(),
+            kind: "call".to_string(),
+            ref_file,
+            ref_line,
+            ref_col,
+            def_file: declaration.file_path.display().to_string(),
+            def_line: position.line,
+            def_col: position.column,
+        };
+        if declaration.callers.is_empty() {
+            return vec![record(String::new(), 0, 0)];
+        }
+        declaration
+            .callers
+            .iter()
+            .flat_map(|call| {
+                call.from_ranges.iter().map(|range| {
+                    let call_position = lsp_to_display(range.start);
+                    record(
+                        display_uri(&call.from.uri),
+                        call_position.line,
+                        call_position.column,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn csv_line(&self) -> String {
+        [
+            escape_csv_field(&self.symbol),
+            escape_csv_field(&self.kind),
+            escape_csv_field(&self.ref_file),
+            Cow::Owned(self.ref_line.to_string()),
+            Cow::Owned(self.ref_col.to_string()),
+            escape_csv_field(&self.def_file),
+            Cow::Owned(self.def_line.to_string()),
+            Cow::Owned(self.def_col.to_string()),
+        ]
+        .join(",")
+    }
+}
+
+/// Quotes a CSV field if it contains a comma, a quote or a line break
+pub fn escape_csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Writes records as CSV with a header line
+pub fn write_csv(
+    writer: &mut impl Write,
+    records: impl Iterator<Item = ReferenceRecord>,
+) -> io::Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for record in records {
+        writeln!(writer, "{}", record.csv_line())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes records as newline-delimited JSON, one record per line
+pub fn write_ndjson(
+    writer: &mut impl Write,
+    records: impl Iterator<Item = ReferenceRecord>,
+) -> io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A report that can be written in each of the `OutputFormat`s
+///
+/// This lets binaries write their results with a single call, and tests
+/// render reports into an in-memory buffer.
+pub trait Render {
+    /// Writes the report to `writer` in `format`
+    fn render(&self, format: OutputFormat, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+/// The references found to a set of symbols
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferencesReport {
+    pub records: Vec<ReferenceRecord>,
+}
+
+impl Render for ReferencesReport {
+    fn render(&self, format: OutputFormat, mut writer: &mut dyn Write) -> io::Result<()> {
+        let records = self.records.iter().cloned();
+        match format {
+            OutputFormat::Text => {
+                for record in &self.records {
+                    writeln!(
+                        writer,
+                        "{}:{}:{} references {} ({}) defined at {}:{}:{}",
+                        record.ref_file,
+                        record.ref_line,
+                        record.ref_col,
+                        record.symbol,
+                        record.kind,
+                        record.def_file,
+                        record.def_line,
+                        record.def_col
+                    )?;
+                }
+                writer.flush()
+            }
+            OutputFormat::Json => write_ndjson(&mut writer, records),
+            OutputFormat::Csv => write_csv(&mut writer, records),
+        }
+    }
+}
+
+/// Writes calls one at a time in an `OutputFormat`
+///
+/// This lets results be written while they are found, see `stream_call_targets`.
+/// Calls are written as `CallWithTarget::pretty_print` text,
+/// `SerializableCallDefinition` JSON or `ReferenceRecord` CSV.
+pub struct CallWriter<W: Write> {
+    writer: W,
+    format: OutputFormat,
+}
+
+impl<W: Write> CallWriter<W> {
+    /// Returns a writer for `format`, writing the CSV header right away
+    pub fn new(mut writer: W, format: OutputFormat) -> io::Result<Self> {
+        if format == OutputFormat::Csv {
+            writeln!(writer, "{}", CSV_HEADER)?;
+        }
+        Ok(CallWriter { writer, format })
+    }
+
+    /// Writes a call with its definitions
+    pub fn write_call(&mut self, call: &CallWithTarget) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Text => {
+                for line in call.pretty_print() {
+                    writeln!(self.writer, "{}", line)?;
+                }
+            }
+            OutputFormat::Json => {
+                serde_json::to_writer(&mut self.writer, &SerializableCallDefinition::from(call))?;
+                self.writer.write_all(b"\n")?;
+            }
+            OutputFormat::Csv => {
+                for record in ReferenceRecord::from_call(call) {
+                    writeln!(self.writer, "{}", record.csv_line())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a call found without an LSP server, which has no definition
+    pub fn write_call_without_lsp(
+        &mut self,
+        file_path: &Path,
+        call_node: &OwnedCallNode,
+    ) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Text => writeln!(
+                self.writer,
+                "Call {}:{} {}",
+                file_path.display(),
+                ts_point_to_display(call_node.start_position),
+                call_node.text
+            ),
+            OutputFormat::Json => {
+                serde_json::to_writer(
+                    &mut self.writer,
+                    &SerializableCallDefinition::without_definition(file_path, call_node),
+                )?;
+                self.writer.write_all(b"\n")
+            }
+            OutputFormat::Csv => writeln!(
+                self.writer,
+                "{}",
+                ReferenceRecord::from_call_without_lsp(file_path, call_node).csv_line()
+            ),
+        }
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Written with a `CallWriter`
+impl Render for CallAnalysisResults {
+    fn render(&self, format: OutputFormat, writer: &mut dyn Write) -> io::Result<()> {
+        let mut writer = CallWriter::new(writer, format)?;
+        for (file_path, call_node) in &self.calls_without_lsp {
+            writer.write_call_without_lsp(file_path, call_node)?;
+        }
+        for call in &self.calls_with_targets {
+            writer.write_call(call)?;
+        }
+        writer.flush()
+    }
+}
+
+/// The callers of a set of function declarations, see `find_all_callers`
+#[derive(Debug, Clone, Default)]
+pub struct CallersReport {
+    pub declarations: Vec<DeclarationCallers>,
+}
+
+impl Render for CallersReport {
+    fn render(&self, format: OutputFormat, mut writer: &mut dyn Write) -> io::Result<()> {
+        let records = self
+            .declarations
+            .iter()
+            .flat_map(ReferenceRecord::from_callers);
+        match format {
+            OutputFormat::Text => {
+                for declaration in &self.declarations {
+                    writeln!(
+                        writer,
+                        "\n{} ({}:{})",
+                        declaration.name,
+                        declaration.file_path.display(),
+                        ts_point_to_display(declaration.position)
+                    )?;
+                    if declaration.callers.is_empty() {
+                        writeln!(writer, "  No callers")?;
+                    }
+                    for call in &declaration.callers {
+                        for range in &call.from_ranges {
+                            writeln!(
+                                writer,
+                                "  <- {} ({}:{})",
+                                call.from.name,
+                                display_uri(&call.from.uri),
+                                lsp_to_display(range.start)
+                            )?;
+                        }
+                    }
+                }
+                writer.flush()
+            }
+            OutputFormat::Json => write_ndjson(&mut writer, records),
+            OutputFormat::Csv => write_csv(&mut writer, records),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_csv_round_trip() -> Result<()> {
+        let records = vec![
+            ReferenceRecord {
+                symbol: "format(\"a, b\", c)".to_string(),
+                kind: "call".to_string(),
+                ref_file: "src/main.rs".to_string(),
+                ref_line: 2,
+                ref_col: 5,
+                def_file: "/project/src/lib.rs".to_string(),
+                def_line: 10,
+                def_col: 1,
+            },
+            ReferenceRecord {
+                symbol: "new".to_string(),
+                kind: "Method".to_string(),
+                ref_file: "src/my, file.rs".to_string(),
+                ref_line: 1,
+                ref_col: 1,
+                def_file: "src/lib.rs".to_string(),
+                def_line: 3,
+                def_col: 8,
+            },
+        ];
+
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, records.clone().into_iter())?;
+
+        let mut reader = csv::Reader::from_reader(buffer.as_slice());
+        assert_eq!(
+            reader.headers()?.iter().collect::<Vec<_>>().join(","),
+            CSV_HEADER
+        );
+        let read = reader
+            .deserialize()
+            .collect::<std::result::Result<Vec<ReferenceRecord>, _>>()?;
+        assert_eq!(read, records);
+
+        Ok(())
+    }
+
+    fn render_to_string(report: &impl Render, format: OutputFormat) -> Result<String> {
+        let mut buffer = Vec::new();
+        report.render(format, &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    #[test]
+    fn test_render_references_report() -> Result<()> {
+        let record = ReferenceRecord {
+            symbol: "area".to_string(),
+            kind: "Method".to_string(),
+            ref_file: "src/main.rs".to_string(),
+            ref_line: 4,
+            ref_col: 9,
+            def_file: "src/shapes.rs".to_string(),
+            def_line: 12,
+            def_col: 8,
+        };
+        let report = ReferencesReport {
+            records: vec![record.clone()],
+        };
+
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Text)?,
+            "src/main.rs:4:9 references area (Method) defined at src/shapes.rs:12:8\n"
+        );
+        let json = render_to_string(&report, OutputFormat::Json)?;
+        assert_eq!(
+            serde_json::from_str::<ReferenceRecord>(json.trim_end())?,
+            record
+        );
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Csv)?,
+            format!("{CSV_HEADER}\narea,Method,src/main.rs,4,9,src/shapes.rs,12,8\n")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_call_analysis_results() -> Result<()> {
+        let source = "fn main() {\n    foo();\n}";
+        let tree = crate::parser::parse_file_content(source, crate::RustLang)?;
+        let call = crate::parser::get_calls(&tree, crate::RustLang)
+            .next()
+            .unwrap();
+        let location = lsp_types::Location {
+            uri: "file:///project/src/lib.rs".parse()?,
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(2, 7),
+                lsp_types::Position::new(2, 10),
+            ),
+        };
+        let report = CallAnalysisResults {
+            calls_with_targets: vec![CallWithTarget {
+                file_path: "src/main.rs".into(),
+                call_node: crate::call_node::OwnedCallNode::from_node(
+                    call.call_node,
+                    source.as_bytes(),
+                ),
+                caller: None,
+                definition: lsp_types::GotoDefinitionResponse::Scalar(location),
+                count: 1,
+            }],
+            total_calls: 1,
+            lsp_enabled: true,
+            calls_without_lsp: Vec::new(),
+        };
+
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Text)?,
+            "Call src/main.rs:2:5 targets /project/src/lib.rs:3:8\n"
+        );
+        let json = render_to_string(&report, OutputFormat::Json)?;
+        let read = CallWithTarget::read_ndjson(&mut json.as_bytes()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].call_range.start, lsp_types::Position::new(1, 4));
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Csv)?,
+            format!("{CSV_HEADER}\nfoo(),call,src/main.rs,2,5,/project/src/lib.rs,3,8\n")
+        );
+
+        // Calls written one at a time share a single CSV header
+        let mut buffer = Vec::new();
+        let mut writer = CallWriter::new(&mut buffer, OutputFormat::Csv)?;
+        writer.write_call(&report.calls_with_targets[0])?;
+        writer.write_call(&report.calls_with_targets[0])?;
+        writer.flush()?;
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            format!(
+                "{CSV_HEADER}\n{0}\n{0}\n",
+                "foo(),call,src/main.rs,2,5,/project/src/lib.rs,3,8"
+            )
+        );
+
+        // Calls found without an LSP server are written without a definition
+        let report = CallAnalysisResults {
+            calls_without_lsp: vec![(
+                "src/main.rs".into(),
+                report.calls_with_targets[0].call_node.clone(),
+            )],
+            calls_with_targets: Vec::new(),
+            total_calls: 1,
+            lsp_enabled: false,
+        };
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Text)?,
+            "Call src/main.rs:2:5 foo()\n"
+        );
+        let json = render_to_string(&report, OutputFormat::Json)?;
+        assert!(!json.contains("definition"));
+        let read = CallWithTarget::read_ndjson(&mut json.as_bytes()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].definition, None);
+        assert_eq!(read[0].call_range.start, lsp_types::Position::new(1, 4));
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Csv)?,
+            format!("{CSV_HEADER}\nfoo(),call,src/main.rs,2,5,,0,0\n")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_callers_report() -> Result<()> {
+        let call: lsp_types::CallHierarchyIncomingCall = serde_json::from_value(
+            serde_json::json!({
+                "from": {
+                    "name": "main",
+                    "kind": 12,
+                    "uri": "file:///project/src/main.rs",
+                    "range": {"start": {"line": 0, "character": 0}, "end": {"line": 2, "character": 1}},
+                    "selectionRange": {"start": {"line": 0, "character": 3}, "end": {"line": 0, "character": 7}},
+                },
+                "fromRanges": [{"start": {"line": 1, "character": 4}, "end": {"line": 1, "character": 7}}],
+            }),
+        )?;
+        let declaration = |name: &str, callers| DeclarationCallers {
+            file_path: "src/lib.rs".into(),
+            name: name.to_string(),
+            position: tree_sitter::Point::new(2, 7),
+            callers,
+        };
+        let report = CallersReport {
+            declarations: vec![
+                declaration("foo", vec![call]),
+                declaration("bar", Vec::new()),
+            ],
+        };
+
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Text)?,
+            "\nfoo (src/lib.rs:3:8)\n  <- main (/project/src/main.rs:2:5)\n\nbar (src/lib.rs:3:8)\n  No callers\n"
+        );
+        let json = render_to_string(&report, OutputFormat::Json)?;
+        let read = json
+            .lines()
+            .map(serde_json::from_str::<ReferenceRecord>)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].ref_file, "/project/src/main.rs");
+        // A declaration without callers has an unknown reference
+        assert_eq!((read[1].ref_file.as_str(), read[1].ref_line), ("", 0));
+        assert_eq!(
+            render_to_string(&report, OutputFormat::Csv)?,
+            format!(
+                "{CSV_HEADER}\nfoo,call,/project/src/main.rs,2,5,src/lib.rs,3,8\nbar,call,,0,0,src/lib.rs,3,8\n"
+            )
+        );
+
+        Ok(())
+    }
+}
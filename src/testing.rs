@@ -0,0 +1,311 @@
+//! Test utilities for exercising `LspServer` without a real language server.
+//!
+//! `StubServer` runs in a background thread and speaks the LSP wire protocol
+//! over in-memory pipes. It replies to requests with scripted responses, which
+//! makes tests deterministic and independent of installed servers.
+//!
+//! This module is available in the crate's own tests and, for downstream
+//! crates, behind the `test-util` feature.
+
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tree_sitter::Node;
+
+use crate::language::{ErrorRecoveryStrategy, Language};
+use crate::lsp::{LspServer, read_message, request_string};
+
+/// A scripted reply to a request
+#[derive(Debug, Clone)]
+pub enum StubReply {
+    /// Reply with the given `result`
+    Result(Value),
+    /// Reply with an error
+    Error { code: i64, message: String },
+    /// Never reply, e.g. to test timeouts
+    NoReply,
+}
+
+/// An in-process LSP server that replies to requests with scripted responses
+///
+/// Replies are registered per method. When several replies are registered for
+/// the same method they are used in order, and the last one is repeated for any
+/// further requests. Requests without a registered reply get a "method not
+/// found" error. Every message received from the client is recorded.
+#[derive(Debug, Clone)]
+pub struct StubServer {
+    replies: HashMap<String, VecDeque<StubReply>>,
+    /// The methods that still have their default reply from `new`
+    default_replies: HashSet<String>,
+    messages_before_reply: HashMap<String, Vec<Value>>,
+    received: Arc<Mutex<Vec<Value>>>,
+}
+
+impl Default for StubServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StubServer {
+    /// Creates a stub that only knows how to reply to `initialize` and `shutdown`
+    pub fn new() -> Self {
+        let defaults = [
+            ("initialize", json!({ "capabilities": {} })),
+            ("shutdown", Value::Null),
+        ];
+        StubServer {
+            replies: defaults
+                .iter()
+                .map(|(method, result)| {
+                    let reply = StubReply::Result(result.clone());
+                    (method.to_string(), VecDeque::from([reply]))
+                })
+                .collect(),
+            default_replies: defaults
+                .iter()
+                .map(|(method, _)| method.to_string())
+                .collect(),
+            messages_before_reply: HashMap::new(),
+            received: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Replies to requests for `method` with `result`
+    pub fn on_request(self, method: &str, result: Value) -> Self {
+        self.reply_with(method, StubReply::Result(result))
+    }
+
+    /// Replies to requests for `method` with an error
+    pub fn on_request_error(self, method: &str, code: i64, message: &str) -> Self {
+        self.reply_with(
+            method,
+            StubReply::Error {
+                code,
+                message: message.to_string(),
+            },
+        )
+    }
+
+    /// Adds a scripted reply for `method` after any previously added ones
+    pub fn reply_with(mut self, method: &str, reply: StubReply) -> Self {
+        let replies = self.replies.entry(method.to_string()).or_default();
+        // The default replies are replaced rather than queued before custom ones
+        if self.default_replies.remove(method) {
+            replies.clear();
+        }
+        replies.push_back(reply);
+        self
+    }
+
+    /// Sends `message` (a notification or server-initiated request) before
+    /// replying to requests for `method`
+    ///
+    /// For notifications the message is sent when the notification is received.
+    pub fn with_message_before_reply(mut self, method: &str, message: Value) -> Self {
+        self.messages_before_reply
+            .entry(method.to_string())
+            .or_default()
+            .push(message);
+        self
+    }
+
+    /// Returns all messages received from the client so far
+    pub fn received_messages(&self) -> Vec<Value> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Returns the received messages with the given method
+    pub fn received_with_method(&self, method: &str) -> Vec<Value> {
+        self.received_messages()
+            .into_iter()
+            .filter(|message| message["method"] == method)
+            .collect()
+    }
+
+    /// Starts the stub in a background thread and connects an `LspServer` to it
+    ///
+    /// The returned server is not initialized, use `start_and_init` for that.
+    pub fn start<L: Language>(&self, language: L, working_dir: PathBuf) -> LspServer<L> {
+        let (client_reader, server_writer) =
+            std::io::pipe().expect("Failed to create pipe for stub server");
+        let (server_reader, client_writer) =
+            std::io::pipe().expect("Failed to create pipe for stub server");
+
+        let stub = self.clone();
+        std::thread::spawn(move || stub.serve(server_reader, server_writer));
+
+        LspServer::from_streams(language, working_dir, client_reader, client_writer)
+    }
+
+    /// Starts the stub and performs the initialization handshake
+    pub fn start_and_init<L: Language>(
+        &self,
+        language: L,
+        working_dir: PathBuf,
+    ) -> anyhow::Result<LspServer<L>> {
+        let mut server = self.start(language, working_dir);
+        server.initialize()?;
+        Ok(server)
+    }
+
+    fn serve(mut self, reader: std::io::PipeReader, mut writer: std::io::PipeWriter) {
+        let mut reader = BufReader::new(reader);
+        while let Ok(Some(buffer)) = read_message(&mut reader) {
+            let Ok(message) = serde_json::from_slice::<Value>(&buffer) else {
+                continue;
+            };
+            self.received.lock().unwrap().push(message.clone());
+
+            // Responses to server-initiated requests need no reply
+            let Some(method) = message["method"].as_str() else {
+                continue;
+            };
+
+            for before in self
+                .messages_before_reply
+                .get(method)
+                .cloned()
+                .unwrap_or_default()
+            {
+                if write_message(&mut writer, &before).is_err() {
+                    return;
+                }
+            }
+
+            // Notifications need no reply
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+
+            let reply = match self.replies.get_mut(method) {
+                Some(replies) if replies.len() > 1 => replies.pop_front(),
+                Some(replies) => replies.front().cloned(),
+                None => None,
+            };
+            let response = match reply {
+                Some(StubReply::Result(result)) => {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+                }
+                Some(StubReply::Error { code, message }) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message }
+                }),
+                Some(StubReply::NoReply) => continue,
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {}", method) }
+                }),
+            };
+            if write_message(&mut writer, &response).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> anyhow::Result<()> {
+    writer.write_all(request_string(message)?.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A language that treats every node of a single kind as a call
+///
+/// It reuses the Rust grammar for parsing, so `call_kind` must be a Rust node
+/// kind. This makes it possible to test the tree traversal independently of
+/// the call detection of the real languages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MockLang {
+    pub call_kind: &'static str,
+    pub error_node_strategy: ErrorRecoveryStrategy,
+}
+
+impl MockLang {
+    /// Returns a language with the default `ErrorRecoveryStrategy`
+    pub fn new(call_kind: &'static str) -> Self {
+        MockLang {
+            call_kind,
+            error_node_strategy: ErrorRecoveryStrategy::default(),
+        }
+    }
+}
+
+impl Language for MockLang {
+    fn cli_name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn file_pattern(&self) -> &'static str {
+        r"\.mock$"
+    }
+
+    fn extensions(&self) -> &'static str {
+        ".mock"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn lsp_server_command(&self) -> (&'static str, Vec<String>) {
+        ("mock-language-server", vec![])
+    }
+
+    fn tree_sitter_language(&self) -> tree_sitter::Language {
+        tree_sitter_rust::LANGUAGE.into()
+    }
+
+    fn call_node_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn error_node_strategy(&self) -> ErrorRecoveryStrategy {
+        self.error_node_strategy
+    }
+
+    fn find_call<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        (node.kind() == self.call_kind).then_some(node)
+    }
+
+    fn find_function_declaration<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
+        None
+    }
+
+    fn call_hierarchy_target<'a>(&self, _node: Node<'a>) -> Option<Node<'a>> {
+        None
+    }
+}
+
+impl std::fmt::Display for MockLang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_reply_with_replaces_default_reply_once() -> Result<()> {
+        let stub = StubServer::new()
+            .on_request_error("shutdown", -32603, "Busy")
+            .on_request("shutdown", Value::Null);
+        let mut server = stub.start_and_init(crate::RustLang, PathBuf::from("/project"))?;
+
+        // Both custom replies are queued, in order, instead of the default
+        let error = server
+            .request::<lsp_types::request::Shutdown>(())
+            .unwrap_err();
+        assert!(error.to_string().contains("Busy"), "{error}");
+        server.request::<lsp_types::request::Shutdown>(())?;
+
+        Ok(())
+    }
+}
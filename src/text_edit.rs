@@ -0,0 +1,85 @@
+//! Applying LSP text edits to source code.
+
+use anyhow::Result;
+use lsp_types::{PositionEncodingKind, TextEdit};
+
+use crate::location::lsp_range_to_byte_offset;
+
+/// Returns `source` with `edits` applied
+///
+/// The ranges of the edits refer to the original `source` and are interpreted
+/// in the given position encoding. As required by LSP, edits may not overlap,
+/// and edits inserting at the same position are applied in the given order.
+/// Positions inside a multi-byte character are an error.
+pub fn apply_text_edits(
+    source: &str,
+    edits: &[TextEdit],
+    encoding: &PositionEncodingKind,
+) -> Result<String> {
+    let mut byte_edits = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let start = lsp_range_to_byte_offset(source, edit.range.start, encoding);
+        let end = lsp_range_to_byte_offset(source, edit.range.end, encoding).max(start);
+        for (offset, position) in [(start, edit.range.start), (end, edit.range.end)] {
+            if !source.is_char_boundary(offset) {
+                anyhow::bail!(
+                    "Text edit position {}:{} is inside a character",
+                    position.line,
+                    position.character
+                );
+            }
+        }
+        byte_edits.push((start, end, edit.new_text.as_str()));
+    }
+    // The sort is stable which keeps inserts at the same position in order
+    byte_edits.sort_by_key(|&(start, end, _)| (start, end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut copied_until = 0;
+    for (start, end, new_text) in byte_edits {
+        if start < copied_until {
+            anyhow::bail!("Overlapping text edits at byte offset {}", start);
+        }
+        result.push_str(&source[copied_until..start]);
+        result.push_str(new_text);
+        copied_until = end;
+    }
+    result.push_str(&source[copied_until..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_text_edits() -> Result<()> {
+        let source = "let ÿ = foo();\nfoo(ÿ);\n";
+        // Given out of order, and in UTF-16 where "ÿ" is a single code unit
+        let edits = [
+            edit((1, 0), (1, 3), "bar"),
+            edit((0, 8), (0, 11), "bar"),
+            edit((0, 0), (0, 0), "// "),
+            edit((0, 0), (0, 0), "renamed\n"),
+        ];
+        let result = apply_text_edits(source, &edits, &PositionEncodingKind::UTF16)?;
+        assert_eq!(result, "// renamed\nlet ÿ = bar();\nbar(ÿ);\n");
+
+        let overlapping = [edit((0, 0), (0, 5), "a"), edit((0, 3), (0, 6), "b")];
+        assert!(apply_text_edits(source, &overlapping, &PositionEncodingKind::UTF16).is_err());
+
+        // In UTF-8 "ÿ" takes two bytes, the second of which is inside the character
+        let inside_character = [edit((0, 5), (0, 6), "x")];
+        assert!(apply_text_edits(source, &inside_character, &PositionEncodingKind::UTF8).is_err());
+
+        Ok(())
+    }
+}
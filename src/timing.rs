@@ -0,0 +1,129 @@
+//! Timing of the phases of an analysis for benchmarking LSP servers.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A timed phase of an analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    /// Requesting the document symbols of a file
+    Symbols,
+    /// Preparing the call hierarchy of a symbol
+    Prepare,
+    /// Requesting the incoming calls of a symbol
+    Incoming,
+    /// Requesting the outgoing calls of a symbol
+    Outgoing,
+}
+
+/// A single timed request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    /// The file the request was made for
+    pub file: PathBuf,
+    pub phase: Phase,
+    pub duration: Duration,
+}
+
+/// The durations of all the timed requests of an analysis
+#[derive(Debug, Clone, Default)]
+pub struct TimingReport {
+    /// The timings in the order they were recorded
+    pub timings: Vec<Timing>,
+}
+
+impl TimingReport {
+    pub fn record(&mut self, file: &Path, phase: Phase, duration: Duration) {
+        self.timings.push(Timing {
+            file: file.to_path_buf(),
+            phase,
+            duration,
+        });
+    }
+
+    /// Returns the durations recorded for `phase`, sorted from fastest to slowest
+    pub fn durations(&self, phase: Phase) -> Vec<Duration> {
+        let mut durations: Vec<_> = self
+            .timings
+            .iter()
+            .filter(|timing| timing.phase == phase)
+            .map(|timing| timing.duration)
+            .collect();
+        durations.sort();
+        durations
+    }
+
+    /// Returns the total time spent in `phase`
+    pub fn total(&self, phase: Phase) -> Duration {
+        self.durations(phase).iter().sum()
+    }
+
+    /// Returns the total time spent on each file across all phases
+    pub fn file_totals(&self) -> BTreeMap<&Path, Duration> {
+        let mut totals = BTreeMap::new();
+        for timing in &self.timings {
+            *totals.entry(timing.file.as_path()).or_default() += timing.duration;
+        }
+        totals
+    }
+
+    /// Returns the `p`th percentile (0-100) of the durations of `phase`
+    pub fn percentile(&self, phase: Phase, p: f64) -> Option<Duration> {
+        percentile(&self.durations(phase), p)
+    }
+
+    /// Returns the median duration of `phase`
+    pub fn p50(&self, phase: Phase) -> Option<Duration> {
+        self.percentile(phase, 50.0)
+    }
+
+    pub fn p95(&self, phase: Phase) -> Option<Duration> {
+        self.percentile(phase, 95.0)
+    }
+}
+
+/// Returns the `p`th percentile (0-100) of sorted durations using the nearest-rank method
+///
+/// Returns None if there are no durations.
+pub fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.saturating_sub(1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut report = TimingReport::default();
+        // Recorded out of order, 1ms to 100ms
+        for ms in (1..=100).rev() {
+            let file = if ms % 2 == 0 { "even.rs" } else { "odd.rs" };
+            report.record(Path::new(file), Phase::Prepare, Duration::from_millis(ms));
+        }
+        report.record(Path::new("odd.rs"), Phase::Symbols, Duration::from_secs(1));
+
+        assert_eq!(report.p50(Phase::Prepare), Some(Duration::from_millis(50)));
+        assert_eq!(report.p95(Phase::Prepare), Some(Duration::from_millis(95)));
+        assert_eq!(
+            report.percentile(Phase::Prepare, 100.0),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            report.percentile(Phase::Prepare, 0.0),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(report.p50(Phase::Symbols), Some(Duration::from_secs(1)));
+        assert_eq!(report.p50(Phase::Outgoing), None);
+
+        assert_eq!(report.total(Phase::Prepare), Duration::from_millis(5050));
+        let totals = report.file_totals();
+        assert_eq!(totals[Path::new("even.rs")], Duration::from_millis(2550));
+        assert_eq!(totals[Path::new("odd.rs")], Duration::from_millis(3500));
+    }
+}
@@ -0,0 +1,232 @@
+//! Conversion between file paths and `file://` URIs.
+//!
+//! The conversion follows RFC 8089: everything except unreserved characters
+//! and `/` is percent-encoded. Windows paths with a drive letter become
+//! `file:///C:/...` and UNC paths `\\server\share` become `file://server/share`.
+
+use anyhow::Result;
+use lsp_types::Uri;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Returns the `file://` URI of an absolute path
+///
+/// Relative paths are an error. Windows paths with a drive letter or a UNC
+/// prefix count as absolute on every platform.
+pub fn to_file_uri(path: &Path) -> Result<Uri> {
+    let bytes = strip_verbatim_prefix(path.as_os_str().as_encoded_bytes());
+    let bytes = bytes.as_ref();
+    if !path.is_absolute() && !has_drive_letter(bytes) && windows_unc_path(bytes).is_none() {
+        anyhow::bail!(
+            "Can not create a file URI for the relative path {}",
+            path.display()
+        );
+    }
+    let mut uri = String::from("file://");
+
+    let path_bytes: Vec<u8> = if let Some(unc) = windows_unc_path(bytes) {
+        // The server becomes the authority of the URI
+        let host_end = unc.iter().position(|&b| b == b'/').unwrap_or(unc.len());
+        let (host, rest) = unc.split_at(host_end);
+        encode_into(&mut uri, host);
+        rest.to_vec()
+    } else if has_drive_letter(bytes) {
+        let mut path_bytes = vec![b'/'];
+        path_bytes.extend(bytes.iter().map(|&b| if b == b'\\' { b'/' } else { b }));
+        path_bytes
+    } else {
+        bytes.to_vec()
+    };
+
+    // The colon of a drive letter is kept as is, like most clients do
+    if has_drive_letter(path_bytes.get(1..).unwrap_or_default()) {
+        uri.push_str(std::str::from_utf8(&path_bytes[..3])?);
+        encode_into(&mut uri, &path_bytes[3..]);
+    } else {
+        encode_into(&mut uri, &path_bytes);
+    }
+    Ok(uri.parse()?)
+}
+
+/// Returns the file path of a `file://` URI, decoding any percent-encoding
+///
+/// Drive letter paths lose the leading slash of the URI path and keep forward
+/// slashes, which Windows accepts as separators.
+pub fn from_file_uri(uri: &Uri) -> Result<PathBuf> {
+    if uri.scheme().map(|scheme| scheme.as_str()) != Some("file") {
+        anyhow::bail!("Not a file URI: {}", uri.as_str());
+    }
+    let mut path = decode(uri.path().as_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid UTF-8 in URI: {}", uri.as_str()))?;
+
+    if has_drive_letter(path.as_bytes().get(1..).unwrap_or_default()) {
+        path.remove(0);
+    }
+    let host = uri
+        .authority()
+        .map(|authority| authority.host().as_str())
+        .unwrap_or_default();
+    if !host.is_empty() && host != "localhost" {
+        path = format!("//{}{}", host, path);
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Returns the decoded path of a `file://` URI for display, or the whole URI for other schemes
+pub fn display_uri(uri: &Uri) -> String {
+    from_file_uri(uri)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| uri.as_str().to_string())
+}
+
+/// Turns the verbatim paths returned by `canonicalize` on Windows, like
+/// `\\?\C:\...` and `\\?\UNC\server\share`, into regular paths
+fn strip_verbatim_prefix(path: &[u8]) -> Cow<'_, [u8]> {
+    if let Some(unc) = path.strip_prefix(br"\\?\UNC\") {
+        Cow::Owned([br"\\".as_slice(), unc].concat())
+    } else {
+        Cow::Borrowed(path.strip_prefix(br"\\?\").unwrap_or(path))
+    }
+}
+
+/// Returns true if `path` starts with a drive letter like `C:\` or `C:/`
+fn has_drive_letter(path: &[u8]) -> bool {
+    matches!(path, [letter, b':', b'/' | b'\\', ..] if letter.is_ascii_alphabetic())
+}
+
+/// Returns the part after `\\` of a UNC path, with forward slashes
+fn windows_unc_path(path: &[u8]) -> Option<Vec<u8>> {
+    let rest = path.strip_prefix(b"\\\\")?;
+    Some(
+        rest.iter()
+            .map(|&b| if b == b'\\' { b'/' } else { b })
+            .collect(),
+    )
+}
+
+fn encode_into(uri: &mut String, bytes: &[u8]) {
+    for &byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+}
+
+/// Decodes percent-encoded bytes, keeping `%` signs that are not followed by two hex digits
+fn decode(encoded: &str) -> Option<String> {
+    let encoded = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_paths() -> Result<()> {
+        let path = Path::new("/project/my file #1/what?.rs");
+        let uri = to_file_uri(path)?;
+        assert_eq!(uri.as_str(), "file:///project/my%20file%20%231/what%3F.rs");
+        assert_eq!(from_file_uri(&uri)?, path);
+        assert_eq!(display_uri(&uri), "/project/my file #1/what?.rs");
+
+        // An encoded percent sign is decoded, a lone one is kept as is
+        let uri: Uri = "file:///project/100%25.rs".parse()?;
+        assert_eq!(from_file_uri(&uri)?, Path::new("/project/100%.rs"));
+        assert_eq!(
+            decode("/project/100%.rs").as_deref(),
+            Some("/project/100%.rs")
+        );
+        assert_eq!(decode("/project/%4.rs").as_deref(), Some("/project/%4.rs"));
+        let uri: Uri = "file://localhost/project/a.rs".parse()?;
+        assert_eq!(from_file_uri(&uri)?, Path::new("/project/a.rs"));
+
+        let uri: Uri = "untitled:Untitled-1".parse()?;
+        assert!(from_file_uri(&uri).is_err());
+        assert_eq!(display_uri(&uri), "untitled:Untitled-1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_paths_are_rejected() {
+        for path in ["src/main.rs", "./main.rs", "../main.rs", ""] {
+            assert!(to_file_uri(Path::new(path)).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_unicode_paths() -> Result<()> {
+        let path = Path::new("/project/ünïcode/日本.rs");
+        let uri = to_file_uri(path)?;
+        assert_eq!(
+            uri.as_str(),
+            "file:///project/%C3%BCn%C3%AFcode/%E6%97%A5%E6%9C%AC.rs"
+        );
+        assert_eq!(from_file_uri(&uri)?, path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_windows_paths() -> Result<()> {
+        let uri = to_file_uri(Path::new(r"C:\Users\me\my project\main.rs"))?;
+        assert_eq!(uri.as_str(), "file:///C:/Users/me/my%20project/main.rs");
+        assert_eq!(
+            from_file_uri(&uri)?,
+            Path::new("C:/Users/me/my project/main.rs")
+        );
+
+        // VS Code encodes the colon of the drive letter
+        let uri: Uri = "file:///c%3A/Users/me/main.rs".parse()?;
+        assert_eq!(from_file_uri(&uri)?, Path::new("c:/Users/me/main.rs"));
+
+        let uri = to_file_uri(Path::new(r"\\?\C:\Users\me\main.rs"))?;
+        assert_eq!(uri.as_str(), "file:///C:/Users/me/main.rs");
+
+        let uri = to_file_uri(Path::new(r"\\?\UNC\server\share\src\main.rs"))?;
+        assert_eq!(uri.as_str(), "file://server/share/src/main.rs");
+
+        let uri = to_file_uri(Path::new(r"\\server\share\src\main.rs"))?;
+        assert_eq!(uri.as_str(), "file://server/share/src/main.rs");
+        assert_eq!(
+            from_file_uri(&uri)?,
+            Path::new("//server/share/src/main.rs")
+        );
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_canonical_windows_path() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().canonicalize()?;
+        let uri = to_file_uri(&path)?;
+        assert!(uri.as_str().starts_with("file:///"));
+        assert_eq!(from_file_uri(&uri)?.canonicalize()?, path);
+
+        Ok(())
+    }
+}